@@ -1,13 +1,13 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 use tauri::{
     tray::{TrayIconBuilder, TrayIconEvent},
     menu::{Menu, MenuItem, PredefinedMenuItem},
-    Manager,
+    Emitter, Manager, RunEvent,
 };
 
 mod modules;
@@ -15,9 +15,26 @@ use modules::Module;
 use modules::{
     process_controller::{ProcessController, ProcessInfo},
     clipboard_history::{ClipboardHistory, ClipboardItem},
-    dynamic_split::DynamicSplit,
+    clipboard_archive::{ExportFilter, ImportSummary},
+    clipboard_sync::{PairingInfo, PendingPairing, SyncPeerInfo, SyncReport},
+    dynamic_split::{DynamicSplit, WorkspaceReport, WorkspaceRequest},
+    focus_modes::{FocusModeWindow, FocusRule},
+    layout_engine::{Divider, Layout},
+    monitors::MonitorInfo,
+    window_ops::WindowInfo,
+    window_rules::LayoutRule,
+    workspace_snapshots::{RestoreReport, WorkspaceSnapshot},
+    tiling::TilingAction,
     taskbar_customizer::TaskbarCustomizer,
+    actions::{self, Action, ActionContext},
+    app_launcher::{self, AppEntry},
+    macros::{self, Macro},
     mouse_action_mapper::MouseActionMapper,
+    mouse_bindings::{MouseBinding, MouseProfile},
+    mouse_remap,
+    screenshots::{self, CaptureTarget},
+    screenshot_pipeline::{self, PostAction},
+    annotations::Annotation,
 };
 
 // Global state for process controller
@@ -29,6 +46,59 @@ struct AppState {
     mouse_action_mapper: Mutex<MouseActionMapper>,
 }
 
+// Actions reach into other modules one lock at a time, so a binding or
+// gesture can run them without holding the mouse module's lock
+impl ActionContext for AppState {
+    fn take_screenshot(&self, target: &CaptureTarget, pipeline: Option<&[PostAction]>) -> anyhow::Result<Option<PathBuf>> {
+        let options = {
+            let mam = self.mouse_action_mapper.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            mam.screenshot_options()
+        };
+        let steps = pipeline.unwrap_or(&options.pipeline);
+        let add_to_history = |path: &Path| {
+            let mut ch = self.clipboard_history.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            ch.add_image_item(path)
+        };
+        screenshot_pipeline::take_screenshot(target, steps, &options, &add_to_history)
+    }
+
+    fn apply_layout(&self, layout: &str, zone: Option<usize>) -> anyhow::Result<()> {
+        let mut ds = self.dynamic_split.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        match zone {
+            Some(zone) => ds.apply_layout_zone(layout, zone),
+            None => ds.apply_layout(layout),
+        }
+    }
+
+    fn cycle_layout(&self) -> anyhow::Result<()> {
+        let mut ds = self.dynamic_split.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        ds.cycle_layout()
+    }
+
+    fn toggle_taskbar(&self) -> anyhow::Result<()> {
+        let mut tc = self.taskbar_customizer.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        tc.toggle_taskbar()
+    }
+
+    fn copy_clipboard_item(&self, id: u64) -> anyhow::Result<()> {
+        let mut ch = self.clipboard_history.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        ch.copy_to_clipboard(id)
+    }
+
+    fn run_macro(&self, name: &str) -> anyhow::Result<()> {
+        let (recorded, abort) = {
+            let mam = self.mouse_action_mapper.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            mam.macro_for_playback(name)?
+        };
+        macros::play(&recorded, abort)
+    }
+
+    fn open_clipboard_history(&self) -> anyhow::Result<()> {
+        let ch = self.clipboard_history.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        ch.open_quick_paste()
+    }
+}
+
 // Configuration structures matching the frontend types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModuleConfig {
@@ -52,6 +122,18 @@ pub struct ModulesConfig {
     pub clipboard_history: ModuleConfig,
 }
 
+impl ModulesConfig {
+    fn sections_mut(&mut self) -> [(&'static str, &mut ModuleConfig); 5] {
+        [
+            ("dynamic_split", &mut self.dynamic_split),
+            ("taskbar_customizer", &mut self.taskbar_customizer),
+            ("mouse_action_mapper", &mut self.mouse_action_mapper),
+            ("process_controller", &mut self.process_controller),
+            ("clipboard_history", &mut self.clipboard_history),
+        ]
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UiConfig {
     pub theme: String,
@@ -62,6 +144,26 @@ impl Default for WinShaperConfig {
     fn default() -> Self {
         let mut dynamic_split_settings = HashMap::new();
         dynamic_split_settings.insert("layouts".to_string(), serde_json::json!(["60-40", "50-50"]));
+        dynamic_split_settings.insert("monitor_layouts".to_string(), serde_json::json!({}));
+        dynamic_split_settings.insert("rules".to_string(), serde_json::json!([]));
+        dynamic_split_settings.insert("custom_layouts".to_string(), serde_json::json!([]));
+        dynamic_split_settings.insert("focus_rules".to_string(), serde_json::json!([]));
+        dynamic_split_settings.insert("auto_restore_workspaces".to_string(), serde_json::json!(true));
+        dynamic_split_settings.insert("tiling_enabled".to_string(), serde_json::json!(false));
+        dynamic_split_settings.insert("tiling_gap".to_string(), serde_json::json!(8));
+        dynamic_split_settings.insert("tiling_floating".to_string(), serde_json::json!([{ "class_name": "#32770" }]));
+        dynamic_split_settings.insert(
+            "tiling_hotkeys".to_string(),
+            serde_json::Value::Object(
+                TilingAction::all()
+                    .into_iter()
+                    .map(|action| (action.key(), serde_json::json!(action.default_hotkey())))
+                    .collect(),
+            ),
+        );
+        dynamic_split_settings.insert("drag_snap_enabled".to_string(), serde_json::json!(true));
+        dynamic_split_settings.insert("drag_snap_modifier".to_string(), serde_json::json!("none"));
+        dynamic_split_settings.insert("drag_span_modifier".to_string(), serde_json::json!("ctrl"));
         
         let mut taskbar_settings = HashMap::new();
         taskbar_settings.insert("theme".to_string(), serde_json::json!("dark"));
@@ -69,6 +171,26 @@ impl Default for WinShaperConfig {
         
         let mut mouse_settings = HashMap::new();
         mouse_settings.insert("sensitivity".to_string(), serde_json::json!(50));
+        mouse_settings.insert(
+            "bindings".to_string(),
+            serde_json::json!([{ "input": "middle_click", "action": { "type": "screenshot" } }]),
+        );
+        mouse_settings.insert("profiles".to_string(), serde_json::json!([]));
+        mouse_settings.insert("remaps".to_string(), serde_json::json!([]));
+        mouse_settings.insert("chords".to_string(), serde_json::json!([]));
+        mouse_settings.insert("wheel".to_string(), serde_json::Value::Null);
+        mouse_settings.insert("hot_corners".to_string(), serde_json::json!([]));
+        mouse_settings.insert("hot_corner_dwell_ms".to_string(), serde_json::json!(300));
+        mouse_settings.insert("hot_corner_size".to_string(), serde_json::json!(8));
+        mouse_settings.insert("hot_corners_in_fullscreen".to_string(), serde_json::json!(false));
+        mouse_settings.insert("gesture_button".to_string(), serde_json::Value::Null);
+        mouse_settings.insert("gestures".to_string(), serde_json::json!({}));
+        mouse_settings.insert("custom_gestures".to_string(), serde_json::json!([]));
+        mouse_settings.insert("screenshot_folder".to_string(), serde_json::Value::Null);
+        mouse_settings.insert("screenshot_filename".to_string(), serde_json::json!(screenshots::DEFAULT_FILENAME_TEMPLATE));
+        mouse_settings.insert("screenshot_pipeline".to_string(), serde_json::json!([{ "type": "save" }]));
+        mouse_settings.insert("macros".to_string(), serde_json::json!([]));
+        mouse_settings.insert("macro_hotkey".to_string(), serde_json::json!("Ctrl+Shift+F12"));
         
         let mut process_settings = HashMap::new();
         process_settings.insert("threshold_cpu".to_string(), serde_json::json!(80));
@@ -77,6 +199,11 @@ impl Default for WinShaperConfig {
         let mut clipboard_settings = HashMap::new();
         clipboard_settings.insert("max_items".to_string(), serde_json::json!(200));
         clipboard_settings.insert("expiry_days".to_string(), serde_json::json!(30));
+        clipboard_settings.insert("max_total_mb".to_string(), serde_json::json!(50));
+        clipboard_settings.insert("sync_enabled".to_string(), serde_json::json!(false));
+        clipboard_settings.insert("sync_port".to_string(), serde_json::json!(47810));
+        clipboard_settings.insert("quick_paste_hotkey".to_string(), serde_json::json!("Ctrl+Shift+V"));
+        clipboard_settings.insert("quick_paste_limit".to_string(), serde_json::json!(9));
 
         Self {
             modules: ModulesConfig {
@@ -109,54 +236,97 @@ fn get_config_path() -> anyhow::Result<PathBuf> {
     Ok(winshaper_dir.join("config.json"))
 }
 
-// Tauri commands
-#[tauri::command]
-async fn load_config() -> Result<WinShaperConfig, String> {
-    match get_config_path() {
-        Ok(config_path) => {
-            if config_path.exists() {
-                match fs::read_to_string(&config_path) {
-                    Ok(content) => {
-                        match serde_json::from_str::<WinShaperConfig>(&content) {
-                            Ok(config) => Ok(config),
-                            Err(_) => {
-                                // If config is corrupted, return default
-                                Ok(WinShaperConfig::default())
-                            }
-                        }
-                    }
-                    Err(_) => Ok(WinShaperConfig::default()),
-                }
-            } else {
-                // First time run, return default config
-                Ok(WinShaperConfig::default())
-            }
+// Read the saved configuration. A missing or corrupted file gives the default config.
+fn read_config() -> anyhow::Result<WinShaperConfig> {
+    let config_path = get_config_path()?;
+    let config = fs::read_to_string(&config_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    Ok(config)
+}
+
+fn write_config(config: &WinShaperConfig) -> anyhow::Result<()> {
+    let config_path = get_config_path()?;
+    fs::write(&config_path, serde_json::to_string_pretty(config)?)?;
+    log::info!("Configuration saved to {:?}", config_path);
+    Ok(())
+}
+
+impl AppState {
+    // Each module is locked on its own, so this never waits on two locks at once
+    fn with_module<T>(&self, module_name: &str, f: impl FnOnce(&mut dyn Module) -> T) -> Result<T, String> {
+        match module_name {
+            "dynamic_split" => Ok(f(&mut *self.dynamic_split.lock().map_err(|e| e.to_string())?)),
+            "taskbar_customizer" => Ok(f(&mut *self.taskbar_customizer.lock().map_err(|e| e.to_string())?)),
+            "mouse_action_mapper" => Ok(f(&mut *self.mouse_action_mapper.lock().map_err(|e| e.to_string())?)),
+            "process_controller" => Ok(f(&mut *self.process_controller.lock().map_err(|e| e.to_string())?)),
+            "clipboard_history" => Ok(f(&mut *self.clipboard_history.lock().map_err(|e| e.to_string())?)),
+            _ => Err(format!("Unknown module: {}", module_name)),
         }
-        Err(e) => Err(format!("Failed to get config path: {}", e)),
     }
-}
 
-#[tauri::command]
-async fn save_config(config: WinShaperConfig) -> Result<(), String> {
-    match get_config_path() {
-        Ok(config_path) => {
-            match serde_json::to_string_pretty(&config) {
-                Ok(json) => {
-                    match fs::write(&config_path, json) {
-                        Ok(_) => {
-                            log::info!("Configuration saved to {:?}", config_path);
-                            Ok(())
-                        }
-                        Err(e) => Err(format!("Failed to write config file: {}", e)),
-                    }
-                }
-                Err(e) => Err(format!("Failed to serialize config: {}", e)),
+    // Hand each module its saved settings section
+    fn load_module_settings(&self, config: &mut WinShaperConfig) {
+        for (name, section) in config.modules.sections_mut() {
+            let settings = std::mem::take(&mut section.settings);
+            match self.with_module(name, |module| module.update_settings(settings)) {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::warn!("Ignoring saved {} settings: {}", name, e),
+                Err(e) => log::warn!("Could not load {} settings: {}", name, e),
             }
         }
-        Err(e) => Err(format!("Failed to get config path: {}", e)),
+    }
+
+    // Modules own their settings; the frontend only switches modules on and
+    // off. Keys a module does not report are kept as they were.
+    fn collect_module_settings(&self, config: &mut WinShaperConfig) -> Result<(), String> {
+        for (name, section) in config.modules.sections_mut() {
+            section.settings.extend(self.with_module(name, |module| module.get_settings())?);
+        }
+        Ok(())
+    }
+
+    // Write the live module settings to the config file
+    fn save_module_settings(&self) -> Result<(), String> {
+        let mut config = read_config().map_err(|e| format!("Failed to get config path: {}", e))?;
+        self.collect_module_settings(&mut config)?;
+        write_config(&config).map_err(|e| format!("Failed to save config: {}", e))
     }
 }
 
+// Tauri commands
+#[tauri::command]
+async fn load_config(state: tauri::State<'_, AppState>) -> Result<WinShaperConfig, String> {
+    let mut config = read_config().map_err(|e| format!("Failed to get config path: {}", e))?;
+    state.collect_module_settings(&mut config)?;
+    Ok(config)
+}
+
+#[tauri::command]
+async fn save_config(mut config: WinShaperConfig, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state.collect_module_settings(&mut config)?;
+    write_config(&config).map_err(|e| format!("Failed to save config: {}", e))
+}
+
+#[tauri::command]
+async fn get_module_settings(module_name: String, state: tauri::State<'_, AppState>) -> Result<HashMap<String, serde_json::Value>, String> {
+    state.with_module(&module_name, |module| module.get_settings())
+}
+
+// Keys left out keep their current values; nothing changes if any key is invalid
+#[tauri::command]
+async fn update_module_settings(
+    module_name: String,
+    settings: HashMap<String, serde_json::Value>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .with_module(&module_name, |module| module.update_settings(settings))?
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()
+}
+
 #[tauri::command]
 async fn get_system_info() -> Result<HashMap<String, serde_json::Value>, String> {
     let mut info = HashMap::new();
@@ -279,9 +449,50 @@ async fn search_clipboard(query: String, state: tauri::State<'_, AppState>) -> R
 }
 
 #[tauri::command]
-async fn copy_clipboard_item(id: u64, state: tauri::State<'_, AppState>) -> Result<(), String> {
+async fn copy_clipboard_item(id: u64, inputs: Option<HashMap<String, String>>, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.copy_to_clipboard_with_inputs(id, &inputs.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_clipboard_item(id: u64, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.remove_item(id).map_err(|e| e.to_string())
+}
+
+// Snippet commands
+#[tauri::command]
+async fn get_snippets(state: tauri::State<'_, AppState>) -> Result<Vec<ClipboardItem>, String> {
+    let clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.get_snippets().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn add_snippet(template: String, abbreviation: Option<String>, state: tauri::State<'_, AppState>) -> Result<ClipboardItem, String> {
+    let mut clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.add_snippet(template, abbreviation).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn update_snippet(id: u64, template: String, abbreviation: Option<String>, state: tauri::State<'_, AppState>) -> Result<ClipboardItem, String> {
+    let mut clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.update_snippet(id, template, abbreviation).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_snippet_inputs(id: u64, state: tauri::State<'_, AppState>) -> Result<Vec<String>, String> {
+    let clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.snippet_inputs(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn copy_snippet_by_abbreviation(abbreviation: String, inputs: Option<HashMap<String, String>>, state: tauri::State<'_, AppState>) -> Result<(), String> {
     let mut clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
-    clipboard.copy_to_clipboard(id).map_err(|e| e.to_string())
+    let snippet = clipboard
+        .find_snippet_by_abbreviation(&abbreviation)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("No snippet with abbreviation '{}'", abbreviation))?;
+    clipboard.copy_to_clipboard_with_inputs(snippet.id, &inputs.unwrap_or_default()).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -297,9 +508,406 @@ async fn check_clipboard(state: tauri::State<'_, AppState>) -> Result<(), String
 }
 
 #[tauri::command]
-async fn apply_window_layout(layout: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+async fn pin_clipboard_item(id: u64, pinned: bool, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.set_pinned(id, pinned).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn export_clipboard_history(path: String, filter: Option<ExportFilter>, state: tauri::State<'_, AppState>) -> Result<usize, String> {
+    let clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.export_archive(Path::new(&path), &filter.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn import_clipboard_history(path: String, state: tauri::State<'_, AppState>) -> Result<ImportSummary, String> {
+    let mut clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.import_archive(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn begin_clipboard_pairing(state: tauri::State<'_, AppState>) -> Result<PairingInfo, String> {
+    let clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.begin_sync_pairing().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn pair_clipboard_peer(address: String, code: String, state: tauri::State<'_, AppState>) -> Result<PendingPairing, String> {
+    // Pairing talks to the peer, so don't hold the history lock meanwhile
+    let client = state
+        .clipboard_history
+        .lock()
+        .map_err(|e| e.to_string())?
+        .sync_client()
+        .map_err(|e| e.to_string())?;
+    client.pair_with(&address, &code).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_clipboard_pairing_requests(state: tauri::State<'_, AppState>) -> Result<Vec<PendingPairing>, String> {
+    let clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.pending_sync_pairings().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn confirm_clipboard_pairing(instance_id: String, state: tauri::State<'_, AppState>) -> Result<SyncPeerInfo, String> {
+    let clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.confirm_sync_pairing(&instance_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn reject_clipboard_pairing(instance_id: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.reject_sync_pairing(&instance_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn unpair_clipboard_peer(instance_id: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.unpair_sync_peer(&instance_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_clipboard_sync_peers(state: tauri::State<'_, AppState>) -> Result<Vec<SyncPeerInfo>, String> {
+    let clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.get_sync_peers().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn sync_clipboard_now(state: tauri::State<'_, AppState>) -> Result<SyncReport, String> {
+    let (client, local) = {
+        let clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+        (
+            clipboard.sync_client().map_err(|e| e.to_string())?,
+            clipboard.get_history().map_err(|e| e.to_string())?,
+        )
+    };
+    let (report, received) = client.sync_now(&local).map_err(|e| e.to_string())?;
+
+    let mut clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.merge_synced(received).map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+const QUICK_PASTE_WINDOW: &str = "quick_paste";
+
+// Show (creating on first use) the always-on-top quick paste picker
+fn show_quick_paste(app: &tauri::AppHandle) {
+    let window = match app.get_webview_window(QUICK_PASTE_WINDOW) {
+        Some(window) => window,
+        None => {
+            let url = tauri::WebviewUrl::App("index.html#quick-paste".into());
+            match tauri::WebviewWindowBuilder::new(app, QUICK_PASTE_WINDOW, url)
+                .title("Quick Paste")
+                .inner_size(420.0, 360.0)
+                .always_on_top(true)
+                .decorations(false)
+                .skip_taskbar(true)
+                .resizable(false)
+                .center()
+                .visible(false)
+                .build()
+            {
+                Ok(window) => window,
+                Err(e) => {
+                    log::warn!("Could not create quick paste window: {}", e);
+                    return;
+                }
+            }
+        }
+    };
+
+    let _ = window.show();
+    let _ = window.set_focus();
+    let _ = window.emit("quick-paste-open", ());
+}
+
+fn hide_quick_paste(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window(QUICK_PASTE_WINDOW) {
+        let _ = window.hide();
+    }
+}
+
+#[tauri::command]
+async fn quick_paste_items(query: String, state: tauri::State<'_, AppState>) -> Result<Vec<ClipboardItem>, String> {
+    let clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.quick_paste_items(&query).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn quick_paste_select(
+    id: u64,
+    inputs: Option<HashMap<String, String>>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    hide_quick_paste(&app);
+    let mut clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.quick_paste_select(id, &inputs.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn quick_paste_cancel(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    hide_quick_paste(&app);
+    let mut clipboard = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    clipboard.quick_paste_cancel().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn apply_window_layout(layout: String, zone: Option<usize>, state: tauri::State<'_, AppState>) -> Result<(), String> {
     let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
-    ds.apply_layout(&layout).map_err(|e| e.to_string())
+    ds.apply_layout_zone(&layout, zone.unwrap_or(0)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_window_layouts(state: tauri::State<'_, AppState>) -> Result<Vec<Layout>, String> {
+    let ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    Ok(ds.get_layouts())
+}
+
+#[tauri::command]
+async fn create_window_layout(layout: Layout, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state
+        .dynamic_split
+        .lock()
+        .map_err(|e| e.to_string())?
+        .create_layout(layout)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()
+}
+
+#[tauri::command]
+async fn update_window_layout(name: String, layout: Layout, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state
+        .dynamic_split
+        .lock()
+        .map_err(|e| e.to_string())?
+        .update_layout(&name, layout)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()
+}
+
+#[tauri::command]
+async fn delete_window_layout(name: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state
+        .dynamic_split
+        .lock()
+        .map_err(|e| e.to_string())?
+        .delete_layout(&name)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()
+}
+
+#[tauri::command]
+async fn get_layout_dividers(name: String, state: tauri::State<'_, AppState>) -> Result<Vec<Divider>, String> {
+    let ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.get_layout_dividers(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_layout_divider(name: String, index: usize, position: f64, state: tauri::State<'_, AppState>) -> Result<Layout, String> {
+    let result = state
+        .dynamic_split
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_layout_divider(&name, index, position)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()?;
+    Ok(result)
+}
+
+#[tauri::command]
+async fn export_window_layouts(path: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.export_layouts(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn import_window_layouts(path: String, replace: bool, state: tauri::State<'_, AppState>) -> Result<usize, String> {
+    let result = state
+        .dynamic_split
+        .lock()
+        .map_err(|e| e.to_string())?
+        .import_layouts(Path::new(&path), replace)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()?;
+    Ok(result)
+}
+
+#[tauri::command]
+async fn snap_window_to_zone(zone: usize, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.snap_to_zone(zone).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn move_window_to_next_monitor(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.move_to_next_monitor().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn undo_window_move(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.undo_window_move().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn restore_original_window_size(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.restore_original_size().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn toggle_always_on_top(state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.toggle_always_on_top().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_window_opacity(opacity: Option<u8>, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.set_window_opacity(opacity).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn toggle_window_pinned(state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.toggle_pinned().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_focus_mode_windows(state: tauri::State<'_, AppState>) -> Result<Vec<FocusModeWindow>, String> {
+    let ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    Ok(ds.get_focus_mode_windows())
+}
+
+#[tauri::command]
+async fn get_focus_rules(state: tauri::State<'_, AppState>) -> Result<Vec<FocusRule>, String> {
+    let ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    Ok(ds.get_focus_rules())
+}
+
+#[tauri::command]
+async fn set_focus_rules(rules: Vec<FocusRule>, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state
+        .dynamic_split
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_focus_rules(rules)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()
+}
+
+#[tauri::command]
+async fn get_monitors(state: tauri::State<'_, AppState>) -> Result<Vec<MonitorInfo>, String> {
+    let ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.get_monitors().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_monitor_layouts(state: tauri::State<'_, AppState>) -> Result<HashMap<String, String>, String> {
+    let ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    Ok(ds.get_monitor_layouts())
+}
+
+#[tauri::command]
+async fn set_monitor_layout(monitor_id: String, layout: Option<String>, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state
+        .dynamic_split
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_monitor_layout(&monitor_id, layout)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()
+}
+
+#[tauri::command]
+async fn apply_workspace(request: WorkspaceRequest, state: tauri::State<'_, AppState>) -> Result<WorkspaceReport, String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.apply_workspace(&request).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_open_windows() -> Result<Vec<WindowInfo>, String> {
+    modules::window_ops::enumerate_windows().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_layout_rules(state: tauri::State<'_, AppState>) -> Result<Vec<LayoutRule>, String> {
+    let ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    Ok(ds.get_rules())
+}
+
+#[tauri::command]
+async fn set_layout_rules(rules: Vec<LayoutRule>, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state
+        .dynamic_split
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_rules(rules)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()
+}
+
+#[tauri::command]
+async fn export_layout_rules(path: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.export_rules(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn import_layout_rules(path: String, replace: bool, state: tauri::State<'_, AppState>) -> Result<usize, String> {
+    let result = state
+        .dynamic_split
+        .lock()
+        .map_err(|e| e.to_string())?
+        .import_rules(Path::new(&path), replace)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()?;
+    Ok(result)
+}
+
+#[tauri::command]
+async fn save_workspace(name: String, state: tauri::State<'_, AppState>) -> Result<WorkspaceSnapshot, String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.save_workspace(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_workspaces(state: tauri::State<'_, AppState>) -> Result<Vec<WorkspaceSnapshot>, String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.get_workspaces().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_workspace(name: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.delete_workspace(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn restore_workspace(name: String, launch_missing: bool, state: tauri::State<'_, AppState>) -> Result<RestoreReport, String> {
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.restore_workspace(&name, launch_missing).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_tiling_enabled(enabled: bool, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state
+        .dynamic_split
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_tiling_enabled(enabled)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()
+}
+
+#[tauri::command]
+async fn run_tiling_action(action: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let action = TilingAction::from_key(&action).map_err(|e| e.to_string())?;
+    let mut ds = state.dynamic_split.lock().map_err(|e| e.to_string())?;
+    ds.run_tiling_action(action).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -322,15 +930,122 @@ async fn get_taskbar_status(state: tauri::State<'_, AppState>) -> Result<bool, S
 }
 
 #[tauri::command]
-async fn trigger_screenshot(state: tauri::State<'_, AppState>) -> Result<(), String> {
+async fn trigger_screenshot(
+    target: Option<CaptureTarget>,
+    pipeline: Option<Vec<PostAction>>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<String>, String> {
+    let path = state
+        .take_screenshot(&target.unwrap_or_default(), pipeline.as_deref())
+        .map_err(|e| e.to_string())?;
+    Ok(path.map(|path| path.to_string_lossy().into_owned()))
+}
+
+#[tauri::command]
+async fn annotate_screenshot(path: String, shapes: Vec<Annotation>) -> Result<(), String> {
+    screenshot_pipeline::annotate_file(Path::new(&path), &shapes).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn execute_action(action: Action, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    actions::execute(&action, state.inner()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_mouse_bindings(state: tauri::State<'_, AppState>) -> Result<Vec<MouseBinding>, String> {
     let mam = state.mouse_action_mapper.lock().map_err(|e| e.to_string())?;
-    mam.take_screenshot().map_err(|e| e.to_string())
+    Ok(mam.get_bindings())
 }
 
 #[tauri::command]
-async fn launch_application(app_name: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+async fn get_mouse_profiles(state: tauri::State<'_, AppState>) -> Result<Vec<MouseProfile>, String> {
+    let mam = state.mouse_action_mapper.lock().map_err(|e| e.to_string())?;
+    Ok(mam.get_profiles())
+}
+
+#[tauri::command]
+async fn get_active_mouse_profile(state: tauri::State<'_, AppState>) -> Result<Option<String>, String> {
+    let mam = state.mouse_action_mapper.lock().map_err(|e| e.to_string())?;
+    Ok(mam.active_profile())
+}
+
+#[tauri::command]
+async fn get_mouse_gestures(state: tauri::State<'_, AppState>) -> Result<Vec<String>, String> {
+    let mam = state.mouse_action_mapper.lock().map_err(|e| e.to_string())?;
+    Ok(mam.get_gesture_names())
+}
+
+#[tauri::command]
+async fn record_mouse_gesture(name: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut mam = state.mouse_action_mapper.lock().map_err(|e| e.to_string())?;
+    mam.record_gesture(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_mouse_gesture(name: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state
+        .mouse_action_mapper
+        .lock()
+        .map_err(|e| e.to_string())?
+        .delete_gesture(&name)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()
+}
+
+#[tauri::command]
+async fn get_macros(state: tauri::State<'_, AppState>) -> Result<Vec<Macro>, String> {
     let mam = state.mouse_action_mapper.lock().map_err(|e| e.to_string())?;
-    mam.launch_app(&app_name).map_err(|e| e.to_string())
+    Ok(mam.get_macros())
+}
+
+#[tauri::command]
+async fn start_macro_recording(name: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut mam = state.mouse_action_mapper.lock().map_err(|e| e.to_string())?;
+    mam.start_macro_recording(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn stop_macro_recording(state: tauri::State<'_, AppState>) -> Result<Macro, String> {
+    let recorded = state
+        .mouse_action_mapper
+        .lock()
+        .map_err(|e| e.to_string())?
+        .finish_macro_recording()
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()?;
+    Ok(recorded)
+}
+
+#[tauri::command]
+async fn save_macro(recorded: Macro, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state
+        .mouse_action_mapper
+        .lock()
+        .map_err(|e| e.to_string())?
+        .save_macro(recorded)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()
+}
+
+#[tauri::command]
+async fn delete_macro(name: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state
+        .mouse_action_mapper
+        .lock()
+        .map_err(|e| e.to_string())?
+        .delete_macro(&name)
+        .map_err(|e| e.to_string())?;
+    state.save_module_settings()
+}
+
+#[tauri::command]
+async fn launch_application(app_name: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    actions::execute(&Action::from_legacy_name(&app_name), state.inner()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_installed_apps(refresh: Option<bool>) -> Result<Vec<AppEntry>, String> {
+    app_launcher::installed_apps(refresh.unwrap_or(false)).map_err(|e| e.to_string())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -351,7 +1066,126 @@ pub fn run() {
             log::info!("WinShaper starting up...");
             log::info!("Platform: Windows");
             log::info!("Version: 0.1.0 (MVP)");
+
+            // Quick paste hotkey opens the picker window
+            let quick_paste_handle = app.handle().clone();
+            app.state::<AppState>()
+                .clipboard_history
+                .lock()
+                .map_err(|e| e.to_string())?
+                .set_quick_paste_handler(Arc::new(move || show_quick_paste(&quick_paste_handle)))?;
+
+            // Window events drive DynamicSplit's app-specific rules
+            let window_event_handle = app.handle().clone();
+            app.state::<AppState>()
+                .dynamic_split
+                .lock()
+                .map_err(|e| e.to_string())?
+                .set_window_event_handler(Arc::new(move |event| {
+                    if let Ok(mut ds) = window_event_handle.state::<AppState>().dynamic_split.lock() {
+                        ds.handle_window_event(event);
+                    }
+                }));
+
+            // Tiling hotkeys run on their own thread so the hotkey loop never
+            // waits on the module lock
+            let tiling_handle = app.handle().clone();
+            app.state::<AppState>()
+                .dynamic_split
+                .lock()
+                .map_err(|e| e.to_string())?
+                .set_tiling_action_handler(Arc::new(move |action| {
+                    let handle = tiling_handle.clone();
+                    std::thread::spawn(move || {
+                        if let Ok(mut ds) = handle.state::<AppState>().dynamic_split.lock() {
+                            if let Err(e) = ds.run_tiling_action(action) {
+                                log::warn!("Tiling action {} failed: {}", action.key(), e);
+                            }
+                        }
+                    });
+                }));
+
+            let mouse_handle = app.handle().clone();
+            app.state::<AppState>()
+                .mouse_action_mapper
+                .lock()
+                .map_err(|e| e.to_string())?
+                .set_action_handler(Arc::new(move |action| {
+                    let handle = mouse_handle.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = actions::execute(&action, handle.state::<AppState>().inner()) {
+                            log::warn!("Mouse action {:?} failed: {}", action, e);
+                        }
+                    });
+                }));
+
+            let gesture_handle = app.handle().clone();
+            app.state::<AppState>()
+                .mouse_action_mapper
+                .lock()
+                .map_err(|e| e.to_string())?
+                .set_gesture_handler(Arc::new(move |path| {
+                    let handle = gesture_handle.clone();
+                    std::thread::spawn(move || {
+                        let state = handle.state::<AppState>();
+                        // The lock is released before the action runs
+                        let (recording, action) = match state.mouse_action_mapper.lock() {
+                            Ok(mut mam) => (mam.is_recording_gesture(), mam.handle_gesture(path)),
+                            Err(_) => return,
+                        };
+                        if recording && action.is_ok() {
+                            if let Err(e) = state.save_module_settings() {
+                                log::warn!("Could not save gesture: {}", e);
+                            }
+                        }
+                        let result = action.and_then(|action| match action {
+                            Some(action) => actions::execute(&action, state.inner()),
+                            None => Ok(()),
+                        });
+                        if let Err(e) = result {
+                            log::warn!("Gesture failed: {}", e);
+                        }
+                    });
+                }));
             
+            let macro_handle = app.handle().clone();
+            app.state::<AppState>()
+                .mouse_action_mapper
+                .lock()
+                .map_err(|e| e.to_string())?
+                .set_macro_stop_handler(Arc::new(move || {
+                    let handle = macro_handle.clone();
+                    std::thread::spawn(move || {
+                        let state = handle.state::<AppState>();
+                        let recorded = match state.mouse_action_mapper.lock() {
+                            Ok(mut mam) => mam.finish_macro_recording().map_err(|e| e.to_string()),
+                            Err(_) => return,
+                        };
+                        // Written to the config file once the module lock is released
+                        if let Err(e) = recorded.and_then(|_| state.save_module_settings()) {
+                            log::warn!("Could not save macro: {}", e);
+                        }
+                    });
+                }));
+
+            // Focus changes switch the mouse profile
+            let profile_handle = app.handle().clone();
+            app.state::<AppState>()
+                .mouse_action_mapper
+                .lock()
+                .map_err(|e| e.to_string())?
+                .set_window_event_handler(Arc::new(move |event| {
+                    if let Ok(mut mam) = profile_handle.state::<AppState>().mouse_action_mapper.lock() {
+                        mam.handle_window_event(event);
+                    }
+                }));
+
+            // Modules start from their saved settings, once their handlers are in place
+            match read_config() {
+                Ok(mut config) => app.state::<AppState>().load_module_settings(&mut config),
+                Err(e) => log::warn!("Could not read config: {}", e),
+            }
+
             // Setup system tray
             let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
             let show_item = MenuItem::with_id(app, "show", "Show Window", true, None::<&str>)?;
@@ -421,6 +1255,8 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             load_config,
             save_config,
+            get_module_settings,
+            update_module_settings,
             get_system_info,
             enable_module,
             disable_module,
@@ -430,15 +1266,88 @@ pub fn run() {
             get_clipboard_history,
             search_clipboard,
             copy_clipboard_item,
+            delete_clipboard_item,
+            get_snippets,
+            add_snippet,
+            update_snippet,
+            get_snippet_inputs,
+            copy_snippet_by_abbreviation,
+            quick_paste_items,
+            quick_paste_select,
+            quick_paste_cancel,
             clear_clipboard_history,
             check_clipboard,
+            pin_clipboard_item,
+            export_clipboard_history,
+            import_clipboard_history,
+            begin_clipboard_pairing,
+            pair_clipboard_peer,
+            get_clipboard_pairing_requests,
+            confirm_clipboard_pairing,
+            reject_clipboard_pairing,
+            unpair_clipboard_peer,
+            get_clipboard_sync_peers,
+            sync_clipboard_now,
             apply_window_layout,
+            get_window_layouts,
+            create_window_layout,
+            update_window_layout,
+            delete_window_layout,
+            get_layout_dividers,
+            set_layout_divider,
+            export_window_layouts,
+            import_window_layouts,
+            snap_window_to_zone,
+            move_window_to_next_monitor,
+            undo_window_move,
+            restore_original_window_size,
+            toggle_always_on_top,
+            set_window_opacity,
+            toggle_window_pinned,
+            get_focus_mode_windows,
+            get_focus_rules,
+            set_focus_rules,
+            get_monitors,
+            get_monitor_layouts,
+            set_monitor_layout,
+            apply_workspace,
+            get_open_windows,
+            get_layout_rules,
+            set_layout_rules,
+            export_layout_rules,
+            import_layout_rules,
+            save_workspace,
+            get_workspaces,
+            delete_workspace,
+            restore_workspace,
+            set_tiling_enabled,
+            run_tiling_action,
             cycle_window_layout,
             toggle_taskbar,
             get_taskbar_status,
             trigger_screenshot,
-            launch_application
+            annotate_screenshot,
+            execute_action,
+            launch_application,
+            get_installed_apps,
+            get_mouse_bindings,
+            get_mouse_profiles,
+            get_active_mouse_profile,
+            get_mouse_gestures,
+            record_mouse_gesture,
+            delete_mouse_gesture,
+            get_macros,
+            start_macro_recording,
+            stop_macro_recording,
+            save_macro,
+            delete_macro
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            // However the app exits, don't leave remapped buttons or keys held
+            if let RunEvent::Exit = event {
+                mouse_remap::release_all();
+            }
+        });
 }
@@ -0,0 +1,334 @@
+use super::app_launcher::{self, LaunchSpec};
+use super::hotkey::KeyChord;
+use super::screenshot_pipeline::{self, PostAction};
+use super::screenshots::CaptureTarget;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use windows::core::HSTRING;
+use windows::Win32::UI::Shell::ShellExecuteW;
+use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+/// Give the clipboard owner a moment before the paste keystroke reads it
+const CLIPBOARD_SETTLE: Duration = Duration::from_millis(50);
+
+/// Something a mouse binding, gesture or other trigger can do. Stored as
+/// JSON tagged by `type`, like `{"type": "open_url", "url": "https://..."}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    Screenshot {
+        #[serde(default)]
+        target: CaptureTarget,
+        /// Steps after the capture; the screenshot settings' when unset
+        #[serde(default)]
+        pipeline: Option<Vec<PostAction>>,
+    },
+    LaunchApp(LaunchSpec),
+    OpenUrl {
+        url: String,
+    },
+    SendKeys {
+        chord: KeyChord,
+    },
+    ApplyLayout {
+        layout: String,
+        /// Snap the active window to one zone instead of arranging all windows
+        #[serde(default)]
+        zone: Option<usize>,
+    },
+    ToggleTaskbar,
+    PasteClipboardItem {
+        id: u64,
+    },
+    /// Run through `cmd /C`, without waiting for it to finish
+    RunCommand {
+        command: String,
+        #[serde(default)]
+        cwd: Option<String>,
+    },
+    RunMacro {
+        name: String,
+    },
+    ShowDesktop,
+    /// Apply the next DynamicSplit layout in the cycle
+    CycleLayout,
+    /// Show the quick paste picker
+    OpenClipboardHistory,
+}
+
+impl Action {
+    /// Action for a plain name, as stored before actions were typed:
+    /// `screenshot` or an app to launch
+    pub fn from_legacy_name(name: &str) -> Self {
+        match name {
+            "screenshot" => Action::Screenshot { target: CaptureTarget::FullScreen, pipeline: None },
+            app => Action::LaunchApp(LaunchSpec::new(app)),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let required = |value: &str, what: &str| {
+            if value.trim().is_empty() {
+                Err(anyhow::anyhow!("Action {} must not be empty", what))
+            } else {
+                Ok(())
+            }
+        };
+        let directory = |cwd: &Option<String>| match cwd {
+            Some(dir) if !Path::new(dir).is_dir() => {
+                Err(anyhow::anyhow!("Working directory does not exist: {}", dir))
+            }
+            _ => Ok(()),
+        };
+
+        match self {
+            Action::Screenshot { pipeline, .. } => match pipeline {
+                Some(steps) => screenshot_pipeline::validate_pipeline(steps),
+                None => Ok(()),
+            },
+            Action::ToggleTaskbar
+            | Action::SendKeys { .. }
+            | Action::PasteClipboardItem { .. }
+            | Action::ShowDesktop
+            | Action::CycleLayout
+            | Action::OpenClipboardHistory => Ok(()),
+            Action::LaunchApp(spec) => spec.validate(),
+            Action::OpenUrl { url } => {
+                let lower = url.trim().to_lowercase();
+                let rest = ["http://", "https://", "mailto:"]
+                    .iter()
+                    .find_map(|scheme| lower.strip_prefix(scheme));
+                match rest {
+                    Some(rest) if !rest.is_empty() => Ok(()),
+                    _ => Err(anyhow::anyhow!("Not an http, https or mailto URL: {}", url)),
+                }
+            }
+            Action::ApplyLayout { layout, .. } => required(layout, "layout"),
+            Action::RunCommand { command, cwd } => {
+                required(command, "command")?;
+                directory(cwd)
+            }
+            Action::RunMacro { name } => required(name, "macro name"),
+        }
+    }
+}
+
+/// Settings written before actions were typed hold plain names
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredAction {
+    Typed(Action),
+    Legacy(String),
+}
+
+impl From<StoredAction> for Action {
+    fn from(stored: StoredAction) -> Self {
+        match stored {
+            StoredAction::Typed(action) => action,
+            StoredAction::Legacy(name) => Action::from_legacy_name(&name),
+        }
+    }
+}
+
+/// For `#[serde(deserialize_with)]` on fields that may hold a legacy name
+pub fn deserialize_action<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Action, D::Error> {
+    StoredAction::deserialize(deserializer).map(Action::from)
+}
+
+/// Like `deserialize_action`, for a map of names to actions
+pub fn deserialize_action_map<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<String, Action>, D::Error> {
+    let stored = HashMap::<String, StoredAction>::deserialize(deserializer)?;
+    Ok(stored.into_iter().map(|(name, action)| (name, action.into())).collect())
+}
+
+/// The parts of an action that belong to other modules. Implemented once
+/// over the app state; each call takes and releases that module's lock.
+pub trait ActionContext {
+    /// Capture and run the steps, or the configured ones when `None`.
+    /// The saved path, if any step saved the image.
+    fn take_screenshot(&self, target: &CaptureTarget, pipeline: Option<&[PostAction]>) -> Result<Option<PathBuf>>;
+    fn apply_layout(&self, layout: &str, zone: Option<usize>) -> Result<()>;
+    fn cycle_layout(&self) -> Result<()>;
+    fn toggle_taskbar(&self) -> Result<()>;
+    /// Put a history item on the clipboard
+    fn copy_clipboard_item(&self, id: u64) -> Result<()>;
+    fn run_macro(&self, name: &str) -> Result<()>;
+    fn open_clipboard_history(&self) -> Result<()>;
+}
+
+/// Run an action. Every trigger goes through here, so an action behaves
+/// the same whether a binding, a gesture or the UI fired it.
+pub fn execute(action: &Action, context: &dyn ActionContext) -> Result<()> {
+    action.validate()?;
+    match action {
+        Action::Screenshot { target, pipeline } => context.take_screenshot(target, pipeline.as_deref()).map(|_| ()),
+        Action::LaunchApp(spec) => app_launcher::launch(spec),
+        Action::OpenUrl { url } => open_url(url),
+        Action::SendKeys { chord } => chord.send(),
+        Action::ApplyLayout { layout, zone } => context.apply_layout(layout, *zone),
+        Action::ToggleTaskbar => context.toggle_taskbar(),
+        Action::PasteClipboardItem { id } => {
+            context.copy_clipboard_item(*id)?;
+            thread::sleep(CLIPBOARD_SETTLE);
+            KeyChord::parse("Ctrl+V")?.send()
+        }
+        Action::RunCommand { command, cwd } => run_command(command, cwd.as_deref()),
+        Action::RunMacro { name } => context.run_macro(name),
+        Action::ShowDesktop => KeyChord::parse("Win+D")?.send(),
+        Action::CycleLayout => context.cycle_layout(),
+        Action::OpenClipboardHistory => context.open_clipboard_history(),
+    }
+}
+
+/// Open a URL in the default browser or mail client
+pub fn open_url(url: &str) -> Result<()> {
+    let result = unsafe {
+        ShellExecuteW(None, &HSTRING::from("open"), &HSTRING::from(url.trim()), None, None, SW_SHOWNORMAL)
+    };
+    // Values up to 32 are error codes
+    if result.0 as isize <= 32 {
+        return Err(anyhow::anyhow!("Could not open {} (error {})", url, result.0 as isize));
+    }
+    log::info!("Opened URL: {}", url);
+    Ok(())
+}
+
+pub fn run_command(command_line: &str, cwd: Option<&str>) -> Result<()> {
+    let mut command = Command::new("cmd");
+    command.args(["/C", command_line]);
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    command
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to run command: {}", e))?;
+
+    log::info!("Ran command: {}", command_line);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn missing_dir() -> String {
+        std::env::temp_dir().join("winshaper-missing-dir").join("nested").to_string_lossy().into_owned()
+    }
+
+    fn url(url: &str) -> Action {
+        Action::OpenUrl { url: url.to_string() }
+    }
+
+    #[test]
+    fn validate_rejects_empty_fields() {
+        assert!(Action::ApplyLayout { layout: " ".to_string(), zone: None }.validate().is_err());
+        assert!(Action::RunMacro { name: String::new() }.validate().is_err());
+        assert!(Action::RunCommand { command: "\t".to_string(), cwd: None }.validate().is_err());
+        assert!(Action::LaunchApp(LaunchSpec::new("")).validate().is_err());
+
+        assert!(Action::ApplyLayout { layout: "60-40".to_string(), zone: Some(1) }.validate().is_ok());
+        assert!(Action::RunMacro { name: "greet".to_string() }.validate().is_ok());
+        assert!(Action::ToggleTaskbar.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_needs_a_known_url_scheme_and_something_after_it() {
+        assert!(url("https://example.com").validate().is_ok());
+        assert!(url("  HTTP://example.com ").validate().is_ok());
+        assert!(url("mailto:someone@example.com").validate().is_ok());
+
+        assert!(url("http://").validate().is_err());
+        assert!(url("https:// ").validate().is_err());
+        assert!(url("mailto:").validate().is_err());
+        assert!(url("").validate().is_err());
+        assert!(url("example.com").validate().is_err());
+        assert!(url("file:///C:/Windows/System32/calc.exe").validate().is_err());
+        assert!(url("javascript:alert(1)").validate().is_err());
+    }
+
+    #[test]
+    fn validate_checks_the_working_directory() {
+        let existing = std::env::temp_dir().to_string_lossy().into_owned();
+        let run = |cwd: Option<String>| Action::RunCommand { command: "echo hi".to_string(), cwd };
+        assert!(run(None).validate().is_ok());
+        assert!(run(Some(existing.clone())).validate().is_ok());
+        assert!(run(Some(missing_dir())).validate().is_err());
+
+        let launch = |cwd: Option<String>| Action::LaunchApp(LaunchSpec { cwd, ..LaunchSpec::new("notepad") });
+        assert!(launch(Some(existing)).validate().is_ok());
+        assert!(launch(Some(missing_dir())).validate().is_err());
+    }
+
+    #[test]
+    fn actions_round_trip_through_their_type_tag() {
+        let actions = vec![
+            Action::Screenshot { target: CaptureTarget::Region, pipeline: Some(vec![PostAction::Save]) },
+            Action::LaunchApp(LaunchSpec { args: vec!["a b".to_string()], ..LaunchSpec::new("notepad") }),
+            url("https://example.com"),
+            Action::SendKeys { chord: KeyChord::parse("Ctrl+Alt+T").unwrap() },
+            Action::ApplyLayout { layout: "50-50".to_string(), zone: Some(0) },
+            Action::ToggleTaskbar,
+            Action::PasteClipboardItem { id: 7 },
+            Action::RunCommand { command: "dir".to_string(), cwd: None },
+            Action::RunMacro { name: "greet".to_string() },
+            Action::ShowDesktop,
+            Action::CycleLayout,
+            Action::OpenClipboardHistory,
+        ];
+        for action in actions {
+            let json = serde_json::to_value(&action).unwrap();
+            assert!(json["type"].is_string(), "{}", json);
+            assert_eq!(serde_json::from_value::<Action>(json).unwrap(), action);
+        }
+
+        assert_eq!(serde_json::to_value(Action::ToggleTaskbar).unwrap(), serde_json::json!({ "type": "toggle_taskbar" }));
+        assert_eq!(
+            serde_json::to_value(url("https://example.com")).unwrap(),
+            serde_json::json!({ "type": "open_url", "url": "https://example.com" })
+        );
+        // Optional fields may be left out
+        assert_eq!(
+            serde_json::from_value::<Action>(serde_json::json!({ "type": "screenshot" })).unwrap(),
+            Action::Screenshot { target: CaptureTarget::FullScreen, pipeline: None }
+        );
+        assert_eq!(
+            serde_json::from_value::<Action>(serde_json::json!({ "type": "launch_app", "path": "code" })).unwrap(),
+            Action::LaunchApp(LaunchSpec::new("code"))
+        );
+        assert!(serde_json::from_value::<Action>(serde_json::json!({ "type": "self_destruct" })).is_err());
+        assert!(serde_json::from_value::<Action>(serde_json::json!({ "type": "open_url" })).is_err());
+    }
+
+    #[test]
+    fn legacy_names_deserialize_as_actions() {
+        assert_eq!(
+            deserialize_action(serde_json::json!("screenshot")).unwrap(),
+            Action::Screenshot { target: CaptureTarget::FullScreen, pipeline: None }
+        );
+        assert_eq!(
+            deserialize_action(serde_json::json!("notepad.exe")).unwrap(),
+            Action::LaunchApp(LaunchSpec::new("notepad.exe"))
+        );
+        assert_eq!(deserialize_action(serde_json::json!({ "type": "cycle_layout" })).unwrap(), Action::CycleLayout);
+        assert!(deserialize_action(serde_json::json!(42)).is_err());
+        assert!(deserialize_action(serde_json::json!({ "type": "unknown" })).is_err());
+
+        let map = deserialize_action_map(serde_json::json!({
+            "swipe_up": "screenshot",
+            "swipe_down": { "type": "show_desktop" },
+            "circle": "calc",
+        }))
+        .unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map["swipe_up"], Action::from_legacy_name("screenshot"));
+        assert_eq!(map["swipe_down"], Action::ShowDesktop);
+        assert_eq!(map["circle"], Action::LaunchApp(LaunchSpec::new("calc")));
+        assert!(deserialize_action_map(serde_json::json!({ "bad": [1, 2] })).is_err());
+    }
+}
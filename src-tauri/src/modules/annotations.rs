@@ -0,0 +1,195 @@
+use super::screenshots::CapturedImage;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_COLOR: &str = "#FF3B30";
+/// Arrow heads are this many times the line thickness, but at least `MIN_HEAD`
+const HEAD_SCALE: f64 = 4.0;
+const MIN_HEAD: f64 = 12.0;
+const HEAD_ANGLE: f64 = std::f64::consts::PI / 6.0;
+
+fn default_color() -> String {
+    DEFAULT_COLOR.to_string()
+}
+
+fn default_thickness() -> u32 {
+    3
+}
+
+fn default_block_size() -> u32 {
+    12
+}
+
+/// A shape drawn onto a capture. Coordinates are pixels of the image, with
+/// the origin at its top left. Colours are written like `#RRGGBB`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum Annotation {
+    Box {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        #[serde(default = "default_color")]
+        color: String,
+        #[serde(default = "default_thickness")]
+        thickness: u32,
+    },
+    Arrow {
+        from: (i32, i32),
+        to: (i32, i32),
+        #[serde(default = "default_color")]
+        color: String,
+        #[serde(default = "default_thickness")]
+        thickness: u32,
+    },
+    /// Pixelated in blocks rather than smoothed, so the content underneath
+    /// cannot be recovered
+    Blur {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        #[serde(default = "default_block_size")]
+        block_size: u32,
+    },
+}
+
+impl Annotation {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Annotation::Box { width, height, color, thickness, .. } => {
+                if *width <= 0 || *height <= 0 {
+                    return Err(anyhow::anyhow!("Box annotation must have a positive size"));
+                }
+                parse_color(color)?;
+                check_thickness(*thickness)
+            }
+            Annotation::Arrow { color, thickness, .. } => {
+                parse_color(color)?;
+                check_thickness(*thickness)
+            }
+            Annotation::Blur { width, height, block_size, .. } => {
+                if *width <= 0 || *height <= 0 {
+                    return Err(anyhow::anyhow!("Blur annotation must have a positive size"));
+                }
+                if !(2..=200).contains(block_size) {
+                    return Err(anyhow::anyhow!("Blur block size must be between 2 and 200"));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn check_thickness(thickness: u32) -> Result<()> {
+    if !(1..=50).contains(&thickness) {
+        return Err(anyhow::anyhow!("Annotation thickness must be between 1 and 50"));
+    }
+    Ok(())
+}
+
+fn parse_color(color: &str) -> Result<[u8; 3]> {
+    let hex = color
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6)
+        .ok_or_else(|| anyhow::anyhow!("Colour must look like #RRGGBB: {}", color))?;
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow::anyhow!("Colour must look like #RRGGBB: {}", color))
+    };
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Draws onto an image, clipping everything to its bounds
+struct Canvas<'a> {
+    image: &'a mut CapturedImage,
+}
+
+impl Canvas<'_> {
+    fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: [u8; 3]) {
+        let (x0, y0) = (x.max(0), y.max(0));
+        let x1 = (x + width).min(self.image.width as i32);
+        let y1 = (y + height).min(self.image.height as i32);
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let offset = (py as usize * self.image.width as usize + px as usize) * 4;
+                self.image.rgba[offset..offset + 3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    /// A line of square dabs, `thickness` wide
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), color: [u8; 3], thickness: u32) {
+        let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).ceil().max(1.0) as i32;
+        let half = thickness as f64 / 2.0;
+        for step in 0..=steps {
+            let t = step as f64 / steps as f64;
+            let x = from.0 + t * (to.0 - from.0) - half;
+            let y = from.1 + t * (to.1 - from.1) - half;
+            self.fill_rect(x.round() as i32, y.round() as i32, thickness as i32, thickness as i32, color);
+        }
+    }
+
+    /// Replace each block with its average colour
+    fn pixelate(&mut self, x: i32, y: i32, width: i32, height: i32, block: u32) {
+        let (x0, y0) = (x.max(0), y.max(0));
+        let x1 = (x + width).min(self.image.width as i32);
+        let y1 = (y + height).min(self.image.height as i32);
+        let block = block as i32;
+
+        for by in (y0..y1).step_by(block as usize) {
+            for bx in (x0..x1).step_by(block as usize) {
+                let (bw, bh) = (block.min(x1 - bx), block.min(y1 - by));
+                let mut sum = [0u64; 3];
+                for py in by..by + bh {
+                    for px in bx..bx + bw {
+                        let offset = (py as usize * self.image.width as usize + px as usize) * 4;
+                        for (channel, total) in sum.iter_mut().enumerate() {
+                            *total += self.image.rgba[offset + channel] as u64;
+                        }
+                    }
+                }
+                let count = (bw * bh) as u64;
+                let average = sum.map(|total| (total / count) as u8);
+                self.fill_rect(bx, by, bw, bh, average);
+            }
+        }
+    }
+}
+
+/// Draw every shape, in order, onto the image
+pub fn annotate(image: &mut CapturedImage, shapes: &[Annotation]) -> Result<()> {
+    for shape in shapes {
+        shape.validate()?;
+    }
+
+    let mut canvas = Canvas { image };
+    for shape in shapes {
+        match shape {
+            Annotation::Box { x, y, width, height, color, thickness } => {
+                let color = parse_color(color)?;
+                let t = *thickness as i32;
+                canvas.fill_rect(*x, *y, *width, t, color);
+                canvas.fill_rect(*x, y + height - t, *width, t, color);
+                canvas.fill_rect(*x, *y, t, *height, color);
+                canvas.fill_rect(x + width - t, *y, t, *height, color);
+            }
+            Annotation::Arrow { from, to, color, thickness } => {
+                let color = parse_color(color)?;
+                let (from, to) = ((from.0 as f64, from.1 as f64), (to.0 as f64, to.1 as f64));
+                canvas.line(from, to, color, *thickness);
+
+                let angle = (from.1 - to.1).atan2(from.0 - to.0);
+                let head = (*thickness as f64 * HEAD_SCALE).max(MIN_HEAD);
+                for side in [-HEAD_ANGLE, HEAD_ANGLE] {
+                    let tip = (to.0 + head * (angle + side).cos(), to.1 + head * (angle + side).sin());
+                    canvas.line(to, tip, color, *thickness);
+                }
+            }
+            Annotation::Blur { x, y, width, height, block_size } => {
+                canvas.pixelate(*x, *y, *width, *height, *block_size);
+            }
+        }
+    }
+    Ok(())
+}
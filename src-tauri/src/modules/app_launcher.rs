@@ -0,0 +1,613 @@
+use super::actions;
+use super::window_ops;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use windows::core::{Interface, HSTRING};
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoUninitialize, IPersistFile, CLSCTX_INPROC_SERVER,
+    COINIT_APARTMENTTHREADED, STGM_READ,
+};
+use windows::Win32::System::Environment::ExpandEnvironmentStringsW;
+use windows::Win32::UI::Shell::{IShellLinkW, ShellExecuteW, ShellLink};
+use windows::Win32::UI::WindowsAndMessaging::{IsIconic, SetForegroundWindow, ShowWindow, SW_RESTORE, SW_SHOWNORMAL};
+
+/// Names accepted before there was a registry that neither the Start Menu
+/// nor PATH knows
+const LEGACY_ALIASES: &[(&str, &str)] = &[("calculator", "calc.exe")];
+/// Only these are started directly from PATH; scripts need `run_command`
+const PATH_EXTENSIONS: &[&str] = &["exe", "com"];
+
+/// Start Menu entries, scanned on first use
+static REGISTRY: Mutex<Option<Vec<AppEntry>>> = Mutex::new(None);
+
+/// An application found in the Start Menu
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppEntry {
+    /// Shortcut name, e.g. `Visual Studio Code`
+    pub name: String,
+    /// Executable the shortcut points at. `None` for shortcuts to documents
+    /// or installer-advertised apps, which are opened through the shell.
+    pub program: Option<String>,
+    /// Arguments stored in the shortcut, as one command line
+    pub arguments: String,
+    pub working_dir: Option<String>,
+    pub shortcut: String,
+}
+
+/// What to launch and how. `path` is a Start Menu name, a program on PATH,
+/// a full path to a program or shortcut, or an http(s)/mailto URL.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LaunchSpec {
+    pub path: String,
+    /// Passed as separate arguments, never through a shell
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Added to the inherited environment
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Bring a running instance's window forward instead of starting another.
+    /// Only for programs; URLs and shortcuts to documents have none to find.
+    #[serde(default)]
+    pub focus_existing: bool,
+}
+
+impl LaunchSpec {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            args: Vec::new(),
+            cwd: None,
+            env: HashMap::new(),
+            focus_existing: false,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.path.trim().is_empty() {
+            return Err(anyhow::anyhow!("Action app path must not be empty"));
+        }
+        if let Some(dir) = &self.cwd {
+            if !Path::new(dir).is_dir() {
+                return Err(anyhow::anyhow!("Working directory does not exist: {}", dir));
+            }
+        }
+        for name in self.env.keys() {
+            if name.is_empty() || name.contains(['=', '\0']) {
+                return Err(anyhow::anyhow!("Invalid environment variable name: {}", name));
+            }
+        }
+        // A URL opens in whatever handles it, so there is no program to look for
+        if self.focus_existing && is_url(&self.path) {
+            return Err(anyhow::anyhow!("focus_existing cannot be used with a URL: {}", self.path));
+        }
+        Ok(())
+    }
+}
+
+fn is_url(path: &str) -> bool {
+    let lower = path.trim().to_lowercase();
+    ["http://", "https://", "mailto:"].iter().any(|scheme| lower.starts_with(scheme))
+}
+
+/// What a launch path turned out to be
+#[derive(Debug)]
+enum Target {
+    Program { path: PathBuf, arguments: String, working_dir: Option<String> },
+    /// Opened through the shell, like a double click
+    Shortcut(PathBuf),
+    Url(String),
+}
+
+/// Initialises COM on the current thread for as long as it lives
+pub struct ComScope(bool);
+
+impl ComScope {
+    pub fn new() -> Self {
+        Self(unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) }.is_ok())
+    }
+}
+
+impl Drop for ComScope {
+    fn drop(&mut self) {
+        if self.0 {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+fn read_wide(buffer: &[u16]) -> String {
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..len])
+}
+
+/// Expand `%VAR%` references the way the shell does when it starts a
+/// shortcut. Unknown variables are left as they are.
+fn expand_env(text: &str) -> String {
+    if !text.contains('%') {
+        return text.to_string();
+    }
+    let source = HSTRING::from(text);
+    unsafe {
+        let needed = ExpandEnvironmentStringsW(&source, None);
+        if needed == 0 {
+            return text.to_string();
+        }
+        let mut buffer = vec![0u16; needed as usize];
+        match ExpandEnvironmentStringsW(&source, Some(&mut buffer)) {
+            0 => text.to_string(),
+            _ => read_wide(&buffer),
+        }
+    }
+}
+
+/// Read a `.lnk` file. COM must be initialised on the calling thread.
+fn read_shortcut(shortcut: &Path) -> Result<AppEntry> {
+    unsafe {
+        let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)?;
+        link.cast::<IPersistFile>()?
+            .Load(&HSTRING::from(shortcut.as_os_str()), STGM_READ)?;
+
+        let mut buffer = [0u16; 1024];
+        let program = match link.GetPath(&mut buffer, std::ptr::null_mut(), 0) {
+            Ok(()) => Some(read_wide(&buffer)),
+            Err(_) => None,
+        }
+        .filter(|path| path.to_lowercase().ends_with(".exe"));
+
+        let mut buffer = [0u16; 1024];
+        // Arguments and working directory are stored unexpanded, e.g. the
+        // Command Prompt shortcut starts in `%HOMEDRIVE%%HOMEPATH%`
+        let arguments = link
+            .GetArguments(&mut buffer)
+            .map(|()| expand_env(&read_wide(&buffer)))
+            .unwrap_or_default();
+        let mut buffer = [0u16; 1024];
+        let working_dir = link
+            .GetWorkingDirectory(&mut buffer)
+            .map(|()| expand_env(&read_wide(&buffer)))
+            .ok()
+            .filter(|dir| !dir.is_empty());
+
+        Ok(AppEntry {
+            name: shortcut.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+            program,
+            arguments,
+            working_dir,
+            shortcut: shortcut.to_string_lossy().into_owned(),
+        })
+    }
+}
+
+/// The per-user Start Menu first, so its entries win over all-users ones
+fn start_menu_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(roaming) = dirs::data_dir() {
+        dirs.push(roaming.join("Microsoft\\Windows\\Start Menu\\Programs"));
+    }
+    if let Some(program_data) = env::var_os("ProgramData") {
+        dirs.push(PathBuf::from(program_data).join("Microsoft\\Windows\\Start Menu\\Programs"));
+    }
+    dirs
+}
+
+fn collect_shortcuts(dir: &Path, shortcuts: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_shortcuts(&path, shortcuts);
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("lnk")) {
+            shortcuts.push(path);
+        }
+    }
+}
+
+fn scan_start_menu() -> Vec<AppEntry> {
+    let _com = ComScope::new();
+    let mut shortcuts = Vec::new();
+    for dir in start_menu_dirs() {
+        collect_shortcuts(&dir, &mut shortcuts);
+    }
+
+    let mut apps: Vec<AppEntry> = Vec::new();
+    for shortcut in shortcuts {
+        match read_shortcut(&shortcut) {
+            // Uninstallers sit next to most apps and are never what is meant
+            Ok(app) if app.name.to_lowercase().starts_with("uninstall") => {}
+            Ok(app) if apps.iter().any(|known| known.name.eq_ignore_ascii_case(&app.name)) => {}
+            Ok(app) => apps.push(app),
+            Err(e) => log::debug!("Skipping shortcut {}: {}", shortcut.display(), e),
+        }
+    }
+    apps.sort_by_key(|app| app.name.to_lowercase());
+    log::info!("Found {} Start Menu applications", apps.len());
+    apps
+}
+
+/// Applications in the Start Menu, sorted by name. Scanned once, then again
+/// only when `refresh` is set.
+pub fn installed_apps(refresh: bool) -> Result<Vec<AppEntry>> {
+    let mut registry = REGISTRY.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    if refresh || registry.is_none() {
+        *registry = Some(scan_start_menu());
+    }
+    Ok(registry.clone().unwrap_or_default())
+}
+
+fn find_app(name: &str) -> Result<Option<AppEntry>> {
+    Ok(find_entry(&installed_apps(false)?, name).cloned())
+}
+
+/// Find an entry by its name or its executable's name, ignoring case
+fn find_entry<'a>(apps: &'a [AppEntry], name: &str) -> Option<&'a AppEntry> {
+    let by_name = apps.iter().find(|app| app.name.eq_ignore_ascii_case(name));
+    let by_program = || {
+        apps.iter().find(|app| {
+            app.program.as_deref().and_then(|program| Path::new(program).file_stem()).is_some_and(|stem| {
+                stem.eq_ignore_ascii_case(name) || stem.eq_ignore_ascii_case(name.trim_end_matches(".exe"))
+            })
+        })
+    };
+    by_name.or_else(by_program)
+}
+
+fn find_on_path(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    find_in_dirs(name, env::split_paths(&path))
+}
+
+/// The first of `dirs` holding `name`, adding an allowed extension when it has none
+fn find_in_dirs(name: &str, dirs: impl IntoIterator<Item = PathBuf>) -> Option<PathBuf> {
+    let has_extension = Path::new(name)
+        .extension()
+        .is_some_and(|ext| PATH_EXTENSIONS.iter().any(|allowed| ext.eq_ignore_ascii_case(allowed)));
+    let candidates: Vec<String> = if has_extension {
+        vec![name.to_string()]
+    } else {
+        PATH_EXTENSIONS.iter().map(|ext| format!("{}.{}", name, ext)).collect()
+    };
+
+    dirs.into_iter()
+        .flat_map(|dir| candidates.iter().map(move |candidate| dir.join(candidate)))
+        .find(|candidate| candidate.is_file())
+}
+
+fn entry_target(app: AppEntry) -> Target {
+    match app.program {
+        Some(program) => Target::Program {
+            path: PathBuf::from(program),
+            arguments: app.arguments,
+            working_dir: app.working_dir,
+        },
+        None => Target::Shortcut(PathBuf::from(app.shortcut)),
+    }
+}
+
+fn program(path: PathBuf) -> Target {
+    Target::Program { path, arguments: String::new(), working_dir: None }
+}
+
+/// Work out what a launch path names. Nothing is started unless it resolves
+/// to a file that exists, a Start Menu entry or a URL.
+fn resolve(name: &str) -> Result<Target> {
+    let name = name.trim();
+    let lower = name.to_lowercase();
+    if is_url(name) {
+        return Ok(Target::Url(name.to_string()));
+    }
+
+    let path = Path::new(name);
+    if path.is_absolute() || name.contains(['\\', '/']) {
+        if !path.is_file() {
+            return Err(anyhow::anyhow!("Application not found: {}", name));
+        }
+        if lower.ends_with(".lnk") {
+            let _com = ComScope::new();
+            return Ok(entry_target(read_shortcut(path)?));
+        }
+        return Ok(program(path.to_path_buf()));
+    }
+
+    if let Some(app) = find_app(name)? {
+        return Ok(entry_target(app));
+    }
+    let name = LEGACY_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == lower)
+        .map_or(name, |(_, program)| program);
+    find_on_path(name)
+        .map(program)
+        .ok_or_else(|| anyhow::anyhow!("Unknown application: {}", name))
+}
+
+/// Quote an argument the way programs split their command line
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    // Backslashes are only special before a quote
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes + 1));
+                backslashes = 0;
+            }
+            _ => backslashes = 0,
+        }
+        quoted.push(c);
+    }
+    quoted.push_str(&"\\".repeat(backslashes));
+    quoted.push('"');
+    quoted
+}
+
+/// Split a shortcut's stored arguments the way the program itself would,
+/// by the rules of `CommandLineToArgvW`: spaces and tabs separate arguments
+/// outside quotes; `2n` backslashes before a quote become `n` and the quote
+/// starts or ends a quoted part, `2n + 1` become `n` and a literal quote;
+/// `""` inside a quoted part is a literal quote that also ends the part.
+fn split_arguments(arguments: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = arguments.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| matches!(c, ' ' | '\t')).is_some() {}
+        if chars.peek().is_none() {
+            return args;
+        }
+
+        let mut arg = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                ' ' | '\t' if !quoted => break,
+                '\\' => {
+                    let mut backslashes = 1;
+                    while chars.next_if_eq(&'\\').is_some() {
+                        backslashes += 1;
+                    }
+                    if chars.peek() == Some(&'"') {
+                        arg.push_str(&"\\".repeat(backslashes / 2));
+                        if backslashes % 2 == 1 {
+                            chars.next();
+                            arg.push('"');
+                        }
+                    } else {
+                        arg.push_str(&"\\".repeat(backslashes));
+                    }
+                }
+                '"' => {
+                    if quoted && chars.next_if_eq(&'"').is_some() {
+                        arg.push('"');
+                    }
+                    quoted = !quoted;
+                }
+                _ => arg.push(c),
+            }
+        }
+        args.push(arg);
+    }
+}
+
+/// Bring forward the most recently used window of a running program
+fn focus_running(program: &Path) -> Result<bool> {
+    let program = program.to_string_lossy();
+    let Some(window) = window_ops::enumerate_windows()?
+        .into_iter()
+        .find(|window| window.exe_path.eq_ignore_ascii_case(&program))
+    else {
+        return Ok(false);
+    };
+
+    let hwnd = window_ops::raw_to_hwnd(window.hwnd);
+    unsafe {
+        if IsIconic(hwnd).as_bool() {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+        if !SetForegroundWindow(hwnd).as_bool() {
+            return Err(anyhow::anyhow!("Could not focus {}", window.title));
+        }
+    }
+    log::info!("Focused running app: {}", window.exe);
+    Ok(true)
+}
+
+fn open_shortcut(shortcut: &Path, spec: &LaunchSpec) -> Result<()> {
+    if !spec.env.is_empty() {
+        log::warn!("Environment is not passed to {}", shortcut.display());
+    }
+    let parameters = spec.args.iter().map(|arg| quote_arg(arg)).collect::<Vec<_>>().join(" ");
+    let directory = spec.cwd.as_deref().map(HSTRING::from);
+    let result = unsafe {
+        ShellExecuteW(
+            None,
+            &HSTRING::from("open"),
+            &HSTRING::from(shortcut.as_os_str()),
+            &HSTRING::from(parameters),
+            directory.as_ref().unwrap_or(&HSTRING::new()),
+            SW_SHOWNORMAL,
+        )
+    };
+    // Values up to 32 are error codes
+    if result.0 as isize <= 32 {
+        return Err(anyhow::anyhow!("Could not open {} (error {})", shortcut.display(), result.0 as isize));
+    }
+    log::info!("Opened shortcut: {}", shortcut.display());
+    Ok(())
+}
+
+/// Launch an application, or focus it if it runs and the spec asks for that
+pub fn launch(spec: &LaunchSpec) -> Result<()> {
+    spec.validate()?;
+    let (path, arguments, working_dir) = match resolve(&spec.path)? {
+        Target::Url(url) => return actions::open_url(&url),
+        Target::Shortcut(shortcut) if spec.focus_existing => {
+            return Err(anyhow::anyhow!(
+                "{} opens through the shell, so there is no program to focus; turn off focus_existing",
+                shortcut.display()
+            ));
+        }
+        Target::Shortcut(shortcut) => return open_shortcut(&shortcut, spec),
+        Target::Program { path, arguments, working_dir } => (path, arguments, working_dir),
+    };
+
+    if spec.focus_existing && focus_running(&path)? {
+        return Ok(());
+    }
+
+    let mut command = Command::new(&path);
+    command.args(split_arguments(&arguments)).args(&spec.args).envs(&spec.env);
+    if let Some(cwd) = spec.cwd.as_ref().or(working_dir.as_ref()) {
+        command.current_dir(cwd);
+    }
+    command
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to launch {}: {}", path.display(), e))?;
+
+    log::info!("Launched app: {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, program: Option<&str>) -> AppEntry {
+        AppEntry {
+            name: name.to_string(),
+            program: program.map(str::to_string),
+            arguments: String::new(),
+            working_dir: None,
+            shortcut: format!("C:\\Start Menu\\{}.lnk", name),
+        }
+    }
+
+    /// A fresh directory under the system temp dir holding empty `files`
+    fn dir_with(name: &str, files: &[&str]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("winshaper-launcher-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for file in files {
+            fs::write(dir.join(file), b"").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn quote_arg_leaves_plain_arguments_alone() {
+        assert_eq!(quote_arg("--verbose"), "--verbose");
+        assert_eq!(quote_arg("C:\\dir\\"), "C:\\dir\\");
+        assert_eq!(quote_arg(""), "\"\"");
+        assert_eq!(quote_arg("two words"), "\"two words\"");
+        assert_eq!(quote_arg("tab\there"), "\"tab\there\"");
+    }
+
+    #[test]
+    fn quote_arg_escapes_quotes_and_the_backslashes_before_them() {
+        assert_eq!(quote_arg(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_arg(r#"a\"b"#), r#""a\\\"b""#);
+        // Backslashes elsewhere are literal
+        assert_eq!(quote_arg(r"C:\my dir\file"), r#""C:\my dir\file""#);
+        // A trailing backslash would otherwise escape the closing quote
+        assert_eq!(quote_arg(r"C:\my dir\"), r#""C:\my dir\\""#);
+        assert_eq!(quote_arg(r"C:\my dir\\"), r#""C:\my dir\\\\""#);
+    }
+
+    #[test]
+    fn split_arguments_follows_the_windows_rules() {
+        assert!(split_arguments("").is_empty());
+        assert!(split_arguments(" \t ").is_empty());
+        assert_eq!(split_arguments(" -a  \"b c\"\td "), vec!["-a", "b c", "d"]);
+        assert_eq!(split_arguments(r#"pre"quoted part"post"#), vec!["prequoted partpost"]);
+        assert_eq!(split_arguments(r#""" x"#), vec!["", "x"]);
+        // Backslashes only matter before a quote
+        assert_eq!(split_arguments(r"C:\dir\ \\server\share"), vec![r"C:\dir\", r"\\server\share"]);
+        assert_eq!(split_arguments(r#"a\"b"#), vec![r#"a"b"#]);
+        assert_eq!(split_arguments(r#"a\\"b c""#), vec![r"a\b c"]);
+        assert_eq!(split_arguments(r#"a\\\"b"#), vec![r#"a\"b"#]);
+        // A doubled quote inside quotes is a literal quote and ends the quoted part
+        assert_eq!(split_arguments(r#""a""b c""#), vec![r#"a"b"#, "c"]);
+        // An unclosed quote runs to the end
+        assert_eq!(split_arguments(r#"x "y z"#), vec!["x", "y z"]);
+    }
+
+    #[test]
+    fn quoted_arguments_split_back_into_the_originals() {
+        let args = ["plain", "", "two words", r#"say "hi""#, r"C:\my dir\", r#"\"\\"#, "\t", r"end\\"];
+        let line = args.iter().map(|arg| quote_arg(arg)).collect::<Vec<_>>().join(" ");
+        assert_eq!(split_arguments(&line), args);
+    }
+
+    #[test]
+    fn find_entry_matches_names_then_programs() {
+        let apps = vec![
+            entry("Visual Studio Code", Some("C:/Code/Code.exe")),
+            entry("Notes", None),
+            entry("code", Some("C:/Other/editor.exe")),
+        ];
+        let found = |name: &str| find_entry(&apps, name).map(|app| app.name.as_str());
+
+        assert_eq!(found("visual studio CODE"), Some("Visual Studio Code"));
+        assert_eq!(found("notes"), Some("Notes"));
+        // A shortcut name wins over another entry's program name
+        assert_eq!(found("Code"), Some("code"));
+        assert_eq!(found("code.exe"), Some("Visual Studio Code"));
+        assert_eq!(found("EDITOR"), Some("code"));
+        assert_eq!(found("editor.exe"), Some("code"));
+        assert_eq!(found("missing"), None);
+    }
+
+    #[test]
+    fn find_in_dirs_only_starts_programs() {
+        let first = dir_with("first", &["tool.exe", "script.bat"]);
+        let second = dir_with("second", &["tool.exe", "other.com"]);
+        let dirs = || vec![first.clone(), second.clone()];
+
+        assert_eq!(find_in_dirs("tool", dirs()), Some(first.join("tool.exe")));
+        assert_eq!(find_in_dirs("tool.exe", dirs()), Some(first.join("tool.exe")));
+        assert_eq!(find_in_dirs("other", dirs()), Some(second.join("other.com")));
+        assert_eq!(find_in_dirs("script", dirs()), None);
+        assert_eq!(find_in_dirs("script.bat", dirs()), None);
+        assert_eq!(find_in_dirs("missing", dirs()), None);
+
+        let _ = fs::remove_dir_all(first);
+        let _ = fs::remove_dir_all(second);
+    }
+
+    #[test]
+    fn resolve_handles_urls_and_paths() {
+        assert!(matches!(resolve(" https://example.com "), Ok(Target::Url(url)) if url == "https://example.com"));
+        assert!(matches!(resolve("MAILTO:me@example.com"), Ok(Target::Url(_))));
+
+        let dir = dir_with("resolve", &["app.exe"]);
+        let program = dir.join("app.exe");
+        assert!(matches!(resolve(&program.to_string_lossy()), Ok(Target::Program { path, .. }) if path == program));
+        assert!(resolve(&dir.join("missing.exe").to_string_lossy()).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn validate_rejects_focusing_a_url() {
+        let spec = |path: &str| LaunchSpec { focus_existing: true, ..LaunchSpec::new(path) };
+        assert!(spec("https://example.com").validate().is_err());
+        assert!(spec("notepad").validate().is_ok());
+        assert!(LaunchSpec::new("https://example.com").validate().is_ok());
+
+        let bad_env = LaunchSpec { env: HashMap::from([("A=B".to_string(), "x".to_string())]), ..LaunchSpec::new("notepad") };
+        assert!(bad_env.validate().is_err());
+    }
+}
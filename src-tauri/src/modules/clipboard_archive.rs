@@ -0,0 +1,176 @@
+use super::clipboard_history::ClipboardItem;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+
+/// Current archive format version. Bump when the manifest layout changes.
+/// Version 2 hashes the content type along with the content.
+pub const ARCHIVE_VERSION: u32 = 2;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const BLOBS_DIR: &str = "blobs";
+
+/// Portable clipboard archive: a directory holding `manifest.json` with item
+/// metadata and a `blobs/` folder with each item's content, named by its hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub version: u32,
+    pub exported_at: i64,
+    pub items: Vec<ArchiveEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub id: u64,
+    pub content_type: String,
+    pub timestamp: i64,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abbreviation: Option<String>,
+    pub hash: String,
+}
+
+/// Selects which items get exported. Empty filter exports everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportFilter {
+    pub query: Option<String>,
+    pub content_type: Option<String>,
+    pub pinned_only: bool,
+    pub since: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub merged: usize,
+    pub evicted: usize,
+}
+
+/// Write items to an archive directory, returning the number of items exported
+pub fn write_archive(dir: &Path, items: &[ClipboardItem]) -> Result<usize> {
+    let blobs_dir = dir.join(BLOBS_DIR);
+    fs::create_dir_all(&blobs_dir)?;
+
+    let mut written = HashSet::new();
+    let mut entries = Vec::with_capacity(items.len());
+
+    for item in items {
+        let hash = item.content_hash();
+        if written.insert(hash.clone()) {
+            fs::write(blobs_dir.join(&hash), item.content.as_bytes())?;
+        }
+
+        entries.push(ArchiveEntry {
+            id: item.id,
+            content_type: item.content_type.clone(),
+            timestamp: item.timestamp,
+            pinned: item.pinned,
+            abbreviation: item.abbreviation.clone(),
+            hash,
+        });
+    }
+
+    let manifest = ArchiveManifest {
+        version: ARCHIVE_VERSION,
+        exported_at: Utc::now().timestamp(),
+        items: entries,
+    };
+    fs::write(dir.join(MANIFEST_FILE), serde_json::to_string_pretty(&manifest)?)?;
+
+    log::info!("Exported {} clipboard items to {:?}", manifest.items.len(), dir);
+    Ok(manifest.items.len())
+}
+
+/// Read items back from an archive directory. Entries whose blob is missing or
+/// does not match its recorded hash are skipped.
+pub fn read_archive(dir: &Path) -> Result<Vec<ClipboardItem>> {
+    let json = fs::read_to_string(dir.join(MANIFEST_FILE))
+        .map_err(|e| anyhow::anyhow!("Could not read archive manifest: {}", e))?;
+    let manifest: ArchiveManifest = serde_json::from_str(&json)?;
+
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(anyhow::anyhow!(
+            "Archive version {} is newer than supported version {}",
+            manifest.version,
+            ARCHIVE_VERSION
+        ));
+    }
+
+    let blobs_dir = dir.join(BLOBS_DIR);
+    let mut items = Vec::with_capacity(manifest.items.len());
+
+    for entry in manifest.items {
+        let content = match fs::read_to_string(blobs_dir.join(&entry.hash)) {
+            Ok(content) => content,
+            Err(e) => {
+                log::warn!("Skipping archive entry {}: {}", entry.id, e);
+                continue;
+            }
+        };
+
+        let mut item = ClipboardItem::restore(entry.id, content, entry.content_type, entry.timestamp, entry.pinned);
+        item.abbreviation = entry.abbreviation;
+        let hash = if manifest.version < 2 { item.legacy_content_hash() } else { item.content_hash() };
+        if hash != entry.hash {
+            log::warn!("Skipping archive entry {}: content hash mismatch", entry.id);
+            continue;
+        }
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("winshaper-archive-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn round_trips_items() {
+        let dir = temp_dir("round-trip");
+        let items = vec![
+            ClipboardItem::restore(1, "hello".to_string(), "text".to_string(), 10, true),
+            ClipboardItem::restore(2, "hello".to_string(), "snippet".to_string(), 20, false),
+        ];
+
+        assert_eq!(write_archive(&dir, &items).unwrap(), 2);
+        let read = read_archive(&dir).unwrap();
+        assert_eq!(read.len(), 2);
+        assert!(read[0].pinned);
+        assert_eq!(read[1].content_type, "snippet");
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn reads_version_1_archives_with_content_only_hashes() {
+        let dir = temp_dir("v1");
+        let item = ClipboardItem::restore(1, "legacy".to_string(), "text".to_string(), 10, false);
+        let hash = item.legacy_content_hash();
+        fs::create_dir_all(dir.join(BLOBS_DIR)).unwrap();
+        fs::write(dir.join(BLOBS_DIR).join(&hash), "legacy").unwrap();
+        let manifest = serde_json::json!({
+            "version": 1,
+            "exported_at": 0,
+            "items": [{ "id": 1, "content_type": "text", "timestamp": 10, "hash": hash }],
+        });
+        fs::write(dir.join(MANIFEST_FILE), manifest.to_string()).unwrap();
+
+        let read = read_archive(&dir).unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].content, "legacy");
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
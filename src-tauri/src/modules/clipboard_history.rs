@@ -1,10 +1,34 @@
 use super::Module;
+use super::clipboard_archive::{self, ExportFilter, ImportSummary};
+use super::clipboard_sync::{ClipboardSync, PairingInfo, PendingPairing, SyncClient, SyncPeerInfo, DEFAULT_SYNC_PORT};
+use super::snippets::{self, ExpansionContext, SNIPPET_CONTENT_TYPE};
+use super::hotkey::KeyChord;
+use super::quick_paste::{self, QuickPaste, QuickPasteHandler};
+use super::screenshots;
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 use serde::{Deserialize, Serialize};
-use chrono::Utc;
+use sha2::{Digest, Sha256};
+use chrono::{Local, Utc};
 use arboard::Clipboard;
 
+/// `content_type` of history items whose content is the path of a PNG file.
+/// The file is the history's own copy; it is deleted with the item, and
+/// never leaves this machine through sync or archives.
+pub const IMAGE_CONTENT_TYPE: &str = "image";
+
+/// Where image items keep their copies of the images
+fn images_dir() -> anyhow::Result<PathBuf> {
+    let app_data = dirs::data_dir()
+        .ok_or_else(|| anyhow::anyhow!("Could not find app data directory"))?;
+    Ok(app_data.join("WinShaper").join("clipboard_images"))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClipboardItem {
     pub id: u64,
@@ -12,45 +36,191 @@ pub struct ClipboardItem {
     pub content_type: String,
     pub timestamp: i64,
     pub preview: String,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abbreviation: Option<String>,
 }
 
 impl ClipboardItem {
     fn new(content: String, content_type: String) -> Self {
         let timestamp = Utc::now().timestamp();
-        let preview = if content.len() > 100 {
-            format!("{}...", &content[..100])
+        Self::restore(timestamp as u64, content, content_type, timestamp, false)
+    }
+
+    /// Rebuild an item from stored fields, regenerating the preview
+    pub(crate) fn restore(id: u64, content: String, content_type: String, timestamp: i64, pinned: bool) -> Self {
+        let preview = if content.chars().count() > 100 {
+            format!("{}...", content.chars().take(100).collect::<String>())
         } else {
             content.clone()
         };
 
         Self {
-            id: timestamp as u64,
+            id,
             content,
             content_type,
             timestamp,
             preview,
+            pinned,
+            abbreviation: None,
+        }
+    }
+
+    /// Hex-encoded SHA-256 of the content type and content, stable across machines
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.content_type.as_bytes());
+        hasher.update([0u8]);
+        hasher.update(self.content.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Hash of the content alone, as archive and sync protocol version 1 recorded it
+    pub fn legacy_content_hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.content.as_bytes()))
+    }
+
+    pub fn is_snippet(&self) -> bool {
+        self.content_type == SNIPPET_CONTENT_TYPE
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.content_type == IMAGE_CONTENT_TYPE
+    }
+
+    /// Pinned items and snippets are exempt from size and age limits
+    fn is_protected(&self) -> bool {
+        self.pinned || self.is_snippet()
+    }
+
+    /// Bytes the item contributes to the history size cap, including the
+    /// image file of an image item
+    fn size_bytes(&self) -> u64 {
+        let file = if self.is_image() { fs::metadata(&self.content).map_or(0, |meta| meta.len()) } else { 0 };
+        (self.content.len() + self.preview.len()) as u64 + file
+    }
+
+    /// Delete the file behind an image item that has left the history.
+    /// Only the history's own copies are touched.
+    fn discard(&self) {
+        if !self.is_image() {
+            return;
         }
+        let path = Path::new(&self.content);
+        if images_dir().is_ok_and(|dir| path.parent() == Some(dir.as_path())) {
+            if let Err(e) = fs::remove_file(path) {
+                log::warn!("Could not delete clipboard image {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    fn matches_query(&self, query_lower: &str) -> bool {
+        self.content.to_lowercase().contains(query_lower)
+            || self
+                .abbreviation
+                .as_ref()
+                .is_some_and(|abbreviation| abbreviation.to_lowercase().contains(query_lower))
+    }
+}
+
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Limits enforced by the retention engine. A `max_total_bytes` of 0 disables
+/// the size cap.
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    pub max_items: usize,
+    pub expiry_days: u32,
+    pub max_total_bytes: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_items: 200,
+            expiry_days: 30,
+            max_total_bytes: 50 * 1024 * 1024,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Drop expired items, then the oldest remaining items until both the item
+    /// count and the total size fit. Pinned items and snippets are never evicted.
+    /// Returns how many items were removed.
+    pub fn apply(&self, history: &mut Vec<ClipboardItem>, now: i64) -> usize {
+        let before = history.len();
+
+        let cutoff_timestamp = now - (self.expiry_days as i64 * 86400);
+        history.retain(|item| {
+            let keep = item.is_protected() || item.timestamp > cutoff_timestamp;
+            if !keep {
+                item.discard();
+            }
+            keep
+        });
+
+        let mut count = history.len();
+        let mut bytes: u64 = history.iter().map(ClipboardItem::size_bytes).sum();
+        let over_limit = |count: usize, bytes: u64| {
+            count > self.max_items || (self.max_total_bytes > 0 && bytes > self.max_total_bytes)
+        };
+
+        let mut oldest_first: Vec<usize> = (0..history.len())
+            .filter(|&index| !history[index].is_protected())
+            .collect();
+        oldest_first.sort_by_key(|&index| history[index].timestamp);
+
+        let mut keep = vec![true; history.len()];
+        for index in oldest_first {
+            if !over_limit(count, bytes) {
+                break;
+            }
+            keep[index] = false;
+            count -= 1;
+            bytes -= history[index].size_bytes();
+        }
+
+        let mut keep = keep.into_iter();
+        history.retain(|item| {
+            let kept = keep.next().unwrap_or(true);
+            if !kept {
+                item.discard();
+            }
+            kept
+        });
+
+        before - history.len()
     }
 }
 
 pub struct ClipboardHistory {
     enabled: bool,
-    max_items: usize,
-    expiry_days: u32,
+    retention: Arc<Mutex<RetentionPolicy>>,
+    retention_timer: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
     history: Arc<Mutex<Vec<ClipboardItem>>>,
     clipboard: Option<Clipboard>,
     last_content: Arc<Mutex<String>>,
+    sync_enabled: bool,
+    sync_port: u16,
+    sync: Option<ClipboardSync>,
+    quick_paste: QuickPaste,
 }
 
 impl ClipboardHistory {
     pub fn new() -> Self {
         Self {
             enabled: false,
-            max_items: 200,
-            expiry_days: 30,
+            retention: Arc::new(Mutex::new(RetentionPolicy::default())),
+            retention_timer: None,
             history: Arc::new(Mutex::new(Vec::new())),
             clipboard: None,
             last_content: Arc::new(Mutex::new(String::new())),
+            sync_enabled: false,
+            sync_port: DEFAULT_SYNC_PORT,
+            sync: None,
+            quick_paste: QuickPaste::new(),
         }
     }
 
@@ -70,25 +240,104 @@ impl ClipboardHistory {
 
         let mut history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
 
-        let item = ClipboardItem::new(content, content_type);
+        let mut item = ClipboardItem::new(content, content_type);
+        item.id = Self::next_id(&history);
         history.insert(0, item);
 
-        // Limit history size
-        if history.len() > self.max_items {
-            history.truncate(self.max_items);
+        self.retention_policy()?.apply(&mut history, Utc::now().timestamp());
+
+        log::info!("Added clipboard item. History size: {}", history.len());
+
+        Ok(())
+    }
+
+    /// Add a saved image, such as a screenshot, as an image item. The item
+    /// gets its own copy, so deleting the original does not break it.
+    pub fn add_image_item(&mut self, path: &Path) -> anyhow::Result<()> {
+        let dir = images_dir()?;
+        fs::create_dir_all(&dir)?;
+        let copy = dir.join(format!("{}.png", uuid::Uuid::new_v4()));
+        fs::copy(path, &copy)?;
+        self.add_item(copy.to_string_lossy().into_owned(), IMAGE_CONTENT_TYPE.to_string())
+    }
+
+    /// An id no item in `history` has yet. Ids are only ever handed out
+    /// here, never taken from peers or archives, so they stay near the clock.
+    fn next_id(history: &[ClipboardItem]) -> u64 {
+        let newest = history.iter().map(|item| item.id).max().unwrap_or(0);
+        newest.saturating_add(1).max(Utc::now().timestamp() as u64)
+    }
+
+    fn retention_policy(&self) -> anyhow::Result<RetentionPolicy> {
+        let policy = self.retention.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        Ok(policy.clone())
+    }
+
+    /// Run the retention engine over the whole history now
+    pub fn apply_retention(&self) -> anyhow::Result<usize> {
+        let policy = self.retention_policy()?;
+        let mut history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        let removed = policy.apply(&mut history, Utc::now().timestamp());
+        if removed > 0 {
+            log::info!("Retention removed {} clipboard items", removed);
         }
+        Ok(removed)
+    }
 
-        // Remove expired items
-        let cutoff_timestamp = Utc::now().timestamp() - (self.expiry_days as i64 * 86400);
-        history.retain(|item| item.timestamp > cutoff_timestamp);
+    /// Re-apply retention periodically so items expire without new copies
+    fn start_retention_timer(&mut self) {
+        if self.retention_timer.is_some() {
+            return;
+        }
 
-        log::info!("Added clipboard item. History size: {}", history.len());
+        let stop = Arc::new(AtomicBool::new(false));
+        let (flag, history, retention) = (stop.clone(), self.history.clone(), self.retention.clone());
+        let handle = thread::spawn(move || {
+            let mut last_run = Instant::now();
+            while !flag.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(500));
+                if last_run.elapsed() < RETENTION_INTERVAL {
+                    continue;
+                }
+                last_run = Instant::now();
+
+                let policy = match retention.lock() {
+                    Ok(policy) => policy.clone(),
+                    Err(_) => break,
+                };
+                if let Ok(mut history) = history.lock() {
+                    let removed = policy.apply(&mut history, Utc::now().timestamp());
+                    if removed > 0 {
+                        log::info!("Retention removed {} clipboard items", removed);
+                    }
+                }
+            }
+        });
+
+        self.retention_timer = Some((stop, handle));
+    }
+
+    fn stop_retention_timer(&mut self) {
+        if let Some((stop, handle)) = self.retention_timer.take() {
+            stop.store(true, Ordering::SeqCst);
+            let _ = handle.join();
+        }
+    }
 
+    pub fn set_pinned(&mut self, id: u64, pinned: bool) -> anyhow::Result<()> {
+        let mut history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        let item = history
+            .iter_mut()
+            .find(|item| item.id == id)
+            .ok_or_else(|| anyhow::anyhow!("Clipboard item {} not found", id))?;
+        item.pinned = pinned;
+        log::info!("Clipboard item {} pinned: {}", id, pinned);
         Ok(())
     }
 
     pub fn clear_history(&mut self) -> anyhow::Result<()> {
         let mut history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        history.iter().for_each(ClipboardItem::discard);
         history.clear();
         log::info!("Clipboard history cleared");
         Ok(())
@@ -100,11 +349,39 @@ impl ClipboardHistory {
     }
 
     pub fn copy_to_clipboard(&mut self, id: u64) -> anyhow::Result<()> {
+        self.copy_to_clipboard_with_inputs(id, &HashMap::new())
+    }
+
+    /// Copy an item to the clipboard. Snippets are expanded first, using `inputs`
+    /// for their `{input:Name}` placeholders.
+    pub fn copy_to_clipboard_with_inputs(&mut self, id: u64, inputs: &HashMap<String, String>) -> anyhow::Result<()> {
         let item = self.get_item(id)?;
         
         if let Some(item) = item {
             if let Some(ref mut clipboard) = self.clipboard {
-                clipboard.set_text(&item.content)?;
+                if item.is_snippet() {
+                    let current = clipboard.get_text().ok();
+                    let ctx = ExpansionContext {
+                        now: Local::now(),
+                        clipboard: current.as_deref(),
+                        inputs,
+                    };
+                    let text = snippets::expand(&item.content, &ctx)?;
+                    clipboard.set_text(&text)?;
+
+                    // Keep the expansion itself out of the history
+                    let mut last = self.last_content.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+                    *last = text;
+                } else if item.is_image() {
+                    let image = screenshots::load_png(Path::new(&item.content))?;
+                    clipboard.set_image(image.to_image_data())?;
+                } else {
+                    clipboard.set_text(&item.content)?;
+
+                    // Re-copying an item should not add a duplicate entry
+                    let mut last = self.last_content.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+                    *last = item.content.clone();
+                }
                 log::info!("Copied item {} to clipboard", id);
             }
         }
@@ -112,19 +389,292 @@ impl ClipboardHistory {
         Ok(())
     }
 
+    pub fn get_snippets(&self) -> anyhow::Result<Vec<ClipboardItem>> {
+        let history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        Ok(history.iter().filter(|item| item.is_snippet()).cloned().collect())
+    }
+
+    /// Create a snippet from a template, optionally bound to an abbreviation
+    pub fn add_snippet(&mut self, template: String, abbreviation: Option<String>) -> anyhow::Result<ClipboardItem> {
+        snippets::validate(&template)?;
+        let mut history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        let abbreviation = Self::check_abbreviation(&history, None, abbreviation)?;
+
+        let mut item = ClipboardItem::new(template, SNIPPET_CONTENT_TYPE.to_string());
+        item.abbreviation = abbreviation;
+        item.id = Self::next_id(&history);
+        history.insert(0, item.clone());
+
+        log::info!("Added snippet {}", item.id);
+        Ok(item)
+    }
+
+    pub fn update_snippet(&mut self, id: u64, template: String, abbreviation: Option<String>) -> anyhow::Result<ClipboardItem> {
+        snippets::validate(&template)?;
+        let mut history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        let abbreviation = Self::check_abbreviation(&history, Some(id), abbreviation)?;
+
+        let item = history
+            .iter_mut()
+            .find(|item| item.id == id && item.is_snippet())
+            .ok_or_else(|| anyhow::anyhow!("Snippet {} not found", id))?;
+        let mut updated = ClipboardItem::restore(id, template, SNIPPET_CONTENT_TYPE.to_string(), Utc::now().timestamp(), item.pinned);
+        updated.abbreviation = abbreviation;
+        *item = updated.clone();
+
+        log::info!("Updated snippet {}", id);
+        Ok(updated)
+    }
+
+    pub fn remove_item(&mut self, id: u64) -> anyhow::Result<()> {
+        let mut history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        let index = history
+            .iter()
+            .position(|item| item.id == id)
+            .ok_or_else(|| anyhow::anyhow!("Clipboard item {} not found", id))?;
+        history.remove(index).discard();
+        Ok(())
+    }
+
+    pub fn find_snippet_by_abbreviation(&self, abbreviation: &str) -> anyhow::Result<Option<ClipboardItem>> {
+        let history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        let abbreviation = abbreviation.trim();
+        Ok(history
+            .iter()
+            .find(|item| item.is_snippet() && item.abbreviation.as_deref() == Some(abbreviation))
+            .cloned())
+    }
+
+    /// Names the caller must supply before a snippet can be copied
+    pub fn snippet_inputs(&self, id: u64) -> anyhow::Result<Vec<String>> {
+        let item = self
+            .get_item(id)?
+            .filter(|item| item.is_snippet())
+            .ok_or_else(|| anyhow::anyhow!("Snippet {} not found", id))?;
+        Ok(snippets::required_inputs(&item.content))
+    }
+
+    /// Normalize an abbreviation and make sure no other snippet uses it
+    fn check_abbreviation(history: &[ClipboardItem], id: Option<u64>, abbreviation: Option<String>) -> anyhow::Result<Option<String>> {
+        let abbreviation = match abbreviation.map(|a| a.trim().to_string()) {
+            Some(a) if !a.is_empty() => a,
+            _ => return Ok(None),
+        };
+
+        if abbreviation.chars().any(char::is_whitespace) {
+            return Err(anyhow::anyhow!("Abbreviation must not contain whitespace"));
+        }
+
+        let taken = history.iter().any(|item| {
+            item.is_snippet() && Some(item.id) != id && item.abbreviation.as_deref() == Some(abbreviation.as_str())
+        });
+        if taken {
+            return Err(anyhow::anyhow!("Abbreviation '{}' is already assigned", abbreviation));
+        }
+
+        Ok(Some(abbreviation))
+    }
+
     pub fn search_history(&self, query: &str) -> anyhow::Result<Vec<ClipboardItem>> {
         let history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
         let query_lower = query.to_lowercase();
         
         let results: Vec<ClipboardItem> = history
             .iter()
-            .filter(|item| item.content.to_lowercase().contains(&query_lower))
+            .filter(|item| item.matches_query(&query_lower))
             .cloned()
             .collect();
 
         Ok(results)
     }
 
+    /// Items selected by an export filter, newest first
+    pub fn filter_items(&self, filter: &ExportFilter) -> anyhow::Result<Vec<ClipboardItem>> {
+        let history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        let query_lower = filter.query.as_deref().map(str::to_lowercase);
+
+        let results: Vec<ClipboardItem> = history
+            .iter()
+            .filter(|item| !filter.pinned_only || item.pinned)
+            .filter(|item| filter.content_type.as_ref().is_none_or(|t| &item.content_type == t))
+            .filter(|item| filter.since.is_none_or(|since| item.timestamp >= since))
+            .filter(|item| query_lower.as_deref().is_none_or(|q| item.matches_query(q)))
+            .cloned()
+            .collect();
+
+        Ok(results)
+    }
+
+    /// Merge items into the history. Items whose content hash is already present
+    /// are folded into the existing entry (keeping pins and the newest timestamp);
+    /// the result is passed through the retention engine.
+    pub fn merge_items(&mut self, items: Vec<ClipboardItem>) -> anyhow::Result<ImportSummary> {
+        let mut history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        let mut summary = ImportSummary::default();
+
+        let mut by_hash: HashMap<String, usize> = history
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (item.content_hash(), index))
+            .collect();
+
+        for mut item in items {
+            // The path would point at a file on another machine
+            if item.is_image() {
+                log::warn!("Skipping image item {} from another machine", item.id);
+                continue;
+            }
+            let hash = item.content_hash();
+            if let Some(&index) = by_hash.get(&hash) {
+                let existing = &mut history[index];
+                existing.pinned |= item.pinned;
+                existing.timestamp = existing.timestamp.max(item.timestamp);
+                summary.merged += 1;
+                continue;
+            }
+
+            // Ids from a peer or an archive mean nothing here
+            item.id = Self::next_id(&history);
+            if item.abbreviation.is_some() && Self::check_abbreviation(&history, None, item.abbreviation.clone()).is_err() {
+                log::warn!("Dropping conflicting abbreviation from imported snippet {}", item.id);
+                item.abbreviation = None;
+            }
+            by_hash.insert(hash, history.len());
+            history.push(item);
+            summary.imported += 1;
+        }
+
+        history.sort_by_key(|item| std::cmp::Reverse(item.timestamp));
+        summary.evicted = self.retention_policy()?.apply(&mut history, Utc::now().timestamp());
+
+        log::info!(
+            "Merged clipboard items: {} imported, {} merged, {} evicted",
+            summary.imported, summary.merged, summary.evicted
+        );
+        Ok(summary)
+    }
+
+    /// Export the items matching `filter` to an archive directory. Image
+    /// items stay behind, as their files are local.
+    pub fn export_archive(&self, dir: &Path, filter: &ExportFilter) -> anyhow::Result<usize> {
+        let mut items = self.filter_items(filter)?;
+        items.retain(|item| !item.is_image());
+        clipboard_archive::write_archive(dir, &items)
+    }
+
+    /// Import an archive directory, merging it into the current history
+    pub fn import_archive(&mut self, dir: &Path) -> anyhow::Result<ImportSummary> {
+        let items = clipboard_archive::read_archive(dir)?;
+        self.merge_items(items)
+    }
+
+    fn start_sync(&mut self) -> anyhow::Result<()> {
+        if self.sync.is_some() {
+            return Ok(());
+        }
+
+        let mut sync = ClipboardSync::new(ClipboardSync::state_path_for(self.sync_port)?, self.sync_port)?;
+        sync.start(self.history.clone())?;
+        self.sync = Some(sync);
+        Ok(())
+    }
+
+    fn stop_sync(&mut self) {
+        if let Some(mut sync) = self.sync.take() {
+            sync.stop();
+        }
+    }
+
+    fn sync_service(&self) -> anyhow::Result<&ClipboardSync> {
+        self.sync
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Clipboard sync is not enabled"))
+    }
+
+    /// Merge any items peers pushed to us since the last check
+    fn merge_received(&mut self) -> anyhow::Result<()> {
+        let received = match self.sync {
+            Some(ref sync) => sync.take_received()?,
+            None => return Ok(()),
+        };
+
+        if !received.is_empty() {
+            self.merge_items(received)?;
+        }
+        Ok(())
+    }
+
+    pub fn begin_sync_pairing(&self) -> anyhow::Result<PairingInfo> {
+        self.sync_service()?.begin_pairing()
+    }
+
+    pub fn pending_sync_pairings(&self) -> anyhow::Result<Vec<PendingPairing>> {
+        self.sync_service()?.pending_pairings()
+    }
+
+    pub fn confirm_sync_pairing(&self, instance_id: &str) -> anyhow::Result<SyncPeerInfo> {
+        self.sync_service()?.confirm_pairing(instance_id)
+    }
+
+    pub fn reject_sync_pairing(&self, instance_id: &str) -> anyhow::Result<()> {
+        self.sync_service()?.reject_pairing(instance_id)
+    }
+
+    pub fn unpair_sync_peer(&self, instance_id: &str) -> anyhow::Result<()> {
+        self.sync_service()?.unpair(instance_id)
+    }
+
+    pub fn get_sync_peers(&self) -> anyhow::Result<Vec<SyncPeerInfo>> {
+        self.sync_service()?.peers()
+    }
+
+    /// Handle for pairing with and syncing to peers. Both block on the
+    /// network, so callers release the history lock before using it.
+    pub fn sync_client(&self) -> anyhow::Result<SyncClient> {
+        Ok(self.sync_service()?.client())
+    }
+
+    /// Merge what a sync pass brought back, plus anything peers pushed meanwhile
+    pub fn merge_synced(&mut self, received: Vec<ClipboardItem>) -> anyhow::Result<()> {
+        self.merge_items(received)?;
+        self.merge_received()
+    }
+
+    /// Install the callback that shows the quick paste picker
+    pub fn set_quick_paste_handler(&mut self, handler: QuickPasteHandler) -> anyhow::Result<()> {
+        self.quick_paste.set_handler(handler);
+        if self.enabled {
+            self.quick_paste.start()?;
+        }
+        Ok(())
+    }
+
+    /// Show the quick paste picker, as its hotkey would
+    pub fn open_quick_paste(&self) -> anyhow::Result<()> {
+        if !self.enabled {
+            return Err(anyhow::anyhow!("Clipboard history is disabled"));
+        }
+        self.quick_paste.open()
+    }
+
+    /// Items for the quick paste picker, fuzzy-ranked against `query`
+    pub fn quick_paste_items(&self, query: &str) -> anyhow::Result<Vec<ClipboardItem>> {
+        let history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        Ok(quick_paste::rank_items(&history, query, self.quick_paste.limit()))
+    }
+
+    /// Put the chosen item on the clipboard and paste it into the window that
+    /// was focused when the picker opened
+    pub fn quick_paste_select(&mut self, id: u64, inputs: &HashMap<String, String>) -> anyhow::Result<()> {
+        self.copy_to_clipboard_with_inputs(id, inputs)?;
+        self.quick_paste.paste_into_target()
+    }
+
+    /// Close the picker without pasting and hand focus back
+    pub fn quick_paste_cancel(&mut self) -> anyhow::Result<()> {
+        self.quick_paste.restore_focus()
+    }
+
     fn start_monitoring(&mut self) -> anyhow::Result<()> {
         // Initialize clipboard
         self.clipboard = Some(Clipboard::new()?);
@@ -158,6 +708,7 @@ impl ClipboardHistory {
                 }
             }
         }
+        self.merge_received()?;
         Ok(())
     }
 
@@ -190,6 +741,8 @@ impl ClipboardHistory {
             
             let mut history = self.history.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
             *history = loaded_history;
+            history.sort_by_key(|item| std::cmp::Reverse(item.timestamp));
+            self.retention_policy()?.apply(&mut history, Utc::now().timestamp());
             
             log::info!("Clipboard history loaded from disk: {} items", history.len());
         }
@@ -222,6 +775,17 @@ impl Module for ClipboardHistory {
         
         // Start clipboard monitoring
         self.start_monitoring()?;
+        self.start_retention_timer();
+
+        if let Err(e) = self.quick_paste.start() {
+            log::warn!("Could not start quick paste: {}", e);
+        }
+
+        if self.sync_enabled {
+            if let Err(e) = self.start_sync() {
+                log::warn!("Could not start clipboard sync: {}", e);
+            }
+        }
         
         Ok(())
     }
@@ -229,6 +793,10 @@ impl Module for ClipboardHistory {
     fn disable(&mut self) -> anyhow::Result<()> {
         log::info!("Disabling Clipboard History module");
         self.enabled = false;
+
+        self.stop_sync();
+        self.stop_retention_timer();
+        self.quick_paste.stop();
         
         // Save history before stopping
         self.save_to_disk()?;
@@ -240,26 +808,217 @@ impl Module for ClipboardHistory {
     }
 
     fn get_settings(&self) -> HashMap<String, serde_json::Value> {
+        let policy = self.retention_policy().unwrap_or_default();
         let mut settings = HashMap::new();
-        settings.insert("max_items".to_string(), serde_json::json!(self.max_items));
-        settings.insert("expiry_days".to_string(), serde_json::json!(self.expiry_days));
+        settings.insert("max_items".to_string(), serde_json::json!(policy.max_items));
+        settings.insert("expiry_days".to_string(), serde_json::json!(policy.expiry_days));
+        settings.insert("max_total_mb".to_string(), serde_json::json!(policy.max_total_bytes / (1024 * 1024)));
+        settings.insert("sync_enabled".to_string(), serde_json::json!(self.sync_enabled));
+        settings.insert("sync_port".to_string(), serde_json::json!(self.sync_port));
+        settings.insert(
+            "quick_paste_hotkey".to_string(),
+            serde_json::json!(self.quick_paste.hotkey().map(|hotkey| hotkey.to_string()).unwrap_or_default()),
+        );
+        settings.insert("quick_paste_limit".to_string(), serde_json::json!(self.quick_paste.limit()));
         settings
     }
 
     fn update_settings(&mut self, settings: HashMap<String, serde_json::Value>) -> anyhow::Result<()> {
-        if let Some(max_items) = settings.get("max_items") {
-            if let Some(value) = max_items.as_u64() {
-                self.max_items = value as usize;
+        {
+            let mut policy = self.retention.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+
+            if let Some(max_items) = settings.get("max_items") {
+                if let Some(value) = max_items.as_u64() {
+                    policy.max_items = value as usize;
+                }
+            }
+
+            if let Some(expiry_days) = settings.get("expiry_days") {
+                if let Some(value) = expiry_days.as_u64() {
+                    policy.expiry_days = value as u32;
+                }
+            }
+
+            if let Some(max_total_mb) = settings.get("max_total_mb") {
+                if let Some(value) = max_total_mb.as_u64() {
+                    policy.max_total_bytes = value.saturating_mul(1024 * 1024);
+                }
             }
         }
 
-        if let Some(expiry_days) = settings.get("expiry_days") {
-            if let Some(value) = expiry_days.as_u64() {
-                self.expiry_days = value as u32;
+        // New limits take effect immediately, not on the next copy
+        self.apply_retention()?;
+
+        let previous_port = self.sync_port;
+        if let Some(sync_enabled) = settings.get("sync_enabled") {
+            if let Some(value) = sync_enabled.as_bool() {
+                self.sync_enabled = value;
+            }
+        }
+
+        if let Some(sync_port) = settings.get("sync_port") {
+            if let Some(value) = sync_port.as_u64() {
+                self.sync_port = u16::try_from(value)
+                    .map_err(|_| anyhow::anyhow!("Invalid sync port: {}", value))?;
+            }
+        }
+
+        if !self.enabled || !self.sync_enabled || self.sync_port != previous_port {
+            self.stop_sync();
+        }
+        if self.enabled && self.sync_enabled {
+            self.start_sync()?;
+        }
+
+        if let Some(quick_paste_limit) = settings.get("quick_paste_limit") {
+            if let Some(value) = quick_paste_limit.as_u64() {
+                self.quick_paste.set_limit(value as usize);
+            }
+        }
+
+        if let Some(quick_paste_hotkey) = settings.get("quick_paste_hotkey") {
+            if let Some(value) = quick_paste_hotkey.as_str() {
+                // An empty hotkey turns quick paste off
+                let hotkey = if value.trim().is_empty() { None } else { Some(KeyChord::parse(value)?) };
+                self.quick_paste.set_hotkey(hotkey)?;
             }
         }
 
         log::info!("Clipboard History settings updated");
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: u64, content: &str, timestamp: i64) -> ClipboardItem {
+        ClipboardItem::restore(id, content.to_string(), "text".to_string(), timestamp, false)
+    }
+
+    #[test]
+    fn merged_items_get_local_ids() {
+        let mut history = ClipboardHistory::new();
+        let now = Utc::now().timestamp();
+        history.merge_items(vec![item(7, "local", now)]).unwrap();
+
+        // Ids a peer made up, including ones that would overflow if bumped
+        let incoming = vec![item(u64::MAX, "a", now), item(u64::MAX, "b", now), item(7, "c", now)];
+        let summary = history.merge_items(incoming).unwrap();
+        assert_eq!(summary.imported, 3);
+
+        let mut ids: Vec<u64> = history.get_history().unwrap().iter().map(|item| item.id).collect();
+        assert!(ids.iter().all(|&id| id < u64::MAX));
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 4);
+    }
+
+    #[test]
+    fn snippet_abbreviations_are_unique_and_trimmed() {
+        let mut history = ClipboardHistory::new();
+        let sig = history.add_snippet("Regards, {input:Name}".to_string(), Some(" sig ".to_string())).unwrap();
+        assert_eq!(sig.abbreviation.as_deref(), Some("sig"));
+
+        assert!(history.add_snippet("Other".to_string(), Some("sig".to_string())).is_err());
+        assert!(history.add_snippet("Other".to_string(), Some("two words".to_string())).is_err());
+        assert!(history.add_snippet("{nope}".to_string(), None).is_err());
+
+        // A blank abbreviation means none
+        let plain = history.add_snippet("Plain".to_string(), Some("   ".to_string())).unwrap();
+        assert_eq!(plain.abbreviation, None);
+        assert!(history.update_snippet(plain.id, "Plain".to_string(), Some("sig".to_string())).is_err());
+        // Keeping its own abbreviation is fine
+        history.update_snippet(sig.id, "Cheers".to_string(), Some("sig".to_string())).unwrap();
+
+        let found = history.find_snippet_by_abbreviation(" sig").unwrap().unwrap();
+        assert_eq!((found.id, found.content.as_str()), (sig.id, "Cheers"));
+        assert!(history.find_snippet_by_abbreviation("sig2").unwrap().is_none());
+        assert_eq!(history.snippet_inputs(sig.id).unwrap(), Vec::<String>::new());
+    }
+
+    const NOW: i64 = 1_700_000_000;
+    const DAY: i64 = 86400;
+
+    fn policy(max_items: usize, expiry_days: u32, max_total_bytes: u64) -> RetentionPolicy {
+        RetentionPolicy { max_items, expiry_days, max_total_bytes }
+    }
+
+    /// Contents of what is left, newest first as the history keeps them
+    fn contents(history: &[ClipboardItem]) -> Vec<&str> {
+        history.iter().map(|item| item.content.as_str()).collect()
+    }
+
+    fn snippet(id: u64, content: &str, timestamp: i64) -> ClipboardItem {
+        ClipboardItem::restore(id, content.to_string(), SNIPPET_CONTENT_TYPE.to_string(), timestamp, false)
+    }
+
+    #[test]
+    fn retention_expires_old_items_but_not_protected_ones() {
+        let mut pinned = item(3, "old pinned", NOW - 40 * DAY);
+        pinned.pinned = true;
+        let mut history = vec![
+            item(1, "fresh", NOW - DAY),
+            // Exactly at the cutoff counts as expired
+            item(2, "at cutoff", NOW - 30 * DAY),
+            pinned,
+            snippet(4, "old snippet", NOW - 40 * DAY),
+            item(5, "just inside", NOW - 30 * DAY + 1),
+        ];
+
+        assert_eq!(policy(100, 30, 0).apply(&mut history, NOW), 1);
+        assert_eq!(contents(&history), vec!["fresh", "old pinned", "old snippet", "just inside"]);
+    }
+
+    #[test]
+    fn retention_evicts_oldest_first_until_the_count_fits() {
+        let mut pinned = item(1, "pinned", NOW - 50);
+        pinned.pinned = true;
+        let mut history = vec![
+            item(5, "e", NOW - 10),
+            item(4, "d", NOW - 20),
+            item(3, "c", NOW - 30),
+            item(2, "b", NOW - 40),
+            pinned,
+        ];
+
+        // Five items, three allowed: the two oldest unprotected go
+        assert_eq!(policy(3, 30, 0).apply(&mut history, NOW), 2);
+        assert_eq!(contents(&history), vec!["e", "d", "pinned"]);
+
+        // At the limit nothing more goes
+        assert_eq!(policy(3, 30, 0).apply(&mut history, NOW), 0);
+    }
+
+    #[test]
+    fn retention_evicts_until_the_size_fits_too() {
+        // Short items count their content and preview, so 2 bytes per character
+        let mut history = vec![
+            item(3, "cccc", NOW - 10),
+            item(2, "bbbb", NOW - 20),
+            item(1, "aaaa", NOW - 30),
+        ];
+        assert_eq!(policy(10, 30, 16).apply(&mut history, NOW), 1);
+        assert_eq!(contents(&history), vec!["cccc", "bbbb"]);
+
+        // Both limits apply: the count alone would keep two
+        let mut history = vec![item(2, "bbbb", NOW - 20), item(1, "aaaa", NOW - 30), item(3, "cc", NOW - 40)];
+        assert_eq!(policy(2, 30, 8).apply(&mut history, NOW), 2);
+        assert_eq!(contents(&history), vec!["bbbb"]);
+
+        // A zero byte limit turns the size cap off
+        let mut history = vec![item(1, &"x".repeat(1000), NOW)];
+        assert_eq!(policy(10, 30, 0).apply(&mut history, NOW), 0);
+    }
+
+    #[test]
+    fn retention_never_evicts_protected_items_to_fit() {
+        let mut pinned = item(1, "pinned pinned", NOW - 30);
+        pinned.pinned = true;
+        let mut history = vec![item(3, "new", NOW - 10), snippet(2, "snippet snippet", NOW - 20), pinned];
+
+        // Protected items alone are over both limits; everything else goes
+        assert_eq!(policy(1, 30, 10).apply(&mut history, NOW), 1);
+        assert_eq!(contents(&history), vec!["snippet snippet", "pinned pinned"]);
+    }
+}
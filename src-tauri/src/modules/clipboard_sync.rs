@@ -0,0 +1,1041 @@
+use super::clipboard_history::ClipboardItem;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use chrono::Utc;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+pub const DEFAULT_SYNC_PORT: u16 = 47810;
+
+/// Version 2 hashes the content type along with the content; version 3
+/// pairs with a key commitment and a check code
+const PROTOCOL_VERSION: u32 = 3;
+/// Oldest version still answered, using its content-only hashes
+const MIN_PROTOCOL_VERSION: u32 = 1;
+/// Older versions pair without a check code, so they can't pair at all
+const MIN_PAIRING_VERSION: u32 = 3;
+const PAIRING_WINDOW: Duration = Duration::from_secs(120);
+const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_FRAME_BYTES: usize = 32 * 1024 * 1024;
+/// Connections handled at once; more are turned away until one finishes
+const MAX_CONNECTIONS: usize = 8;
+const NONCE_LEN: usize = 12;
+const PAIRING_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// A paired WinShaper instance and the key shared with it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SyncPeer {
+    instance_id: String,
+    name: String,
+    address: String,
+    key: String,
+    #[serde(default)]
+    last_synced: Option<i64>,
+}
+
+/// Peer details safe to hand to the frontend (no key material)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncPeerInfo {
+    pub instance_id: String,
+    pub name: String,
+    pub address: String,
+    pub last_synced: Option<i64>,
+}
+
+impl From<&SyncPeer> for SyncPeerInfo {
+    fn from(peer: &SyncPeer) -> Self {
+        Self {
+            instance_id: peer.instance_id.clone(),
+            name: peer.name.clone(),
+            address: peer.address.clone(),
+            last_synced: peer.last_synced,
+        }
+    }
+}
+
+/// A pairing whose key exchange finished, kept aside until the user has
+/// checked that both machines show the same `check_code`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingPairing {
+    pub instance_id: String,
+    pub name: String,
+    pub address: String,
+    pub check_code: String,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Clone)]
+struct Unconfirmed {
+    peer: SyncPeer,
+    check_code: String,
+    since: Instant,
+    expires_at: i64,
+}
+
+impl From<&Unconfirmed> for PendingPairing {
+    fn from(unconfirmed: &Unconfirmed) -> Self {
+        Self {
+            instance_id: unconfirmed.peer.instance_id.clone(),
+            name: unconfirmed.peer.name.clone(),
+            address: unconfirmed.peer.address.clone(),
+            check_code: unconfirmed.check_code.clone(),
+            expires_at: unconfirmed.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairingInfo {
+    pub code: String,
+    pub port: u16,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub peers_reached: usize,
+    pub items_sent: usize,
+    pub items_received: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SyncState {
+    instance_id: String,
+    peers: Vec<SyncPeer>,
+}
+
+/// First frame on every connection, sent in the clear. A pairing initiator
+/// only commits to its public key here and reveals it after the reply.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Hello {
+    Pair { version: u32, instance_id: String, name: String, port: u16, key_commitment: String },
+    Sync { version: u32, instance_id: String },
+}
+
+/// The responder's answer to `Hello::Pair`
+#[derive(Debug, Serialize, Deserialize)]
+struct PairReply {
+    version: u32,
+    instance_id: String,
+    name: String,
+    port: u16,
+    public_key: String,
+}
+
+/// The initiator's public key, matching its commitment
+#[derive(Debug, Serialize, Deserialize)]
+struct Reveal {
+    public_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Confirm {
+    mac: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OfferEntry {
+    hash: String,
+    pinned: bool,
+}
+
+/// Encrypted messages exchanged after the hello. The initiator offers the hashes
+/// it holds, the responder replies with what the initiator is missing plus the
+/// hashes it wants, and the initiator sends those back.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SyncMessage {
+    Offer { entries: Vec<OfferEntry> },
+    Reply { items: Vec<ClipboardItem>, want: Vec<String> },
+    Items { items: Vec<ClipboardItem> },
+}
+
+struct Shared {
+    state: SyncState,
+    state_path: PathBuf,
+    name: String,
+    port: u16,
+    pairing: Option<(String, Instant)>,
+    unconfirmed: Vec<Unconfirmed>,
+}
+
+impl Shared {
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.state_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.state_path, serde_json::to_string_pretty(&self.state)?)?;
+        Ok(())
+    }
+
+    fn peer_key(&self, instance_id: &str) -> Result<[u8; 32]> {
+        let peer = self
+            .state
+            .peers
+            .iter()
+            .find(|peer| peer.instance_id == instance_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown sync peer {}", instance_id))?;
+        decode_key(&peer.key)
+    }
+
+    fn upsert_peer(&mut self, peer: SyncPeer) {
+        self.state.peers.retain(|existing| existing.instance_id != peer.instance_id);
+        self.state.peers.push(peer);
+    }
+
+    /// Hold a freshly paired peer until the user confirms its check code
+    fn hold_unconfirmed(&mut self, peer: SyncPeer, check_code: String) -> PendingPairing {
+        self.unconfirmed.retain(|held| held.peer.instance_id != peer.instance_id);
+        let unconfirmed = Unconfirmed {
+            peer,
+            check_code,
+            since: Instant::now(),
+            expires_at: Utc::now().timestamp() + PAIRING_WINDOW.as_secs() as i64,
+        };
+        let pending = PendingPairing::from(&unconfirmed);
+        self.unconfirmed.push(unconfirmed);
+        pending
+    }
+
+    fn drop_expired(&mut self) {
+        self.unconfirmed.retain(|held| held.since.elapsed() <= PAIRING_WINDOW);
+    }
+
+    fn mark_synced(&mut self, instance_id: &str) {
+        if let Some(peer) = self.state.peers.iter_mut().find(|peer| peer.instance_id == instance_id) {
+            peer.last_synced = Some(Utc::now().timestamp());
+        }
+    }
+}
+
+/// End-to-end encrypted clipboard sync between paired instances on the LAN.
+///
+/// Pairing runs an X25519 exchange in which the initiator commits to its key
+/// before seeing the responder's. Both machines then show a six-digit check
+/// code derived from the two keys, and the key is only stored per peer once
+/// the user confirms the codes match on each side. The one-time pairing code
+/// authenticates the exchange as a second factor. Sync sessions are push-pull over TCP
+/// with every message sealed by ChaCha20-Poly1305. Received items are queued
+/// and merged by content hash, so the same item arriving twice is harmless.
+pub struct ClipboardSync {
+    shared: Arc<Mutex<Shared>>,
+    inbox: Arc<Mutex<Vec<ClipboardItem>>>,
+    stop: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ClipboardSync {
+    /// Create a sync service persisting its identity and peers at `state_path`.
+    /// Separate state files let several instances run side by side on one machine.
+    pub fn new(state_path: PathBuf, port: u16) -> Result<Self> {
+        let state = if state_path.exists() {
+            serde_json::from_str(&fs::read_to_string(&state_path)?)?
+        } else {
+            SyncState {
+                instance_id: random_hex(16),
+                peers: Vec::new(),
+            }
+        };
+
+        let name = std::env::var("COMPUTERNAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| "WinShaper".to_string());
+
+        let shared = Shared {
+            state,
+            state_path,
+            name,
+            port,
+            pairing: None,
+            unconfirmed: Vec::new(),
+        };
+        shared.save()?;
+
+        Ok(Self {
+            shared: Arc::new(Mutex::new(shared)),
+            inbox: Arc::new(Mutex::new(Vec::new())),
+            stop: Arc::new(AtomicBool::new(false)),
+            workers: Vec::new(),
+        })
+    }
+
+    /// State file in %APPDATA%/WinShaper/ for the instance listening on `port`.
+    /// Instances on one machine need different ports, so each gets its own
+    /// identity and peer list.
+    pub fn state_path_for(port: u16) -> Result<PathBuf> {
+        let app_data = dirs::data_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not find app data directory"))?;
+        let file = if port == DEFAULT_SYNC_PORT {
+            "clipboard_sync.json".to_string()
+        } else {
+            format!("clipboard_sync_{}.json", port)
+        };
+        Ok(app_data.join("WinShaper").join(file))
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.workers.is_empty()
+    }
+
+    /// Start listening for peers and syncing with paired peers in the background.
+    /// `history` is read to answer peers; received items land in the inbox.
+    pub fn start(&mut self, history: Arc<Mutex<Vec<ClipboardItem>>>) -> Result<()> {
+        if self.is_running() {
+            return Ok(());
+        }
+
+        let mut port = lock(&self.shared)?.port;
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        // Port 0 picks a free port; peers need the real one
+        port = listener.local_addr()?.port();
+        lock(&self.shared)?.port = port;
+        self.stop.store(false, Ordering::SeqCst);
+
+        let (shared, inbox, stop, local) = (self.shared.clone(), self.inbox.clone(), self.stop.clone(), history.clone());
+        self.workers.push(thread::spawn(move || {
+            let active = Arc::new(AtomicUsize::new(0));
+            while !stop.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, addr)) => {
+                        // Each connection gets its own thread, so a slow or idle
+                        // peer only holds up itself
+                        if active.load(Ordering::SeqCst) >= MAX_CONNECTIONS {
+                            log::warn!("Turning away clipboard sync connection from {}: too many open", addr);
+                            continue;
+                        }
+                        active.fetch_add(1, Ordering::SeqCst);
+                        let (shared, local, inbox, active) = (shared.clone(), local.clone(), inbox.clone(), active.clone());
+                        thread::spawn(move || {
+                            if let Err(e) = handle_connection(stream, addr, &shared, &local, &inbox) {
+                                log::warn!("Clipboard sync connection from {} failed: {}", addr, e);
+                            }
+                            active.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(200)),
+                    Err(e) => log::warn!("Clipboard sync accept failed: {}", e),
+                }
+            }
+        }));
+
+        let (shared, inbox, stop) = (self.shared.clone(), self.inbox.clone(), self.stop.clone());
+        self.workers.push(thread::spawn(move || {
+            let mut last_sync = Instant::now();
+            while !stop.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(200));
+                if last_sync.elapsed() < SYNC_INTERVAL {
+                    continue;
+                }
+                last_sync = Instant::now();
+
+                let snapshot = match history.lock() {
+                    Ok(history) => history.clone(),
+                    Err(_) => break,
+                };
+                match sync_all(&shared, &snapshot) {
+                    Ok((_, received)) if !received.is_empty() => {
+                        if let Ok(mut inbox) = inbox.lock() {
+                            inbox.extend(received);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Periodic clipboard sync failed: {}", e),
+                }
+            }
+        }));
+
+        log::info!("Clipboard sync listening on port {}", port);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        log::info!("Clipboard sync stopped");
+    }
+
+    /// Open a pairing window and return the code the other instance must enter.
+    /// The code is valid for a single attempt within the window.
+    pub fn begin_pairing(&self) -> Result<PairingInfo> {
+        let mut shared = lock(&self.shared)?;
+        let code = pairing_code();
+        shared.pairing = Some((code.clone(), Instant::now()));
+
+        Ok(PairingInfo {
+            code,
+            port: shared.port,
+            expires_at: Utc::now().timestamp() + PAIRING_WINDOW.as_secs() as i64,
+        })
+    }
+
+    /// Pairings waiting for the user to compare check codes
+    pub fn pending_pairings(&self) -> Result<Vec<PendingPairing>> {
+        let mut shared = lock(&self.shared)?;
+        shared.drop_expired();
+        Ok(shared.unconfirmed.iter().map(PendingPairing::from).collect())
+    }
+
+    /// Keep a pending pairing once the user has seen the same check code on
+    /// both machines. Each side confirms its own.
+    pub fn confirm_pairing(&self, instance_id: &str) -> Result<SyncPeerInfo> {
+        let mut shared = lock(&self.shared)?;
+        shared.drop_expired();
+        let index = shared
+            .unconfirmed
+            .iter()
+            .position(|held| held.peer.instance_id == instance_id)
+            .ok_or_else(|| anyhow::anyhow!("No pending pairing with {} (expired?)", instance_id))?;
+        let peer = shared.unconfirmed.remove(index).peer;
+        let info = SyncPeerInfo::from(&peer);
+        shared.upsert_peer(peer);
+        shared.save()?;
+
+        log::info!("Paired with clipboard sync peer {} at {}", info.name, info.address);
+        Ok(info)
+    }
+
+    /// Forget a pending pairing, e.g. because the check codes differ
+    pub fn reject_pairing(&self, instance_id: &str) -> Result<()> {
+        let mut shared = lock(&self.shared)?;
+        shared.unconfirmed.retain(|held| held.peer.instance_id != instance_id);
+        Ok(())
+    }
+
+    pub fn unpair(&self, instance_id: &str) -> Result<()> {
+        let mut shared = lock(&self.shared)?;
+        shared.state.peers.retain(|peer| peer.instance_id != instance_id);
+        shared.save()
+    }
+
+    pub fn peers(&self) -> Result<Vec<SyncPeerInfo>> {
+        let shared = lock(&self.shared)?;
+        Ok(shared.state.peers.iter().map(SyncPeerInfo::from).collect())
+    }
+
+    /// Handle for pairing and syncing, which block on the network and so
+    /// should run without holding whatever lock guards this service
+    pub fn client(&self) -> SyncClient {
+        SyncClient { shared: self.shared.clone() }
+    }
+
+    /// Drain items received from peers since the last call
+    pub fn take_received(&self) -> Result<Vec<ClipboardItem>> {
+        let mut inbox = lock(&self.inbox)?;
+        Ok(std::mem::take(&mut *inbox))
+    }
+}
+
+impl Drop for ClipboardSync {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The initiating side of pairing and sync, detached from `ClipboardSync`
+#[derive(Clone)]
+pub struct SyncClient {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl SyncClient {
+    /// Pair with the instance at `address` that is showing `code`. The peer
+    /// is kept once the user confirms the returned check code.
+    pub fn pair_with(&self, address: &str, code: &str) -> Result<PendingPairing> {
+        let (our_id, our_name, our_port) = {
+            let shared = lock(&self.shared)?;
+            (shared.state.instance_id.clone(), shared.name.clone(), shared.port)
+        };
+
+        let mut stream = connect(address)?;
+        let secret = EphemeralSecret::random_from_rng(OsRng);
+        let our_public = to_hex(PublicKey::from(&secret).as_bytes());
+
+        write_json(&mut stream, &Hello::Pair {
+            version: PROTOCOL_VERSION,
+            instance_id: our_id.clone(),
+            name: our_name,
+            port: our_port,
+            key_commitment: key_commitment(&our_public),
+        })?;
+
+        let reply: PairReply = read_json(&mut stream)
+            .map_err(|_| anyhow::anyhow!("Peer did not answer the pairing request"))?;
+        check_pairing_version(reply.version)?;
+        let PairReply { instance_id: their_id, name: their_name, public_key: their_public, .. } = reply;
+        // The peer's key is fixed now, so ours can go out
+        write_json(&mut stream, &Reveal { public_key: our_public.clone() })?;
+
+        let shared_secret = secret.diffie_hellman(&decode_public(&their_public)?);
+        let (confirm_key, sync_key) = derive_pairing_keys(code, shared_secret.as_bytes(), &our_public, &their_public)?;
+
+        write_json(&mut stream, &Confirm { mac: confirm_mac(&confirm_key, &our_id)? })?;
+        let confirm: Confirm = read_json(&mut stream)
+            .map_err(|_| anyhow::anyhow!("Pairing rejected by peer (wrong or expired code?)"))?;
+        verify_confirm(&confirm_key, &their_id, &confirm.mac)?;
+
+        let peer = SyncPeer {
+            instance_id: their_id,
+            name: their_name,
+            address: address.to_string(),
+            key: to_hex(&sync_key),
+            last_synced: None,
+        };
+        let pending = lock(&self.shared)?.hold_unconfirmed(peer, check_code(&our_public, &their_public));
+        log::info!("Paired with {} at {}, waiting for the check code to be confirmed", pending.name, pending.address);
+        Ok(pending)
+    }
+
+    /// Sync with every paired peer now, returning the items they sent
+    pub fn sync_now(&self, local: &[ClipboardItem]) -> Result<(SyncReport, Vec<ClipboardItem>)> {
+        sync_all(&self.shared, local)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> Result<std::sync::MutexGuard<'_, T>> {
+    mutex.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))
+}
+
+fn sync_all(shared: &Arc<Mutex<Shared>>, local: &[ClipboardItem]) -> Result<(SyncReport, Vec<ClipboardItem>)> {
+    let (our_id, peers) = {
+        let shared = lock(shared)?;
+        (shared.state.instance_id.clone(), shared.state.peers.clone())
+    };
+
+    let mut report = SyncReport::default();
+    let mut received = Vec::new();
+
+    for peer in peers {
+        match sync_with_peer(&our_id, &peer, local) {
+            Ok((sent, items)) => {
+                report.peers_reached += 1;
+                report.items_sent += sent;
+                report.items_received += items.len();
+                received.extend(items);
+                lock(shared)?.mark_synced(&peer.instance_id);
+            }
+            Err(e) => log::warn!("Could not sync with {} ({}): {}", peer.name, peer.address, e),
+        }
+    }
+
+    if report.peers_reached > 0 {
+        lock(shared)?.save()?;
+    }
+    Ok((report, received))
+}
+
+/// Initiator side of a sync session. Returns (items sent, items received).
+fn sync_with_peer(our_id: &str, peer: &SyncPeer, local: &[ClipboardItem]) -> Result<(usize, Vec<ClipboardItem>)> {
+    let key = decode_key(&peer.key)?;
+    let mut stream = connect(&peer.address)?;
+
+    write_json(&mut stream, &Hello::Sync {
+        version: PROTOCOL_VERSION,
+        instance_id: our_id.to_string(),
+    })?;
+
+    // Image items are paths to local files, so they stay on this machine
+    let local: Vec<&ClipboardItem> = local.iter().filter(|item| !item.is_image()).collect();
+    let entries = local
+        .iter()
+        .map(|item| OfferEntry { hash: item.content_hash(), pinned: item.pinned })
+        .collect();
+    write_sealed(&mut stream, &key, our_id, &SyncMessage::Offer { entries })?;
+
+    let (items, want) = match read_sealed(&mut stream, &key, &peer.instance_id)? {
+        SyncMessage::Reply { items, want } => (items, want),
+        _ => return Err(anyhow::anyhow!("Unexpected sync message from peer")),
+    };
+
+    let outgoing: Vec<ClipboardItem> = local
+        .into_iter()
+        .filter(|item| want.contains(&item.content_hash()))
+        .cloned()
+        .collect();
+    let sent = outgoing.len();
+    write_sealed(&mut stream, &key, our_id, &SyncMessage::Items { items: outgoing })?;
+
+    Ok((sent, items))
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    addr: SocketAddr,
+    shared: &Arc<Mutex<Shared>>,
+    history: &Arc<Mutex<Vec<ClipboardItem>>>,
+    inbox: &Arc<Mutex<Vec<ClipboardItem>>>,
+) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    match read_json(&mut stream)? {
+        Hello::Pair { version, instance_id, name, port, key_commitment } => {
+            check_pairing_version(version)?;
+            respond_pairing(&mut stream, addr, shared, instance_id, name, port, key_commitment)
+        }
+        Hello::Sync { version, instance_id } => {
+            check_version(version)?;
+            respond_sync(&mut stream, shared, history, inbox, instance_id, version)
+        }
+    }
+}
+
+fn respond_pairing(
+    stream: &mut TcpStream,
+    addr: SocketAddr,
+    shared: &Arc<Mutex<Shared>>,
+    their_id: String,
+    their_name: String,
+    their_port: u16,
+    their_commitment: String,
+) -> Result<()> {
+    // Taking the code makes every pairing window single-use, so a wrong guess
+    // closes it instead of allowing repeated attempts.
+    let (code, our_id, our_name, our_port) = {
+        let mut shared = lock(shared)?;
+        let (code, started) = shared
+            .pairing
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Pairing request from {} but no pairing in progress", addr))?;
+        if started.elapsed() > PAIRING_WINDOW {
+            return Err(anyhow::anyhow!("Pairing code expired"));
+        }
+        (code, shared.state.instance_id.clone(), shared.name.clone(), shared.port)
+    };
+
+    let secret = EphemeralSecret::random_from_rng(OsRng);
+    let our_public = to_hex(PublicKey::from(&secret).as_bytes());
+
+    write_json(stream, &PairReply {
+        version: PROTOCOL_VERSION,
+        instance_id: our_id.clone(),
+        name: our_name,
+        port: our_port,
+        public_key: our_public.clone(),
+    })?;
+
+    let Reveal { public_key: their_public } = read_json(stream)?;
+    if key_commitment(&their_public) != their_commitment {
+        return Err(anyhow::anyhow!("Pairing peer from {} revealed a key it had not committed to", addr));
+    }
+
+    let shared_secret = secret.diffie_hellman(&decode_public(&their_public)?);
+    let (confirm_key, sync_key) = derive_pairing_keys(&code, shared_secret.as_bytes(), &our_public, &their_public)?;
+
+    let confirm: Confirm = read_json(stream)?;
+    verify_confirm(&confirm_key, &their_id, &confirm.mac)?;
+
+    let peer = SyncPeer {
+        instance_id: their_id,
+        name: their_name,
+        address: SocketAddr::new(addr.ip(), their_port).to_string(),
+        key: to_hex(&sync_key),
+        last_synced: None,
+    };
+    // Held before answering, so it is listed by the time the initiator shows its code
+    let pending = lock(shared)?.hold_unconfirmed(peer, check_code(&our_public, &their_public));
+    log::info!("Paired with {} at {}, waiting for the check code to be confirmed", pending.name, pending.address);
+    write_json(stream, &Confirm { mac: confirm_mac(&confirm_key, &our_id)? })
+}
+
+fn respond_sync(
+    stream: &mut TcpStream,
+    shared: &Arc<Mutex<Shared>>,
+    history: &Arc<Mutex<Vec<ClipboardItem>>>,
+    inbox: &Arc<Mutex<Vec<ClipboardItem>>>,
+    their_id: String,
+    version: u32,
+) -> Result<()> {
+    let (our_id, key) = {
+        let shared = lock(shared)?;
+        (shared.state.instance_id.clone(), shared.peer_key(&their_id)?)
+    };
+
+    let offered: HashMap<String, bool> = match read_sealed(stream, &key, &their_id)? {
+        SyncMessage::Offer { entries } => entries.into_iter().map(|entry| (entry.hash, entry.pinned)).collect(),
+        _ => return Err(anyhow::anyhow!("Unexpected sync message from peer")),
+    };
+
+    let mut local = lock(history)?.clone();
+    local.retain(|item| !item.is_image());
+    let ours: HashMap<String, bool> = local.iter().map(|item| (item_hash(item, version), item.pinned)).collect();
+
+    // Send what the peer lacks, or holds without our pin; ask for the reverse.
+    let items = local
+        .into_iter()
+        .filter(|item| match offered.get(&item_hash(item, version)) {
+            Some(&their_pin) => item.pinned && !their_pin,
+            None => true,
+        })
+        .collect();
+    let want = offered
+        .iter()
+        .filter(|(hash, &their_pin)| match ours.get(*hash) {
+            Some(&our_pin) => their_pin && !our_pin,
+            None => true,
+        })
+        .map(|(hash, _)| hash.clone())
+        .collect();
+
+    write_sealed(stream, &key, &our_id, &SyncMessage::Reply { items, want })?;
+
+    let received = match read_sealed(stream, &key, &their_id)? {
+        SyncMessage::Items { items } => items,
+        _ => return Err(anyhow::anyhow!("Unexpected sync message from peer")),
+    };
+    lock(inbox)?.extend(received);
+
+    let mut shared = lock(shared)?;
+    shared.mark_synced(&their_id);
+    shared.save()
+}
+
+fn connect(address: &str) -> Result<TcpStream> {
+    let addr = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Could not resolve {}", address))?;
+    let stream = TcpStream::connect_timeout(&addr, IO_TIMEOUT)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    Ok(stream)
+}
+
+fn check_pairing_version(version: u32) -> Result<()> {
+    if version < MIN_PAIRING_VERSION {
+        return Err(anyhow::anyhow!(
+            "Sync protocol version {} cannot pair; update WinShaper on both machines",
+            version
+        ));
+    }
+    check_version(version)
+}
+
+fn check_version(version: u32) -> Result<()> {
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+        return Err(anyhow::anyhow!(
+            "Sync protocol version {} is not supported (expected {} to {})",
+            version,
+            MIN_PROTOCOL_VERSION,
+            PROTOCOL_VERSION
+        ));
+    }
+    Ok(())
+}
+
+/// An item's hash as a peer speaking `version` computes it
+fn item_hash(item: &ClipboardItem, version: u32) -> String {
+    if version < 2 {
+        item.legacy_content_hash()
+    } else {
+        item.content_hash()
+    }
+}
+
+fn write_frame(stream: &mut TcpStream, data: &[u8]) -> Result<()> {
+    stream.write_all(&(data.len() as u32).to_be_bytes())?;
+    stream.write_all(data)?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn read_frame(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_BYTES {
+        return Err(anyhow::anyhow!("Sync frame too large: {} bytes", len));
+    }
+
+    let mut data = vec![0u8; len];
+    stream.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn write_json<T: Serialize>(stream: &mut TcpStream, value: &T) -> Result<()> {
+    write_frame(stream, &serde_json::to_vec(value)?)
+}
+
+fn read_json<T: DeserializeOwned>(stream: &mut TcpStream) -> Result<T> {
+    Ok(serde_json::from_slice(&read_frame(stream)?)?)
+}
+
+/// Encrypt a message as nonce || ciphertext, binding the sender's instance ID
+fn write_sealed(stream: &mut TcpStream, key: &[u8; 32], sender_id: &str, message: &SyncMessage) -> Result<()> {
+    let cipher = ChaCha20Poly1305::new(key.into());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let plaintext = serde_json::to_vec(message)?;
+    let ciphertext = cipher
+        .encrypt(&nonce, Payload { msg: &plaintext, aad: sender_id.as_bytes() })
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt sync message"))?;
+
+    let mut frame = nonce.to_vec();
+    frame.extend_from_slice(&ciphertext);
+    write_frame(stream, &frame)
+}
+
+fn read_sealed(stream: &mut TcpStream, key: &[u8; 32], sender_id: &str) -> Result<SyncMessage> {
+    let frame = read_frame(stream)?;
+    if frame.len() < NONCE_LEN {
+        return Err(anyhow::anyhow!("Sync frame too short"));
+    }
+
+    let (nonce, ciphertext) = frame.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(key.into());
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: sender_id.as_bytes() })
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt sync message (peer key mismatch?)"))?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// Derive (confirmation key, long-term sync key) from the X25519 secret, salted
+/// with the pairing code and bound to both public keys. The code is only a
+/// second factor: a man in the middle can test guesses offline against a
+/// confirmation MAC, but still fails the check code comparison.
+fn derive_pairing_keys(code: &str, shared_secret: &[u8], public_a: &str, public_b: &str) -> Result<([u8; 32], [u8; 32])> {
+    let code = normalize_code(code);
+    let (first, second) = if public_a < public_b { (public_a, public_b) } else { (public_b, public_a) };
+    let hkdf = Hkdf::<Sha256>::new(Some(code.as_bytes()), shared_secret);
+
+    let mut confirm_key = [0u8; 32];
+    let mut sync_key = [0u8; 32];
+    hkdf.expand(format!("winshaper-pair-confirm:{}:{}", first, second).as_bytes(), &mut confirm_key)
+        .map_err(|_| anyhow::anyhow!("Key derivation failed"))?;
+    hkdf.expand(format!("winshaper-sync-key:{}:{}", first, second).as_bytes(), &mut sync_key)
+        .map_err(|_| anyhow::anyhow!("Key derivation failed"))?;
+
+    Ok((confirm_key, sync_key))
+}
+
+/// What the pairing initiator sends in place of its public key
+fn key_commitment(public_key: &str) -> String {
+    to_hex(&Sha256::digest(format!("winshaper-pair-commit:{}", public_key).as_bytes()))
+}
+
+/// Six digits both users compare before a pairing is kept. The initiator
+/// commits to its key before seeing the responder's, so a man in the middle
+/// has to pick its own keys blind and matches both sides one time in a million.
+fn check_code(public_a: &str, public_b: &str) -> String {
+    let (first, second) = if public_a < public_b { (public_a, public_b) } else { (public_b, public_a) };
+    let digest = Sha256::digest(format!("winshaper-pair-check:{}:{}", first, second).as_bytes());
+    let number = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % 1_000_000;
+    format!("{:03} {:03}", number / 1000, number % 1000)
+}
+
+fn confirm_mac(confirm_key: &[u8; 32], instance_id: &str) -> Result<String> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(confirm_key)?;
+    mac.update(instance_id.as_bytes());
+    Ok(to_hex(&mac.finalize().into_bytes()))
+}
+
+fn verify_confirm(confirm_key: &[u8; 32], instance_id: &str, tag: &str) -> Result<()> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(confirm_key)?;
+    mac.update(instance_id.as_bytes());
+    mac.verify_slice(&from_hex(tag)?)
+        .map_err(|_| anyhow::anyhow!("Pairing code mismatch"))
+}
+
+fn pairing_code() -> String {
+    let mut bytes = [0u8; 8];
+    OsRng.fill_bytes(&mut bytes);
+    let chars: String = bytes
+        .iter()
+        .map(|b| PAIRING_ALPHABET[(*b as usize) % PAIRING_ALPHABET.len()] as char)
+        .collect();
+    format!("{}-{}", &chars[..4], &chars[4..])
+}
+
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+fn decode_public(hex: &str) -> Result<PublicKey> {
+    let bytes: [u8; 32] = from_hex(hex)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Invalid public key"))?;
+    Ok(PublicKey::from(bytes))
+}
+
+fn decode_key(hex: &str) -> Result<[u8; 32]> {
+    from_hex(hex)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Invalid sync key"))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return Err(anyhow::anyhow!("Invalid hex string"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| anyhow::anyhow!("Invalid hex string: {}", e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: u64, content: &str, pinned: bool) -> ClipboardItem {
+        ClipboardItem::restore(id, content.to_string(), "text".to_string(), id as i64, pinned)
+    }
+
+    fn instance(dir: &std::path::Path, name: &str, items: Vec<ClipboardItem>) -> (ClipboardSync, Arc<Mutex<Vec<ClipboardItem>>>) {
+        let history = Arc::new(Mutex::new(items));
+        let mut sync = ClipboardSync::new(dir.join(format!("{}.json", name)), 0).unwrap();
+        sync.start(history.clone()).unwrap();
+        (sync, history)
+    }
+
+    fn wait_for_received(sync: &ClipboardSync) -> Vec<ClipboardItem> {
+        let deadline = Instant::now() + IO_TIMEOUT;
+        loop {
+            let received = sync.take_received().unwrap();
+            if !received.is_empty() || Instant::now() > deadline {
+                return received;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn pairs_and_syncs_two_instances_on_localhost() {
+        let dir = std::env::temp_dir().join(format!("winshaper-sync-{}", random_hex(4)));
+        let (laptop, laptop_history) = instance(&dir, "laptop", vec![item(1, "shared", false), item(2, "from laptop", false)]);
+        let (desktop, _) = instance(&dir, "desktop", vec![item(3, "shared", true), item(4, "from desktop", false)]);
+
+        let pairing = desktop.begin_pairing().unwrap();
+        let pending = laptop
+            .client()
+            .pair_with(&format!("127.0.0.1:{}", pairing.port), &pairing.code.to_lowercase())
+            .unwrap();
+        assert_eq!(pending.instance_id, lock(&desktop.shared).unwrap().state.instance_id);
+
+        // Nothing is kept until each side confirms the same check code
+        assert!(laptop.peers().unwrap().is_empty());
+        assert!(desktop.peers().unwrap().is_empty());
+        let theirs = desktop.pending_pairings().unwrap();
+        assert_eq!(theirs.len(), 1);
+        assert_eq!(theirs[0].check_code, pending.check_code);
+        laptop.confirm_pairing(&pending.instance_id).unwrap();
+        desktop.confirm_pairing(&theirs[0].instance_id).unwrap();
+        assert_eq!(desktop.peers().unwrap().len(), 1);
+        assert!(desktop.pending_pairings().unwrap().is_empty());
+
+        let local = laptop_history.lock().unwrap().clone();
+        let (report, received) = laptop.client().sync_now(&local).unwrap();
+        assert_eq!(report.peers_reached, 1);
+
+        // The laptop gets the desktop's new item and its pin on the shared one
+        let mut contents: Vec<(String, bool)> = received.iter().map(|item| (item.content.clone(), item.pinned)).collect();
+        contents.sort();
+        assert_eq!(contents, vec![("from desktop".to_string(), false), ("shared".to_string(), true)]);
+
+        let pushed = wait_for_received(&desktop);
+        assert_eq!(pushed.len(), 1);
+        assert_eq!(pushed[0].content, "from laptop");
+
+        drop((laptop, desktop));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn wrong_pairing_code_is_rejected() {
+        let dir = std::env::temp_dir().join(format!("winshaper-sync-{}", random_hex(4)));
+        let (laptop, _) = instance(&dir, "laptop", Vec::new());
+        let (desktop, _) = instance(&dir, "desktop", Vec::new());
+
+        let pairing = desktop.begin_pairing().unwrap();
+        let result = laptop.client().pair_with(&format!("127.0.0.1:{}", pairing.port), "AAAA-AAAA");
+        assert!(result.is_err());
+        assert!(laptop.pending_pairings().unwrap().is_empty());
+        assert!(desktop.pending_pairings().unwrap().is_empty());
+
+        drop((laptop, desktop));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rejected_pairing_is_not_kept() {
+        let dir = std::env::temp_dir().join(format!("winshaper-sync-{}", random_hex(4)));
+        let (laptop, _) = instance(&dir, "laptop", Vec::new());
+        let (desktop, _) = instance(&dir, "desktop", Vec::new());
+
+        let pairing = desktop.begin_pairing().unwrap();
+        let pending = laptop.client().pair_with(&format!("127.0.0.1:{}", pairing.port), &pairing.code).unwrap();
+        laptop.reject_pairing(&pending.instance_id).unwrap();
+        assert!(laptop.confirm_pairing(&pending.instance_id).is_err());
+        assert!(laptop.peers().unwrap().is_empty());
+
+        drop((laptop, desktop));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn revealed_key_must_match_the_commitment() {
+        let dir = std::env::temp_dir().join(format!("winshaper-sync-{}", random_hex(4)));
+        let (desktop, _) = instance(&dir, "desktop", Vec::new());
+        let pairing = desktop.begin_pairing().unwrap();
+
+        // An initiator that waits for the responder's key before picking its own
+        let mut stream = connect(&format!("127.0.0.1:{}", pairing.port)).unwrap();
+        write_json(&mut stream, &Hello::Pair {
+            version: PROTOCOL_VERSION,
+            instance_id: "attacker".to_string(),
+            name: "attacker".to_string(),
+            port: 1,
+            key_commitment: key_commitment(&to_hex(&[1u8; 32])),
+        })
+        .unwrap();
+        let _: PairReply = read_json(&mut stream).unwrap();
+        write_json(&mut stream, &Reveal { public_key: to_hex(&[2u8; 32]) }).unwrap();
+        let _ = write_json(&mut stream, &Confirm { mac: String::new() });
+        assert!(read_json::<Confirm>(&mut stream).is_err());
+        assert!(desktop.pending_pairings().unwrap().is_empty());
+
+        drop(desktop);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn check_code_is_six_digits_independent_of_order() {
+        let (a, b) = (to_hex(&[1u8; 32]), to_hex(&[2u8; 32]));
+        let code = check_code(&a, &b);
+        assert_eq!(code, check_code(&b, &a));
+        assert_eq!(code.len(), 7);
+        assert!(code.chars().enumerate().all(|(i, c)| if i == 3 { c == ' ' } else { c.is_ascii_digit() }));
+        assert_ne!(code, check_code(&a, &to_hex(&[3u8; 32])));
+    }
+}
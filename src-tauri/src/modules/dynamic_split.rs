@@ -1,18 +1,161 @@
+use super::focus_modes::{self, FocusModeTracker, FocusModeWindow, FocusModes, FocusRule};
+use super::hotkey::{HotkeyListener, KeyChord};
+use super::layout_engine::{self, Divider, Layout, Rect};
+use super::monitors::{self, MonitorInfo};
+use super::snap_overlay::{DragSession, DragSnapOptions, ModifierKey, SnapZone};
+use super::tiling::{TilingAction, TilingActionHandler, TilingManager, DEFAULT_TILING_GAP};
+use super::window_backend::Win32Backend;
+use super::window_history::MoveHistory;
+use super::window_events::{WindowEvent, WindowEventHandler, WindowEventKind, WindowEventWatcher};
+use super::window_ops::{self, WindowInfo, WindowMatcher};
+use super::window_rules::{self, LayoutRule};
+use super::workspace_snapshots::{self, RestoreReport, SnapshotStore, WorkspaceSnapshot};
 use super::Module;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use windows::Win32::Foundation::HWND;
-use windows::Win32::UI::WindowsAndMessaging::{
-    GetForegroundWindow, GetSystemMetrics, SetWindowPos,
-    SM_CXSCREEN, SM_CYSCREEN, SWP_NOZORDER, SWP_SHOWWINDOW,
-};
+
+/// Where DynamicSplit last put a window, so moves between monitors keep the zone
+#[derive(Debug, Clone)]
+struct Placement {
+    layout: String,
+    zone: usize,
+}
+
+/// How long a window launched by a workspace restore is waited for
+const LAUNCH_WAIT: Duration = Duration::from_secs(60);
+
+/// Where to put the window of an app launched by a workspace restore once it appears
+#[derive(Debug, Clone)]
+struct PendingLaunch {
+    exe: String,
+    rect: Rect,
+    maximized: bool,
+    minimized: bool,
+    expires: Instant,
+}
+
+/// Place several windows into the zones of one layout at once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceRequest {
+    pub layout: String,
+    /// Monitor id; defaults to the monitor of the foreground window
+    pub monitor: Option<String>,
+    /// Window to put in each zone, by zone index. Zones without a matcher
+    /// (or beyond the list) take the most recently used remaining windows.
+    pub zones: Vec<Option<WindowMatcher>>,
+    /// Fill zones without a matcher with the most recently used windows
+    pub fill_from_recent: bool,
+}
+
+impl Default for WorkspaceRequest {
+    fn default() -> Self {
+        Self {
+            layout: String::new(),
+            monitor: None,
+            zones: Vec::new(),
+            fill_from_recent: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneAssignment {
+    pub zone: usize,
+    pub window: WindowInfo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceReport {
+    pub layout: String,
+    pub monitor: String,
+    pub filled: Vec<ZoneAssignment>,
+    pub empty: Vec<usize>,
+}
+
+/// Choose a window (index into `windows`) for each of `zone_count` zones.
+/// Matchers are resolved first so recently used fill-ins never take a window
+/// a matcher asked for; each window is used at most once.
+pub fn assign_windows(
+    zone_count: usize,
+    matchers: &[Option<WindowMatcher>],
+    windows: &[WindowInfo],
+    fill_from_recent: bool,
+) -> Vec<Option<usize>> {
+    let mut used = vec![false; windows.len()];
+    let mut assigned: Vec<Option<usize>> = vec![None; zone_count];
+
+    for (zone, slot) in assigned.iter_mut().enumerate() {
+        if let Some(Some(matcher)) = matchers.get(zone) {
+            *slot = (0..windows.len()).find(|&index| !used[index] && matcher.matches(&windows[index]));
+            if let Some(index) = *slot {
+                used[index] = true;
+            }
+        }
+    }
+
+    if fill_from_recent {
+        for (zone, slot) in assigned.iter_mut().enumerate() {
+            if matches!(matchers.get(zone), Some(Some(_))) {
+                continue;
+            }
+            // Minimized windows are not what the user is working with right now
+            *slot = (0..windows.len()).find(|&index| !used[index] && !windows[index].minimized);
+            if let Some(index) = *slot {
+                used[index] = true;
+            }
+        }
+    }
+
+    assigned
+}
 
 pub struct DynamicSplit {
     enabled: bool,
+    /// Every known layout definition, built-in and from settings
+    library: Vec<Layout>,
+    /// Names of the layouts `cycle_layout` steps through
     layouts: Vec<String>,
     current_layout_index: usize,
+    /// Layout assigned to a monitor, keyed by monitor id
+    monitor_layouts: HashMap<String, String>,
+    placements: HashMap<isize, Placement>,
+    /// Where windows were before DynamicSplit moved them
+    history: MoveHistory,
+    /// App-specific rules, highest priority first
+    rules: Vec<LayoutRule>,
+    /// Windows a rule has already placed, so later focus changes leave them be
+    rules_applied: HashSet<isize>,
+    event_handler: Option<WindowEventHandler>,
+    event_watcher: Option<WindowEventWatcher>,
+    /// Loaded on first use
+    snapshots: Option<SnapshotStore>,
+    pending_launches: Vec<PendingLaunch>,
+    /// Monitor set last seen, to restore a matching workspace after docking changes
+    monitor_key: Option<String>,
+    auto_restore: bool,
+    tiling_enabled: bool,
+    tiling_gap: i32,
+    /// Windows matching these always float in tiling mode
+    tiling_floating: Vec<WindowMatcher>,
+    tiling_hotkeys: Vec<(TilingAction, KeyChord)>,
+    /// Present while tiling mode is running
+    tiling: Option<TilingManager<Win32Backend>>,
+    tiling_listener: Option<HotkeyListener>,
+    tiling_handler: Option<TilingActionHandler>,
+    drag_snap_enabled: bool,
+    drag_snap: DragSnapOptions,
+    /// Present while a window is being dragged with the overlay up
+    drag_session: Option<DragSession>,
+    focus_modes: FocusModeTracker,
+    focus_rules: Vec<FocusRule>,
     settings: HashMap<String, Value>,
 }
 
@@ -20,42 +163,959 @@ impl DynamicSplit {
     pub fn new() -> Self {
         Self {
             enabled: false,
+            library: layout_engine::builtin_layouts(),
             layouts: vec!["left".to_string(), "right".to_string(), "left-60".to_string(), "right-60".to_string(), "center".to_string()],
             current_layout_index: 0,
+            monitor_layouts: HashMap::new(),
+            placements: HashMap::new(),
+            history: MoveHistory::new(),
+            rules: Vec::new(),
+            rules_applied: HashSet::new(),
+            event_handler: None,
+            event_watcher: None,
+            snapshots: None,
+            pending_launches: Vec::new(),
+            monitor_key: None,
+            auto_restore: true,
+            tiling_enabled: false,
+            tiling_gap: DEFAULT_TILING_GAP,
+            tiling_floating: vec![WindowMatcher {
+                class_name: Some("#32770".to_string()),
+                ..Default::default()
+            }],
+            tiling_hotkeys: TilingAction::all()
+                .into_iter()
+                .filter_map(|action| KeyChord::parse(&action.default_hotkey()).ok().map(|chord| (action, chord)))
+                .collect(),
+            tiling: None,
+            tiling_listener: None,
+            tiling_handler: None,
+            drag_snap_enabled: true,
+            drag_snap: DragSnapOptions {
+                activation: ModifierKey::None,
+                span: ModifierKey::Ctrl,
+            },
+            drag_session: None,
+            focus_modes: FocusModeTracker::new(),
+            focus_rules: Vec::new(),
             settings: HashMap::new(),
         }
     }
 
-    /// Apply a layout to the currently focused window. Basic MVP layouts are supported.
+    pub fn get_layouts(&self) -> Vec<Layout> {
+        self.library.clone()
+    }
+
+    pub fn find_layout(&self, name: &str) -> Result<&Layout> {
+        self.library
+            .iter()
+            .find(|layout| layout.name == name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown layout: {}", name))
+    }
+
+    /// Add a layout definition, replacing any existing one with the same name
+    fn define_layout(library: &mut Vec<Layout>, layout: Layout) -> Result<()> {
+        layout.validate()?;
+        match library.iter_mut().find(|existing| existing.name == layout.name) {
+            Some(existing) => *existing = layout,
+            None => library.push(layout),
+        }
+        Ok(())
+    }
+
+    /// Layouts the user defined, as opposed to the built-in ones
+    pub fn custom_layouts(&self) -> Vec<Layout> {
+        self.library
+            .iter()
+            .filter(|layout| !layout_engine::is_builtin(&layout.name))
+            .cloned()
+            .collect()
+    }
+
+    fn editable_layout(&mut self, name: &str) -> Result<&mut Layout> {
+        if layout_engine::is_builtin(name) {
+            return Err(anyhow::anyhow!("Built-in layout '{}' cannot be changed", name));
+        }
+        self.library
+            .iter_mut()
+            .find(|layout| layout.name == name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown layout: {}", name))
+    }
+
+    pub fn create_layout(&mut self, layout: Layout) -> Result<()> {
+        if self.library.iter().any(|existing| existing.name == layout.name) {
+            return Err(anyhow::anyhow!("Layout '{}' already exists", layout.name));
+        }
+        layout.validate()?;
+        self.library.push(layout);
+        self.store_layouts()
+    }
+
+    /// Replace the custom layout `name`. If the new definition has a different
+    /// name the layout is renamed, and cycle entries, monitor assignments and
+    /// rules follow it.
+    pub fn update_layout(&mut self, name: &str, layout: Layout) -> Result<()> {
+        layout.validate()?;
+        if layout.name != name && self.library.iter().any(|existing| existing.name == layout.name) {
+            return Err(anyhow::anyhow!("Layout '{}' already exists", layout.name));
+        }
+
+        // Zone indexes stored elsewhere must still exist
+        let zones = layout.zones().len();
+        if let Some(rule) = self.rules.iter().find(|rule| rule.layout == name && rule.zone >= zones) {
+            return Err(anyhow::anyhow!("Rule '{}' uses zone {} of layout '{}'", rule.name, rule.zone, name));
+        }
+
+        let new_name = layout.name.clone();
+        *self.editable_layout(name)? = layout;
+
+        if new_name != name {
+            let rename = |layout: &mut String| {
+                if layout == name {
+                    *layout = new_name.clone();
+                }
+            };
+            self.layouts.iter_mut().for_each(rename);
+            self.monitor_layouts.values_mut().for_each(rename);
+            self.rules.iter_mut().for_each(|rule| rename(&mut rule.layout));
+            self.placements.values_mut().for_each(|placement| rename(&mut placement.layout));
+            self.store_rules()?;
+            self.store_cycle();
+        }
+        self.placements.retain(|_, placement| placement.zone < zones || placement.layout != new_name);
+        self.store_layouts()
+    }
+
+    /// Delete a custom layout, dropping it from the cycle and monitor
+    /// assignments. Layouts still used by a rule cannot be deleted.
+    pub fn delete_layout(&mut self, name: &str) -> Result<()> {
+        self.editable_layout(name)?;
+        if let Some(rule) = self.rules.iter().find(|rule| rule.layout == name) {
+            return Err(anyhow::anyhow!("Layout '{}' is used by rule '{}'", name, rule.name));
+        }
+
+        self.library.retain(|layout| layout.name != name);
+        self.layouts.retain(|layout| layout != name);
+        if self.current_layout_index >= self.layouts.len() {
+            self.current_layout_index = 0;
+        }
+        self.monitor_layouts.retain(|_, layout| layout != name);
+        self.placements.retain(|_, placement| placement.layout != name);
+        self.store_cycle();
+        self.store_layouts()
+    }
+
+    pub fn get_layout_dividers(&self, name: &str) -> Result<Vec<Divider>> {
+        Ok(self.find_layout(name)?.dividers())
+    }
+
+    /// Move a divider of a custom layout, as the editor's ratio sliders do,
+    /// and return the updated layout
+    pub fn set_layout_divider(&mut self, name: &str, index: usize, position: f64) -> Result<Layout> {
+        let mut layout = self.editable_layout(name)?.clone();
+        layout.set_divider(index, position)?;
+        layout.validate()?;
+        *self.editable_layout(name)? = layout.clone();
+        self.store_layouts()?;
+        Ok(layout)
+    }
+
+    pub fn export_layouts(&self, path: &Path) -> Result<()> {
+        layout_engine::export_layouts(path, &self.custom_layouts())
+    }
+
+    /// Import custom layouts from a JSON file, either replacing all custom
+    /// layouts or merging by name. Returns the number of layouts imported.
+    pub fn import_layouts(&mut self, path: &Path, replace: bool) -> Result<usize> {
+        let incoming = layout_engine::import_layouts(path)?;
+        let count = incoming.len();
+        let library = self.merge_custom_layouts(incoming, replace)?;
+        self.check_layout_references(&library, &self.layouts, &self.monitor_layouts, &self.rules)?;
+
+        self.library = library;
+        self.placements.clear();
+        self.store_layouts()?;
+        Ok(count)
+    }
+
+    /// The library with `incoming` custom layouts added by name, or replacing
+    /// all custom layouts. Built-in layouts cannot be redefined.
+    fn merge_custom_layouts(&self, incoming: Vec<Layout>, replace: bool) -> Result<Vec<Layout>> {
+        if let Some(layout) = incoming.iter().find(|layout| layout_engine::is_builtin(&layout.name)) {
+            return Err(anyhow::anyhow!("Built-in layout '{}' cannot be redefined", layout.name));
+        }
+
+        let mut library = self.library.clone();
+        if replace {
+            library.retain(|layout| layout_engine::is_builtin(&layout.name));
+        }
+        for layout in incoming {
+            Self::define_layout(&mut library, layout)?;
+        }
+        Ok(library)
+    }
+
+    /// Check that a cycle, monitor assignments and rules all resolve against `library`
+    fn check_layout_references(
+        &self,
+        library: &[Layout],
+        cycle: &[String],
+        monitor_layouts: &HashMap<String, String>,
+        rules: &[LayoutRule],
+    ) -> Result<()> {
+        let resolves = |name: &String| library.iter().any(|layout| &layout.name == name);
+        if let Some(missing) = cycle
+            .iter()
+            .chain(monitor_layouts.values())
+            .chain(rules.iter().map(|rule| &rule.layout))
+            .find(|name| !resolves(name))
+        {
+            return Err(anyhow::anyhow!("Layout '{}' is in use but not defined", missing));
+        }
+        for rule in rules {
+            let zones = library
+                .iter()
+                .find(|layout| layout.name == rule.layout)
+                .map_or(0, |layout| layout.zones().len());
+            if rule.zone >= zones {
+                return Err(anyhow::anyhow!("Rule '{}' uses zone {} of layout '{}'", rule.name, rule.zone, rule.layout));
+            }
+        }
+        Ok(())
+    }
+
+    fn store_layouts(&mut self) -> Result<()> {
+        self.settings.insert("custom_layouts".to_string(), serde_json::to_value(self.custom_layouts())?);
+        Ok(())
+    }
+
+    fn store_cycle(&mut self) {
+        self.settings.insert("layouts".to_string(), serde_json::json!(self.layouts));
+        self.settings.insert("monitor_layouts".to_string(), serde_json::json!(self.monitor_layouts));
+    }
+
+    /// Apply a layout to the currently focused window, placing it in the first zone
     pub fn apply_layout(&mut self, layout: &str) -> Result<()> {
-        unsafe {
-            let hwnd = GetForegroundWindow();
-            if hwnd.0.is_null() {
-                return Err(anyhow::anyhow!("No foreground window found"));
-            }
-
-            let screen_w = GetSystemMetrics(SM_CXSCREEN);
-            let screen_h = GetSystemMetrics(SM_CYSCREEN);
-
-            let (x, y, w, h) = match layout {
-                "left" | "50-50" => (0, 0, screen_w / 2, screen_h),
-                "right" => (screen_w / 2, 0, screen_w / 2, screen_h),
-                "left-60" | "60-40" => (0, 0, (screen_w * 60) / 100, screen_h),
-                "right-60" => ((screen_w * 40) / 100, 0, (screen_w * 60) / 100, screen_h),
-                "center" => ((screen_w / 8), (screen_h / 8), (screen_w * 3) / 4, (screen_h * 3) / 4),
-                _ => return Err(anyhow::anyhow!("Unknown layout")),
+        self.apply_layout_zone(layout, 0)
+    }
+
+    /// Move the currently focused window into zone `zone` of a layout, on the
+    /// monitor that window is on
+    pub fn apply_layout_zone(&mut self, layout: &str, zone: usize) -> Result<()> {
+        let hwnd = window_ops::foreground_window()?;
+        let monitor = monitors::monitor_for_window(hwnd)?;
+        self.place_in_zone(hwnd, &monitor, layout, zone)
+    }
+
+    /// Move the focused window into a zone of its monitor's assigned layout,
+    /// falling back to the current cycle layout
+    pub fn snap_to_zone(&mut self, zone: usize) -> Result<()> {
+        let hwnd = window_ops::foreground_window()?;
+        let monitor = monitors::monitor_for_window(hwnd)?;
+        let layout = self.layout_for_monitor(&monitor)?;
+        self.place_in_zone(hwnd, &monitor, &layout, zone)
+    }
+
+    fn place_in_zone(&mut self, hwnd: HWND, monitor: &MonitorInfo, layout: &str, zone: usize) -> Result<()> {
+        let rects = layout_engine::compute_zones(self.find_layout(layout)?, monitor.work_area);
+        let rect = rects
+            .get(zone)
+            .ok_or_else(|| anyhow::anyhow!("Layout '{}' has no zone {}", layout, zone))?;
+
+        self.remember_geometry(hwnd);
+        window_ops::place_window(hwnd, *rect)?;
+        self.placements.retain(|raw, _| window_ops::is_window(window_ops::raw_to_hwnd(*raw)));
+        self.placements.insert(
+            window_ops::hwnd_to_raw(hwnd),
+            Placement { layout: layout.to_string(), zone },
+        );
+        Ok(())
+    }
+
+    /// Record a window's geometry before DynamicSplit moves it, so the move can be undone
+    fn remember_geometry(&mut self, hwnd: HWND) {
+        self.history.retain_windows(|raw| window_ops::is_window(window_ops::raw_to_hwnd(raw)));
+        match window_ops::window_geometry(hwnd) {
+            Ok(geometry) => self.history.record(window_ops::hwnd_to_raw(hwnd), geometry),
+            Err(e) => log::warn!("Could not record window geometry: {}", e),
+        }
+    }
+
+    /// Undo the last move DynamicSplit made to the focused window
+    pub fn undo_window_move(&mut self) -> Result<()> {
+        let hwnd = window_ops::foreground_window()?;
+        let raw = window_ops::hwnd_to_raw(hwnd);
+        let geometry = self
+            .history
+            .undo(raw)
+            .ok_or_else(|| anyhow::anyhow!("No window move to undo"))?;
+        self.placements.remove(&raw);
+        window_ops::restore_geometry(hwnd, &geometry)
+    }
+
+    /// Put the focused window back to the size and position it had before
+    /// DynamicSplit first moved it
+    pub fn restore_original_size(&mut self) -> Result<()> {
+        let hwnd = window_ops::foreground_window()?;
+        let raw = window_ops::hwnd_to_raw(hwnd);
+        let geometry = self
+            .history
+            .take_original(raw)
+            .ok_or_else(|| anyhow::anyhow!("Window was not moved by DynamicSplit"))?;
+        self.placements.remove(&raw);
+        window_ops::restore_geometry(hwnd, &geometry)
+    }
+
+    /// Put every window DynamicSplit moved back to its original geometry
+    fn restore_all_windows(&mut self) {
+        let originals = self.history.take_all_originals();
+        let mut restored = 0;
+        for (raw, geometry) in originals {
+            let hwnd = window_ops::raw_to_hwnd(raw);
+            if !window_ops::is_window(hwnd) {
+                continue;
+            }
+            match window_ops::restore_geometry(hwnd, &geometry) {
+                Ok(()) => restored += 1,
+                Err(e) => log::warn!("Could not restore window geometry: {}", e),
+            }
+        }
+        self.placements.clear();
+        log::info!("Restored {} windows to their original geometry", restored);
+    }
+
+    /// Layout assigned to `monitor`, or the layout currently selected by cycling
+    fn layout_for_monitor(&self, monitor: &MonitorInfo) -> Result<String> {
+        if let Some(layout) = self.monitor_layouts.get(&monitor.id) {
+            return Ok(layout.clone());
+        }
+        self.layouts
+            .get(self.current_layout_index)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No layouts configured"))
+    }
+
+    pub fn get_monitors(&self) -> Result<Vec<MonitorInfo>> {
+        monitors::enumerate_monitors()
+    }
+
+    pub fn get_monitor_layouts(&self) -> HashMap<String, String> {
+        self.monitor_layouts.clone()
+    }
+
+    /// Assign a layout to a monitor, or clear the assignment with `None`
+    pub fn set_monitor_layout(&mut self, monitor_id: &str, layout: Option<String>) -> Result<()> {
+        match layout {
+            Some(layout) => {
+                self.find_layout(&layout)?;
+                self.monitor_layouts.insert(monitor_id.to_string(), layout);
+            }
+            None => {
+                self.monitor_layouts.remove(monitor_id);
+            }
+        }
+
+        self.settings.insert(
+            "monitor_layouts".to_string(),
+            serde_json::to_value(&self.monitor_layouts)?,
+        );
+        Ok(())
+    }
+
+    /// Move the focused window to the next monitor. A window DynamicSplit placed
+    /// keeps its zone index, using the target monitor's layout if it has one;
+    /// any other window keeps its relative position and size.
+    pub fn move_to_next_monitor(&mut self) -> Result<()> {
+        let hwnd = window_ops::foreground_window()?;
+        let monitors = monitors::enumerate_monitors()?;
+        if monitors.len() < 2 {
+            return Err(anyhow::anyhow!("Only one monitor is connected"));
+        }
+
+        let current = monitors::monitor_for_window(hwnd)?;
+        let index = monitors.iter().position(|m| m.id == current.id).unwrap_or(0);
+        let target = &monitors[(index + 1) % monitors.len()];
+        log::info!("Moving window from {} to {}", current.id, target.id);
+
+        let placement = self.placements.get(&window_ops::hwnd_to_raw(hwnd)).cloned();
+        match placement {
+            Some(placement) => {
+                let layout = self
+                    .monitor_layouts
+                    .get(&target.id)
+                    .cloned()
+                    .unwrap_or(placement.layout);
+                let zones = self.find_layout(&layout)?.zones().len();
+                self.place_in_zone(hwnd, target, &layout, placement.zone.min(zones - 1))
+            }
+            None => {
+                let rect = window_ops::visible_rect(hwnd)?;
+                let moved = monitors::translate_rect(rect, current.work_area, target.work_area);
+                self.remember_geometry(hwnd);
+                window_ops::place_window(hwnd, moved)
+            }
+        }
+    }
+
+    /// Place several windows into the zones of a layout and report which
+    /// zones were filled and which were left empty
+    pub fn apply_workspace(&mut self, request: &WorkspaceRequest) -> Result<WorkspaceReport> {
+        let monitor = match &request.monitor {
+            Some(id) => monitors::enumerate_monitors()?
+                .into_iter()
+                .find(|monitor| &monitor.id == id)
+                .ok_or_else(|| anyhow::anyhow!("Unknown monitor: {}", id))?,
+            None => monitors::monitor_for_window(window_ops::foreground_window()?)?,
+        };
+
+        let zone_count = self.find_layout(&request.layout)?.zones().len();
+        let windows = window_ops::enumerate_windows()?;
+        let assigned = assign_windows(zone_count, &request.zones, &windows, request.fill_from_recent);
+
+        let mut report = WorkspaceReport {
+            layout: request.layout.clone(),
+            monitor: monitor.id.clone(),
+            filled: Vec::new(),
+            empty: Vec::new(),
+        };
+
+        for (zone, window) in assigned.into_iter().enumerate() {
+            let Some(window) = window.map(|index| windows[index].clone()) else {
+                report.empty.push(zone);
+                continue;
             };
 
-            let flags = SWP_NOZORDER | SWP_SHOWWINDOW;
-            let ok = SetWindowPos(hwnd, HWND(std::ptr::null_mut()), x, y, w, h, flags).is_ok();
-            if ok {
-                return Err(anyhow::anyhow!("SetWindowPos failed"));
+            let hwnd = window_ops::raw_to_hwnd(window.hwnd);
+            match self.place_in_zone(hwnd, &monitor, &request.layout, zone) {
+                Ok(()) => report.filled.push(ZoneAssignment { zone, window }),
+                Err(e) => {
+                    log::warn!("Could not place '{}' in zone {}: {}", window.title, zone, e);
+                    report.empty.push(zone);
+                }
             }
+        }
+
+        log::info!(
+            "Applied workspace '{}' on {}: {} filled, {} empty",
+            report.layout,
+            report.monitor,
+            report.filled.len(),
+            report.empty.len()
+        );
+        Ok(report)
+    }
+
+    pub fn get_rules(&self) -> Vec<LayoutRule> {
+        self.rules.clone()
+    }
+
+    fn check_rule(&self, rule: &LayoutRule) -> Result<()> {
+        rule.validate()?;
+        let zones = self.find_layout(&rule.layout)?.zones().len();
+        if rule.zone >= zones {
+            return Err(anyhow::anyhow!("Rule '{}': layout '{}' has no zone {}", rule.name, rule.layout, rule.zone));
+        }
+        Ok(())
+    }
+
+    /// Replace the whole rule table
+    pub fn set_rules(&mut self, mut rules: Vec<LayoutRule>) -> Result<()> {
+        for rule in &rules {
+            self.check_rule(rule)?;
+        }
+        window_rules::sort_rules(&mut rules);
+        self.rules = rules;
+        self.store_rules()
+    }
+
+    pub fn export_rules(&self, path: &Path) -> Result<()> {
+        window_rules::export_rules(path, &self.rules)
+    }
+
+    /// Import rules from a JSON file, either replacing the table or merging by
+    /// rule name. Returns the number of rules imported.
+    pub fn import_rules(&mut self, path: &Path, replace: bool) -> Result<usize> {
+        let incoming = window_rules::import_rules(path)?;
+        for rule in &incoming {
+            self.check_rule(rule)?;
+        }
+
+        let count = if replace {
+            let count = incoming.len();
+            self.rules = incoming;
+            window_rules::sort_rules(&mut self.rules);
+            count
+        } else {
+            window_rules::merge_rules(&mut self.rules, incoming)
+        };
+
+        self.store_rules()?;
+        Ok(count)
+    }
+
+    fn store_rules(&mut self) -> Result<()> {
+        self.settings.insert("rules".to_string(), serde_json::to_value(&self.rules)?);
+        Ok(())
+    }
+
+    /// Install the callback that routes window events back into this module
+    pub fn set_window_event_handler(&mut self, handler: WindowEventHandler) {
+        self.event_handler = Some(handler);
+    }
+
+    fn start_event_watcher(&mut self) -> Result<()> {
+        if self.event_watcher.is_some() {
+            return Ok(());
+        }
+        let Some(handler) = self.event_handler.clone() else {
+            return Ok(());
+        };
+
+        self.event_watcher = Some(WindowEventWatcher::start(move |event| handler(event))?);
+        Ok(())
+    }
+
+    fn stop_event_watcher(&mut self) {
+        if let Some(watcher) = self.event_watcher.take() {
+            watcher.stop();
+        }
+    }
+
+    /// React to a window being created or focused by applying its matching rule.
+    /// Each window is placed once, unless its rule asks to re-apply on focus.
+    /// Display changes may restore the workspace saved for the new monitors.
+    pub fn handle_window_event(&mut self, event: WindowEvent) {
+        if !self.enabled {
+            return;
+        }
+        if event.kind == WindowEventKind::DisplayChanged {
+            self.check_monitor_change();
+            return;
+        }
+
+        if event.kind == WindowEventKind::Created {
+            self.apply_focus_rule(event.hwnd);
+        }
+
+        // In tiling mode the tree owns window placement
+        if let Some(tiling) = self.tiling.as_mut() {
+            let result = match event.kind {
+                WindowEventKind::Created => {
+                    if let Ok(geometry) = window_ops::window_geometry(window_ops::raw_to_hwnd(event.hwnd)) {
+                        self.history.record(event.hwnd, geometry);
+                    }
+                    tiling.window_appeared(event.hwnd)
+                }
+                WindowEventKind::Restored => tiling.window_appeared(event.hwnd),
+                WindowEventKind::Hidden | WindowEventKind::Destroyed | WindowEventKind::Minimized => {
+                    tiling.window_disappeared(event.hwnd)
+                }
+                WindowEventKind::Focused
+                | WindowEventKind::MoveSizeStart
+                | WindowEventKind::MoveSizeEnd
+                | WindowEventKind::DisplayChanged => Ok(()),
+            };
+            if let Err(e) = result {
+                log::warn!("Tiling update failed: {}", e);
+            }
+            return;
+        }
+
+        match event.kind {
+            WindowEventKind::MoveSizeStart => return self.begin_drag(event.hwnd),
+            WindowEventKind::MoveSizeEnd => return self.end_drag(event.hwnd),
+            WindowEventKind::Created | WindowEventKind::Focused => {}
+            _ => return,
+        }
+
+        let hwnd = window_ops::raw_to_hwnd(event.hwnd);
+        if !window_ops::is_app_window(hwnd) {
+            return;
+        }
+        let Ok(window) = window_ops::window_info(hwnd) else {
+            return;
+        };
+
+        if event.kind == WindowEventKind::Created && self.place_pending_launch(hwnd, &window) {
+            return;
+        }
 
+        let Some(rule) = window_rules::find_rule(&self.rules, &window).cloned() else {
+            return;
+        };
+
+        let already_applied = self.rules_applied.contains(&event.hwnd);
+        if already_applied && !(event.kind == WindowEventKind::Focused && rule.reapply_on_focus) {
+            return;
+        }
+
+        if let Err(e) = self.apply_rule(hwnd, &rule) {
+            log::warn!("Could not apply layout rule '{}' to '{}': {}", rule.name, window.title, e);
+            return;
+        }
+        log::info!("Applied layout rule '{}' to '{}'", rule.name, window.title);
+
+        self.rules_applied.retain(|raw| window_ops::is_window(window_ops::raw_to_hwnd(*raw)));
+        self.rules_applied.insert(event.hwnd);
+    }
+
+    /// Install the callback that runs tiling hotkey actions
+    pub fn set_tiling_action_handler(&mut self, handler: TilingActionHandler) {
+        self.tiling_handler = Some(handler);
+    }
+
+    pub fn is_tiling(&self) -> bool {
+        self.tiling.is_some()
+    }
+
+    /// Turn tiling mode on or off. It only runs while the module is enabled.
+    pub fn set_tiling_enabled(&mut self, enabled: bool) -> Result<()> {
+        self.tiling_enabled = enabled;
+        self.settings.insert("tiling_enabled".to_string(), serde_json::json!(enabled));
+
+        if enabled && self.enabled {
+            self.start_tiling()
+        } else {
+            self.stop_tiling();
             Ok(())
         }
     }
 
+    fn start_tiling(&mut self) -> Result<()> {
+        if self.tiling.is_some() {
+            return Ok(());
+        }
+
+        for window in window_ops::enumerate_windows()? {
+            self.remember_geometry(window_ops::raw_to_hwnd(window.hwnd));
+        }
+        let mut tiling = TilingManager::new(Win32Backend);
+        tiling.set_gap(self.tiling_gap);
+        tiling.set_floating_rules(self.tiling_floating.clone());
+        tiling.tile_all()?;
+        self.tiling = Some(tiling);
+
+        if let Some(handler) = self.tiling_handler.clone() {
+            let actions: Vec<TilingAction> = self.tiling_hotkeys.iter().map(|(action, _)| *action).collect();
+            let chords: Vec<KeyChord> = self.tiling_hotkeys.iter().map(|(_, chord)| *chord).collect();
+            // Tiling commands stay available from the UI if a hotkey is taken
+            match HotkeyListener::start(chords, move |index| handler(actions[index])) {
+                Ok(listener) => self.tiling_listener = Some(listener),
+                Err(e) => log::warn!("Tiling hotkeys unavailable: {}", e),
+            }
+        }
+
+        log::info!("Tiling mode started");
+        Ok(())
+    }
+
+    fn stop_tiling(&mut self) {
+        if let Some(listener) = self.tiling_listener.take() {
+            listener.stop();
+        }
+        if self.tiling.take().is_some() {
+            log::info!("Tiling mode stopped");
+        }
+    }
+
+    pub fn run_tiling_action(&mut self, action: TilingAction) -> Result<()> {
+        self.tiling
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Tiling mode is off"))?
+            .run(action)
+    }
+
+    fn snapshot_store(&mut self) -> Result<&mut SnapshotStore> {
+        if self.snapshots.is_none() {
+            self.snapshots = Some(SnapshotStore::load(SnapshotStore::default_path()?)?);
+        }
+        self.snapshots
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Workspace store unavailable"))
+    }
+
+    /// Snapshot every top-level window under `name`, replacing an older snapshot
+    pub fn save_workspace(&mut self, name: &str) -> Result<WorkspaceSnapshot> {
+        if name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Workspace name must not be empty"));
+        }
+
+        let monitors = monitors::enumerate_monitors()?;
+        let windows = window_ops::enumerate_windows()?;
+        let snapshot = WorkspaceSnapshot::capture(name, monitors, &windows);
+        self.snapshot_store()?.insert(snapshot.clone())?;
+
+        log::info!("Saved workspace '{}' with {} windows", name, snapshot.windows.len());
+        Ok(snapshot)
+    }
+
+    pub fn get_workspaces(&mut self) -> Result<Vec<WorkspaceSnapshot>> {
+        Ok(self.snapshot_store()?.list().to_vec())
+    }
+
+    pub fn delete_workspace(&mut self, name: &str) -> Result<()> {
+        self.snapshot_store()?.remove(name)
+    }
+
+    /// Move open windows back to where a snapshot recorded them, optionally
+    /// launching apps that are not running. Launched windows are placed when
+    /// they appear, which needs the module to be enabled.
+    pub fn restore_workspace(&mut self, name: &str, launch_missing: bool) -> Result<RestoreReport> {
+        let snapshot = self
+            .snapshot_store()?
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Workspace '{}' not found", name))?;
+        self.restore_snapshot(&snapshot, launch_missing)
+    }
+
+    fn restore_snapshot(&mut self, snapshot: &WorkspaceSnapshot, launch_missing: bool) -> Result<RestoreReport> {
+        let current_monitors = monitors::enumerate_monitors()?;
+        let open = window_ops::enumerate_windows()?;
+        let matched = workspace_snapshots::match_windows(&snapshot.windows, &open);
+
+        let mut report = RestoreReport::default();
+        self.pending_launches.retain(|pending| pending.expires > Instant::now());
+
+        for (saved, index) in snapshot.windows.iter().zip(matched) {
+            let rect = snapshot.target_rect(saved, &current_monitors);
+
+            if let Some(index) = index {
+                let window = &open[index];
+                let hwnd = window_ops::raw_to_hwnd(window.hwnd);
+                self.remember_geometry(hwnd);
+                match window_ops::place_window(hwnd, rect) {
+                    Ok(()) => {
+                        window_ops::set_window_state(hwnd, saved.maximized, saved.minimized);
+                        self.placements.remove(&window.hwnd);
+                        report.restored.push(window.title.clone());
+                    }
+                    Err(e) => {
+                        log::warn!("Could not restore '{}': {}", window.title, e);
+                        report.missing.push(saved.title.clone());
+                    }
+                }
+                continue;
+            }
+
+            if !launch_missing || saved.exe_path.is_empty() {
+                report.missing.push(saved.title.clone());
+                continue;
+            }
+
+            // Several windows of one app need only one launch; each waits for a window
+            if !report.launched.contains(&saved.exe_path) {
+                if let Err(e) = Command::new(&saved.exe_path).spawn() {
+                    log::warn!("Could not launch {}: {}", saved.exe_path, e);
+                    report.missing.push(saved.title.clone());
+                    continue;
+                }
+                report.launched.push(saved.exe_path.clone());
+            }
+            self.pending_launches.push(PendingLaunch {
+                exe: saved.exe.clone(),
+                rect,
+                maximized: saved.maximized,
+                minimized: saved.minimized,
+                expires: Instant::now() + LAUNCH_WAIT,
+            });
+        }
+
+        log::info!(
+            "Restored workspace '{}': {} placed, {} launched, {} missing",
+            snapshot.name,
+            report.restored.len(),
+            report.launched.len(),
+            report.missing.len()
+        );
+        Ok(report)
+    }
+
+    /// Place a newly created window if a workspace restore launched its app
+    fn place_pending_launch(&mut self, hwnd: HWND, window: &WindowInfo) -> bool {
+        let now = Instant::now();
+        self.pending_launches.retain(|pending| pending.expires > now);
+
+        let Some(index) = self
+            .pending_launches
+            .iter()
+            .position(|pending| pending.exe.eq_ignore_ascii_case(&window.exe))
+        else {
+            return false;
+        };
+
+        let pending = self.pending_launches.remove(index);
+        self.remember_geometry(hwnd);
+        match window_ops::place_window(hwnd, pending.rect) {
+            Ok(()) => window_ops::set_window_state(hwnd, pending.maximized, pending.minimized),
+            Err(e) => log::warn!("Could not place launched window '{}': {}", window.title, e),
+        }
+        true
+    }
+
+    /// After docking or undocking, restore the latest workspace saved on the
+    /// new monitor set, if there is one
+    fn check_monitor_change(&mut self) {
+        let Ok(monitors) = monitors::enumerate_monitors() else {
+            return;
+        };
+        let key = workspace_snapshots::monitor_set_key(&monitors);
+        let previous = self.monitor_key.replace(key.clone());
+        if !self.auto_restore || previous.is_none() || previous.as_deref() == Some(key.as_str()) {
+            return;
+        }
+
+        log::info!("Monitor set changed: {}", key);
+        let snapshot = match self.snapshot_store() {
+            Ok(store) => store.latest_for_monitors(&key).cloned(),
+            Err(e) => {
+                log::warn!("Could not load workspaces: {}", e);
+                None
+            }
+        };
+
+        if let Some(snapshot) = snapshot {
+            if let Err(e) = self.restore_snapshot(&snapshot, false) {
+                log::warn!("Could not restore workspace '{}': {}", snapshot.name, e);
+            }
+        }
+    }
+
+    /// Zones of every monitor's layout, which a dragged window can be dropped on
+    fn snap_zones(&self) -> Result<Vec<SnapZone>> {
+        let mut zones = Vec::new();
+        for (index, monitor) in monitors::enumerate_monitors()?.iter().enumerate() {
+            let layout = self.layout_for_monitor(monitor)?;
+            let rects = layout_engine::compute_zones(self.find_layout(&layout)?, monitor.work_area);
+            zones.extend(rects.into_iter().enumerate().map(|(zone, rect)| SnapZone {
+                monitor: index,
+                layout: layout.clone(),
+                zone,
+                rect,
+            }));
+        }
+        Ok(zones)
+    }
+
+    /// Show the zone overlay for a window the user started dragging
+    fn begin_drag(&mut self, window: isize) {
+        if !self.drag_snap_enabled || !window_ops::is_app_window(window_ops::raw_to_hwnd(window)) {
+            return;
+        }
+        // A drag end that never arrived leaves a stale session behind
+        if let Some(session) = self.drag_session.take() {
+            session.finish();
+        }
+
+        let session = self
+            .snap_zones()
+            .and_then(|zones| DragSession::start(window, zones, self.drag_snap));
+        match session {
+            Ok(session) => self.drag_session = Some(session),
+            Err(e) => log::warn!("Could not start drag-to-snap: {}", e),
+        }
+    }
+
+    /// Snap a dropped window to the zones highlighted when it was released
+    fn end_drag(&mut self, window: isize) {
+        if self.drag_session.as_ref().map(|session| session.window) != Some(window) {
+            return;
+        }
+        let Some(selection) = self.drag_session.take().and_then(DragSession::finish) else {
+            return;
+        };
+
+        let hwnd = window_ops::raw_to_hwnd(window);
+        self.remember_geometry(hwnd);
+        if let Err(e) = window_ops::place_window(hwnd, selection.rect) {
+            log::warn!("Could not snap dropped window: {}", e);
+            return;
+        }
+
+        // Only a single zone is a placement that can follow the window between monitors
+        self.placements.retain(|raw, _| window_ops::is_window(window_ops::raw_to_hwnd(*raw)));
+        match selection.zones.as_slice() {
+            [zone] => {
+                self.placements.insert(window, Placement { layout: zone.layout.clone(), zone: zone.zone });
+            }
+            _ => {
+                self.placements.remove(&window);
+            }
+        }
+    }
+
+    /// Update the focused window's focus modes with `change`
+    fn change_focus_modes(&mut self, change: impl FnOnce(&mut FocusModes)) -> Result<FocusModes> {
+        let window = window_ops::hwnd_to_raw(window_ops::foreground_window()?);
+        let mut modes = self.focus_modes.modes(window);
+        change(&mut modes);
+        self.focus_modes.apply(window, modes)?;
+        Ok(modes)
+    }
+
+    /// Toggle always-on-top for the focused window, returning the new state
+    pub fn toggle_always_on_top(&mut self) -> Result<bool> {
+        Ok(self.change_focus_modes(|modes| modes.always_on_top = !modes.always_on_top)?.always_on_top)
+    }
+
+    /// Set the focused window's opacity in percent, or make it opaque with `None`
+    pub fn set_window_opacity(&mut self, opacity: Option<u8>) -> Result<()> {
+        self.change_focus_modes(|modes| modes.opacity = opacity.filter(|&opacity| opacity < 100))?;
+        Ok(())
+    }
+
+    /// Toggle showing the focused window on all virtual desktops, returning the new state
+    pub fn toggle_pinned(&mut self) -> Result<bool> {
+        Ok(self.change_focus_modes(|modes| modes.pinned = !modes.pinned)?.pinned)
+    }
+
+    pub fn get_focus_mode_windows(&self) -> Vec<FocusModeWindow> {
+        self.focus_modes.modified_windows()
+    }
+
+    pub fn get_focus_rules(&self) -> Vec<FocusRule> {
+        self.focus_rules.clone()
+    }
+
+    pub fn set_focus_rules(&mut self, rules: Vec<FocusRule>) -> Result<()> {
+        for rule in &rules {
+            rule.validate()?;
+        }
+        self.focus_rules = rules;
+        self.settings.insert("focus_rules".to_string(), serde_json::to_value(&self.focus_rules)?);
+        Ok(())
+    }
+
+    /// Apply the focus modes of the first matching focus rule to a window
+    fn apply_focus_rule(&mut self, window: isize) {
+        let hwnd = window_ops::raw_to_hwnd(window);
+        if self.focus_rules.is_empty() || !window_ops::is_app_window(hwnd) {
+            return;
+        }
+        let Ok(info) = window_ops::window_info(hwnd) else {
+            return;
+        };
+        let Some(rule) = focus_modes::find_focus_rule(&self.focus_rules, &info) else {
+            return;
+        };
+
+        match self.focus_modes.apply(window, rule.modes) {
+            Ok(()) => log::info!("Applied focus rule '{}' to '{}'", rule.name, info.title),
+            Err(e) => log::warn!("Could not apply focus rule '{}' to '{}': {}", rule.name, info.title, e),
+        }
+    }
+
+    fn apply_rule(&mut self, hwnd: HWND, rule: &LayoutRule) -> Result<()> {
+        let monitor = match &rule.monitor {
+            Some(id) => monitors::enumerate_monitors()?
+                .into_iter()
+                .find(|monitor| &monitor.id == id)
+                .ok_or_else(|| anyhow::anyhow!("Unknown monitor: {}", id))?,
+            None => monitors::monitor_for_window(hwnd)?,
+        };
+        self.place_in_zone(hwnd, &monitor, &rule.layout, rule.zone)
+    }
+
     /// Cycle to the next layout and apply it to the foreground window
     pub fn cycle_layout(&mut self) -> Result<()> {
         if self.layouts.is_empty() {
@@ -67,6 +1127,33 @@ impl DynamicSplit {
         log::info!("Cycling to layout: {}", layout);
         self.apply_layout(&layout)
     }
+
+    /// Parse the `layouts` setting into a cycle order. Entries are either the
+    /// name of a known layout or a full layout definition, which is added to
+    /// `library`; both join the cycle.
+    fn parse_cycle(library: &mut Vec<Layout>, entries: &[Value]) -> Result<Vec<String>> {
+        let mut cycle = Vec::with_capacity(entries.len());
+
+        for entry in entries {
+            let name = match entry {
+                Value::String(name) => name.clone(),
+                Value::Object(_) => {
+                    let layout: Layout = serde_json::from_value(entry.clone())
+                        .map_err(|e| anyhow::anyhow!("Invalid layout definition: {}", e))?;
+                    let name = layout.name.clone();
+                    Self::define_layout(library, layout)?;
+                    name
+                }
+                _ => return Err(anyhow::anyhow!("Layout entries must be names or objects")),
+            };
+
+            if !library.iter().any(|layout| layout.name == name) {
+                return Err(anyhow::anyhow!("Unknown layout: {}", name));
+            }
+            cycle.push(name);
+        }
+        Ok(cycle)
+    }
 }
 
 impl Module for DynamicSplit {
@@ -84,12 +1171,29 @@ impl Module for DynamicSplit {
 
     fn enable(&mut self) -> anyhow::Result<()> {
         log::info!("Enabling Dynamic Split module");
+        self.start_event_watcher()?;
+        self.monitor_key = monitors::enumerate_monitors()
+            .ok()
+            .map(|monitors| workspace_snapshots::monitor_set_key(&monitors));
+        if self.tiling_enabled {
+            self.start_tiling()?;
+        }
+        for window in window_ops::enumerate_windows().unwrap_or_default() {
+            self.apply_focus_rule(window.hwnd);
+        }
         self.enabled = true;
         Ok(())
     }
 
     fn disable(&mut self) -> anyhow::Result<()> {
         log::info!("Disabling Dynamic Split module");
+        self.stop_tiling();
+        self.stop_event_watcher();
+        if let Some(session) = self.drag_session.take() {
+            session.finish();
+        }
+        self.restore_all_windows();
+        self.focus_modes.revert_all();
         self.enabled = false;
         Ok(())
     }
@@ -99,7 +1203,275 @@ impl Module for DynamicSplit {
     }
 
     fn update_settings(&mut self, settings: HashMap<String, serde_json::Value>) -> anyhow::Result<()> {
-        self.settings = settings.into_iter().collect();
+        // Parse and check every key before touching the module, so a bad
+        // value leaves all settings as they were
+        let mut library = match settings.get("custom_layouts") {
+            Some(custom) => {
+                let custom: Vec<Layout> = serde_json::from_value(custom.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid custom_layouts: {}", e))?;
+                self.merge_custom_layouts(custom, true)?
+            }
+            None => self.library.clone(),
+        };
+        let cycle = match settings.get("layouts").and_then(Value::as_array) {
+            Some(entries) => Some(Self::parse_cycle(&mut library, entries)?),
+            None => None,
+        };
+        let monitor_layouts: Option<HashMap<String, String>> = match settings.get("monitor_layouts") {
+            Some(assignments) => Some(
+                serde_json::from_value(assignments.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid monitor_layouts: {}", e))?,
+            ),
+            None => None,
+        };
+        let rules = match settings.get("rules") {
+            Some(rules) => {
+                let mut rules: Vec<LayoutRule> = serde_json::from_value(rules.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid rules: {}", e))?;
+                for rule in &rules {
+                    rule.validate()?;
+                }
+                window_rules::sort_rules(&mut rules);
+                Some(rules)
+            }
+            None => None,
+        };
+        self.check_layout_references(
+            &library,
+            cycle.as_deref().unwrap_or(&self.layouts),
+            monitor_layouts.as_ref().unwrap_or(&self.monitor_layouts),
+            rules.as_deref().unwrap_or(&self.rules),
+        )?;
+
+        let auto_restore = settings.get("auto_restore_workspaces").and_then(Value::as_bool);
+        let tiling_gap = settings.get("tiling_gap").and_then(Value::as_u64).map(|gap| gap as i32);
+        let tiling_floating: Option<Vec<WindowMatcher>> = match settings.get("tiling_floating") {
+            Some(floating) => Some(
+                serde_json::from_value(floating.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid tiling_floating: {}", e))?,
+            ),
+            None => None,
+        };
+        let tiling_hotkeys = match settings.get("tiling_hotkeys").and_then(Value::as_object) {
+            Some(hotkeys) => {
+                let mut bindings = Vec::with_capacity(hotkeys.len());
+                for (key, chord) in hotkeys {
+                    let action = TilingAction::from_key(key)?;
+                    // An empty or null chord leaves the action without a hotkey
+                    match chord.as_str().map(str::trim) {
+                        Some("") | None => {}
+                        Some(chord) => bindings.push((action, KeyChord::parse(chord)?)),
+                    }
+                }
+                Some(bindings)
+            }
+            None => None,
+        };
+
+        let mut drag_snap = self.drag_snap;
+        if let Some(modifier) = settings.get("drag_snap_modifier").and_then(Value::as_str) {
+            drag_snap.activation = ModifierKey::parse(modifier)?;
+        }
+        if let Some(modifier) = settings.get("drag_span_modifier").and_then(Value::as_str) {
+            drag_snap.span = ModifierKey::parse(modifier)?;
+        }
+
+        let focus_rules = match settings.get("focus_rules") {
+            Some(rules) => {
+                let rules: Vec<FocusRule> = serde_json::from_value(rules.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid focus_rules: {}", e))?;
+                for rule in &rules {
+                    rule.validate()?;
+                }
+                Some(rules)
+            }
+            None => None,
+        };
+
+        // Everything is valid; apply it
+        if library != self.library {
+            self.placements.clear();
+        }
+        self.library = library;
+        if let Some(cycle) = cycle {
+            self.layouts = cycle;
+            if self.current_layout_index >= self.layouts.len() {
+                self.current_layout_index = 0;
+            }
+        }
+        if let Some(assignments) = monitor_layouts {
+            self.monitor_layouts = assignments;
+        }
+        if let Some(rules) = rules {
+            self.rules = rules;
+        }
+        if let Some(rules) = focus_rules {
+            self.focus_rules = rules;
+        }
+        if let Some(enabled) = auto_restore {
+            self.auto_restore = enabled;
+        }
+        if let Some(enabled) = settings.get("drag_snap_enabled").and_then(Value::as_bool) {
+            self.drag_snap_enabled = enabled;
+        }
+        self.drag_snap = drag_snap;
+
+        let mut tiling_changed = false;
+        if let Some(gap) = tiling_gap {
+            tiling_changed |= self.tiling_gap != gap;
+            self.tiling_gap = gap;
+        }
+        if let Some(floating) = tiling_floating {
+            tiling_changed |= self.tiling_floating != floating;
+            self.tiling_floating = floating;
+        }
+        if let Some(bindings) = tiling_hotkeys {
+            tiling_changed |= self.tiling_hotkeys != bindings;
+            self.tiling_hotkeys = bindings;
+        }
+
+        // Keys left out of a partial update keep their stored values
+        self.settings.extend(settings.clone());
+        self.store_layouts()?;
+        self.store_cycle();
+        self.store_rules()?;
+        self.settings.insert("focus_rules".to_string(), serde_json::to_value(&self.focus_rules)?);
+
+        if let Some(enabled) = settings.get("tiling_enabled").and_then(Value::as_bool) {
+            if enabled != self.tiling_enabled {
+                self.set_tiling_enabled(enabled)?;
+            }
+        }
+        if tiling_changed && self.tiling.is_some() {
+            // Pick up the new gap, floating rules and hotkeys
+            self.stop_tiling();
+            self.start_tiling()?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(hwnd: isize, exe: &str, title: &str, minimized: bool) -> WindowInfo {
+        WindowInfo {
+            hwnd,
+            pid: 1,
+            exe: exe.to_string(),
+            exe_path: String::new(),
+            class_name: String::new(),
+            title: title.to_string(),
+            rect: Rect { x: 0, y: 0, width: 100, height: 100 },
+            maximized: false,
+            minimized,
+        }
+    }
+
+    fn matcher(json: serde_json::Value) -> Option<WindowMatcher> {
+        Some(serde_json::from_value(json).unwrap())
+    }
+
+    #[test]
+    fn assign_windows_resolves_matchers_before_recent_windows() {
+        // Most recently used first
+        let windows = vec![
+            window(1, "firefox.exe", "Docs - Mozilla Firefox", false),
+            window(2, "WindowsTerminal.exe", "pwsh", true),
+            window(3, "Code.exe", "main.rs - Visual Studio Code", false),
+            window(4, "explorer.exe", "Downloads", false),
+        ];
+        let matchers = vec![matcher(serde_json::json!({ "exe": "code.exe" })), None, matcher(serde_json::json!({ "exe": "WindowsTerminal.exe" }))];
+
+        assert_eq!(assign_windows(4, &matchers, &windows, true), vec![Some(2), Some(0), Some(1), Some(3)]);
+        assert_eq!(assign_windows(4, &matchers, &windows, false), vec![Some(2), None, Some(1), None]);
+    }
+
+    #[test]
+    fn assign_windows_uses_each_window_once() {
+        let windows = vec![window(1, "Code.exe", "a.rs", false), window(2, "Code.exe", "b.rs", false)];
+        let code = matcher(serde_json::json!({ "exe": "Code.exe", "title": "\\.RS$" }));
+
+        assert_eq!(assign_windows(3, &[code.clone(), code.clone(), code], &windows, true), vec![Some(0), Some(1), None]);
+    }
+
+    #[test]
+    fn layouts_setting_changes_nothing_when_an_entry_is_invalid() {
+        let mut ds = DynamicSplit::new();
+        let before = ds.layouts.clone();
+        let entries = vec![
+            serde_json::json!({ "name": "mine", "type": "grid", "columns": [2, 1] }),
+            serde_json::json!("missing"),
+        ];
+
+        assert!(ds.update_settings(layouts(&entries)).is_err());
+        assert!(ds.find_layout("mine").is_err());
+        assert_eq!(ds.layouts, before);
+
+        ds.update_settings(layouts(&entries[..1])).unwrap();
+        assert!(ds.find_layout("mine").is_ok());
+        assert_eq!(ds.layouts, vec!["mine".to_string()]);
+    }
+
+    fn layouts(entries: &[Value]) -> HashMap<String, Value> {
+        HashMap::from([("layouts".to_string(), Value::Array(entries.to_vec()))])
+    }
+
+    fn custom_layouts(layouts: serde_json::Value) -> HashMap<String, Value> {
+        HashMap::from([("custom_layouts".to_string(), layouts)])
+    }
+
+    #[test]
+    fn custom_layouts_setting_is_checked_before_it_is_applied() {
+        let mut ds = DynamicSplit::new();
+        let builtins = ds.get_layouts();
+
+        let renamed_builtin = serde_json::json!([{ "name": "50-50", "type": "grid", "columns": [3, 1] }]);
+        assert!(ds.update_settings(custom_layouts(renamed_builtin)).is_err());
+        let invalid_second = serde_json::json!([
+            { "name": "mine", "type": "grid", "columns": [2, 1] },
+            { "name": "broken", "type": "grid", "columns": [] },
+        ]);
+        assert!(ds.update_settings(custom_layouts(invalid_second)).is_err());
+        assert_eq!(ds.get_layouts(), builtins);
+
+        let mine = serde_json::json!([{ "name": "mine", "type": "grid", "columns": [2, 1] }]);
+        ds.update_settings(custom_layouts(mine)).unwrap();
+        ds.update_settings(layouts(&[serde_json::json!("mine")])).unwrap();
+
+        // Still in the cycle, so it cannot be dropped on its own
+        assert!(ds.update_settings(custom_layouts(serde_json::json!([]))).is_err());
+        assert!(ds.find_layout("mine").is_ok());
+
+        let mut settings = custom_layouts(serde_json::json!([]));
+        settings.insert("layouts".to_string(), serde_json::json!(["50-50"]));
+        ds.update_settings(settings).unwrap();
+        assert_eq!(ds.get_layouts(), builtins);
+        assert_eq!(ds.layouts, vec!["50-50".to_string()]);
+    }
+
+    #[test]
+    fn a_bad_setting_leaves_every_other_setting_unchanged() {
+        let mut ds = DynamicSplit::new();
+        ds.update_settings(HashMap::from([("tiling_gap".to_string(), serde_json::json!(4))])).unwrap();
+        let before = ds.get_settings();
+
+        let settings = HashMap::from([
+            ("custom_layouts".to_string(), serde_json::json!([{ "name": "mine", "type": "grid", "columns": [2, 1] }])),
+            ("layouts".to_string(), serde_json::json!(["mine"])),
+            ("monitor_layouts".to_string(), serde_json::json!({ "DISPLAY1": "mine" })),
+            ("tiling_gap".to_string(), serde_json::json!(12)),
+            ("tiling_hotkeys".to_string(), serde_json::json!({ "not_an_action": "Ctrl+Alt+T" })),
+        ]);
+        assert!(ds.update_settings(settings).is_err());
+        assert!(ds.find_layout("mine").is_err());
+        assert!(!ds.layouts.contains(&"mine".to_string()));
+        assert!(ds.monitor_layouts.is_empty());
+        assert_eq!(ds.tiling_gap, 4);
+        assert_eq!(ds.get_settings(), before);
+
+        // A partial update keeps the keys it leaves out
+        ds.update_settings(HashMap::from([("auto_restore_workspaces".to_string(), serde_json::json!(true))])).unwrap();
+        assert_eq!(ds.get_settings().get("tiling_gap"), Some(&serde_json::json!(4)));
+    }
+}
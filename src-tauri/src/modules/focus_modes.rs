@@ -0,0 +1,319 @@
+use super::app_launcher::ComScope;
+use super::window_ops::{self, WindowInfo, WindowMatcher};
+use std::collections::HashMap;
+use std::ffi::c_void;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use windows::core::{IUnknown, IUnknown_Vtbl, Interface, GUID, HRESULT};
+use windows::Win32::Foundation::{BOOL, COLORREF, HWND};
+use windows::Win32::System::Com::{CoCreateInstance, IServiceProvider, CLSCTX_LOCAL_SERVER};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetLayeredWindowAttributes, GetWindowLongW, SetLayeredWindowAttributes, SetWindowLongW,
+    SetWindowPos, GWL_EXSTYLE, HWND_NOTOPMOST, HWND_TOPMOST, LAYERED_WINDOW_ATTRIBUTES_FLAGS,
+    LWA_ALPHA, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE, WS_EX_LAYERED, WS_EX_TOPMOST,
+};
+
+/// Lowest opacity allowed, so a window cannot be made invisible by accident
+pub const MIN_OPACITY: u8 = 10;
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Focus modes applied to one window. The default is an untouched window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FocusModes {
+    #[serde(skip_serializing_if = "is_false")]
+    pub always_on_top: bool,
+    /// Opacity in percent; `None` leaves the window opaque
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<u8>,
+    /// Show the window on every virtual desktop
+    #[serde(skip_serializing_if = "is_false")]
+    pub pinned: bool,
+}
+
+impl FocusModes {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(opacity) = self.opacity {
+            if !(MIN_OPACITY..=100).contains(&opacity) {
+                return Err(anyhow::anyhow!("Opacity must be between {} and 100", MIN_OPACITY));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Apply focus modes to windows of a given application when they open
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FocusRule {
+    pub name: String,
+    #[serde(flatten)]
+    pub matcher: WindowMatcher,
+    #[serde(flatten)]
+    pub modes: FocusModes,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl FocusRule {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Rule name must not be empty"));
+        }
+        if self.matcher == WindowMatcher::default() {
+            return Err(anyhow::anyhow!("Rule '{}' must match on exe, class or title", self.name));
+        }
+        self.modes.validate()
+    }
+}
+
+/// First enabled rule matching `window`
+pub fn find_focus_rule<'a>(rules: &'a [FocusRule], window: &WindowInfo) -> Option<&'a FocusRule> {
+    rules.iter().find(|rule| rule.enabled && rule.matcher.matches(window))
+}
+
+/// A window with focus modes applied, as listed to the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusModeWindow {
+    pub hwnd: isize,
+    pub title: String,
+    pub modes: FocusModes,
+}
+
+/// Window attributes focus modes change, captured before the first change
+#[derive(Debug, Clone, Copy)]
+struct OriginalStyle {
+    ex_style: u32,
+    /// Layered attributes the window set itself, if it was already layered
+    layered: Option<(COLORREF, u8, LAYERED_WINDOW_ATTRIBUTES_FLAGS)>,
+    /// Already shown on all desktops; only checked when pinning, since it
+    /// takes a round trip to the shell
+    pinned: bool,
+}
+
+impl OriginalStyle {
+    fn capture(hwnd: HWND) -> Self {
+        let ex_style = unsafe { GetWindowLongW(hwnd, GWL_EXSTYLE) } as u32;
+        let layered = (ex_style & WS_EX_LAYERED.0 != 0).then(|| {
+            let mut key = COLORREF::default();
+            let mut alpha = 255u8;
+            let mut flags = LAYERED_WINDOW_ATTRIBUTES_FLAGS::default();
+            unsafe {
+                let _ = GetLayeredWindowAttributes(hwnd, Some(&mut key), Some(&mut alpha), Some(&mut flags));
+            }
+            (key, alpha, flags)
+        });
+        Self { ex_style, layered, pinned: false }
+    }
+}
+
+fn ex_style(hwnd: HWND) -> u32 {
+    unsafe { GetWindowLongW(hwnd, GWL_EXSTYLE) as u32 }
+}
+
+fn set_ex_style_bit(hwnd: HWND, bit: u32, on: bool) {
+    let style = ex_style(hwnd);
+    let updated = if on { style | bit } else { style & !bit };
+    if updated != style {
+        unsafe {
+            SetWindowLongW(hwnd, GWL_EXSTYLE, updated as i32);
+        }
+    }
+}
+
+fn set_topmost(hwnd: HWND, topmost: bool) -> Result<()> {
+    let after = if topmost { HWND_TOPMOST } else { HWND_NOTOPMOST };
+    unsafe { SetWindowPos(hwnd, after, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE) }
+        .map_err(|e| anyhow::anyhow!("Could not change always-on-top: {}", e))
+}
+
+fn set_opacity(hwnd: HWND, opacity: Option<u8>, original: &OriginalStyle) -> Result<()> {
+    match (opacity, original.layered) {
+        (Some(percent), _) => {
+            set_ex_style_bit(hwnd, WS_EX_LAYERED.0, true);
+            let alpha = (percent as u32 * 255 / 100) as u8;
+            unsafe { SetLayeredWindowAttributes(hwnd, COLORREF(0), alpha, LWA_ALPHA) }
+                .map_err(|e| anyhow::anyhow!("Could not change opacity: {}", e))
+        }
+        (None, Some((key, alpha, flags))) => unsafe { SetLayeredWindowAttributes(hwnd, key, alpha, flags) }
+            .map_err(|e| anyhow::anyhow!("Could not restore opacity: {}", e)),
+        (None, None) => {
+            set_ex_style_bit(hwnd, WS_EX_LAYERED.0, false);
+            Ok(())
+        }
+    }
+}
+
+// Windows has no public API to pin a window to all virtual desktops. These
+// are the shell's own interfaces behind Task View's "Show this window on all
+// desktops"; their IDs have not changed since Windows 10 1809.
+const CLSID_IMMERSIVE_SHELL: GUID = GUID::from_u128(0xc2f03a33_21f5_47fa_b4bb_156362a2f239);
+const CLSID_VIRTUAL_DESKTOP_PINNED_APPS: GUID = GUID::from_u128(0xb5a399e7_1c87_46b8_88e9_fc5747b171bd);
+const IID_APPLICATION_VIEW_COLLECTION: GUID = GUID::from_u128(0x1841c6d7_4f9d_42c0_af41_8747538f10e5);
+const IID_VIRTUAL_DESKTOP_PINNED_APPS: GUID = GUID::from_u128(0x4ce81583_1e4c_4632_a621_07a53543148f);
+
+type Unused = *const c_void;
+
+/// `IApplicationViewCollection`, up to the one method used
+#[repr(C)]
+struct ApplicationViewCollectionVtbl {
+    base: IUnknown_Vtbl,
+    get_views: Unused,
+    get_views_by_z_order: Unused,
+    get_views_by_app_user_model_id: Unused,
+    get_view_for_hwnd: unsafe extern "system" fn(*mut c_void, HWND, *mut *mut c_void) -> HRESULT,
+}
+
+/// `IVirtualDesktopPinnedApps`
+#[repr(C)]
+struct VirtualDesktopPinnedAppsVtbl {
+    base: IUnknown_Vtbl,
+    is_app_id_pinned: Unused,
+    pin_app_id: Unused,
+    unpin_app_id: Unused,
+    is_view_pinned: unsafe extern "system" fn(*mut c_void, *mut c_void, *mut BOOL) -> HRESULT,
+    pin_view: unsafe extern "system" fn(*mut c_void, *mut c_void) -> HRESULT,
+    unpin_view: unsafe extern "system" fn(*mut c_void, *mut c_void) -> HRESULT,
+}
+
+unsafe fn query_service(shell: &IServiceProvider, service: &GUID, iid: &GUID) -> windows::core::Result<IUnknown> {
+    let mut object = std::ptr::null_mut();
+    (shell.vtable().QueryService)(shell.as_raw(), service, iid, &mut object).ok()?;
+    Ok(IUnknown::from_raw(object))
+}
+
+/// The vtable of a COM object whose interface the windows crate doesn't know
+unsafe fn vtable<T>(object: &IUnknown) -> &T {
+    &**(object.as_raw() as *const *const T)
+}
+
+/// Show a window on every virtual desktop or only its own. Returns whether
+/// it was pinned before.
+fn set_pinned(hwnd: HWND, pinned: bool) -> Result<bool> {
+    let _com = ComScope::new();
+    let unavailable = |e: windows::core::Error| anyhow::anyhow!("Pinning to all desktops is not available: {}", e);
+
+    unsafe {
+        let shell: IServiceProvider =
+            CoCreateInstance(&CLSID_IMMERSIVE_SHELL, None, CLSCTX_LOCAL_SERVER).map_err(unavailable)?;
+        let views = query_service(&shell, &IID_APPLICATION_VIEW_COLLECTION, &IID_APPLICATION_VIEW_COLLECTION)
+            .map_err(unavailable)?;
+        let pinned_apps = query_service(&shell, &CLSID_VIRTUAL_DESKTOP_PINNED_APPS, &IID_VIRTUAL_DESKTOP_PINNED_APPS)
+            .map_err(unavailable)?;
+        let views_vtable = vtable::<ApplicationViewCollectionVtbl>(&views);
+        let pinned_vtable = vtable::<VirtualDesktopPinnedAppsVtbl>(&pinned_apps);
+
+        let mut view = std::ptr::null_mut();
+        (views_vtable.get_view_for_hwnd)(views.as_raw(), hwnd, &mut view)
+            .ok()
+            .map_err(|e| anyhow::anyhow!("Window has no desktop view: {}", e))?;
+        // Released when dropped
+        let view = IUnknown::from_raw(view);
+
+        let mut was_pinned = BOOL::default();
+        (pinned_vtable.is_view_pinned)(pinned_apps.as_raw(), view.as_raw(), &mut was_pinned).ok()?;
+        if was_pinned.as_bool() != pinned {
+            let change = if pinned { pinned_vtable.pin_view } else { pinned_vtable.unpin_view };
+            change(pinned_apps.as_raw(), view.as_raw())
+                .ok()
+                .map_err(|e| anyhow::anyhow!("Could not change pinning: {}", e))?;
+        }
+        Ok(was_pinned.as_bool())
+    }
+}
+
+struct ModifiedWindow {
+    original: OriginalStyle,
+    modes: FocusModes,
+}
+
+/// Windows whose focus modes were changed, with what they looked like
+/// before, so every change can be reverted
+#[derive(Default)]
+pub struct FocusModeTracker {
+    windows: HashMap<isize, ModifiedWindow>,
+}
+
+impl FocusModeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn modes(&self, window: isize) -> FocusModes {
+        self.windows.get(&window).map(|modified| modified.modes).unwrap_or_default()
+    }
+
+    /// Set a window's focus modes. Going back to the defaults restores the
+    /// window's own style and stops tracking it. Each mode is recorded as
+    /// soon as it is applied, so one that fails leaves the others revertible.
+    pub fn apply(&mut self, window: isize, modes: FocusModes) -> Result<()> {
+        modes.validate()?;
+        let hwnd = window_ops::raw_to_hwnd(window);
+        if !window_ops::is_window(hwnd) {
+            self.windows.remove(&window);
+            return Err(anyhow::anyhow!("Window no longer exists"));
+        }
+
+        self.windows.retain(|raw, _| window_ops::is_window(window_ops::raw_to_hwnd(*raw)));
+        let modified = self.windows.entry(window).or_insert_with(|| ModifiedWindow {
+            original: OriginalStyle::capture(hwnd),
+            modes: FocusModes::default(),
+        });
+        let was_topmost = modified.original.ex_style & WS_EX_TOPMOST.0 != 0;
+
+        if modes.always_on_top != modified.modes.always_on_top {
+            set_topmost(hwnd, modes.always_on_top || was_topmost)?;
+            modified.modes.always_on_top = modes.always_on_top;
+        }
+        if modes.opacity != modified.modes.opacity {
+            set_opacity(hwnd, modes.opacity, &modified.original)?;
+            modified.modes.opacity = modes.opacity;
+        }
+        if modes.pinned != modified.modes.pinned {
+            // A window the user pinned themselves stays pinned
+            if modes.pinned {
+                modified.original.pinned = set_pinned(hwnd, true)?;
+            } else if !modified.original.pinned {
+                set_pinned(hwnd, false)?;
+            }
+            modified.modes.pinned = modes.pinned;
+        }
+
+        if modes == FocusModes::default() {
+            self.windows.remove(&window);
+        }
+        Ok(())
+    }
+
+    pub fn modified_windows(&self) -> Vec<FocusModeWindow> {
+        self.windows
+            .iter()
+            .filter_map(|(raw, modified)| {
+                let info = window_ops::window_info(window_ops::raw_to_hwnd(*raw)).ok()?;
+                Some(FocusModeWindow {
+                    hwnd: *raw,
+                    title: info.title,
+                    modes: modified.modes,
+                })
+            })
+            .collect()
+    }
+
+    /// Put every modified window back the way it was
+    pub fn revert_all(&mut self) {
+        let windows: Vec<isize> = self.windows.keys().copied().collect();
+        for window in windows {
+            if let Err(e) = self.apply(window, FocusModes::default()) {
+                log::warn!("Could not revert focus modes: {}", e);
+            }
+        }
+        self.windows.clear();
+    }
+}
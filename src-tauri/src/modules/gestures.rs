@@ -0,0 +1,368 @@
+use super::mouse_hook::{MouseButton, MouseEvent, MouseEventKind};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Points every path is resampled to before comparing
+const RESAMPLE_POINTS: usize = 64;
+/// Side of the square paths are scaled into
+const SQUARE_SIZE: f64 = 250.0;
+/// Paths shorter than this, in pixels, are clicks rather than gestures
+pub const MIN_GESTURE_LENGTH: f64 = 40.0;
+/// Longest path kept while drawing, to bound memory on a stuck button
+const MAX_PATH_POINTS: usize = 4096;
+/// Templates are direction sensitive, so only small rotations are tried
+const ANGLE_RANGE: f64 = std::f64::consts::PI / 12.0;
+const ANGLE_PRECISION: f64 = std::f64::consts::PI / 90.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    fn distance(self, other: Point) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
+
+/// A named shape, stored as the raw path it was drawn with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GestureTemplate {
+    pub name: String,
+    pub points: Vec<Point>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GestureMatch {
+    pub name: String,
+    /// 0.0 to 1.0, higher is closer
+    pub score: f64,
+}
+
+pub fn path_length(points: &[Point]) -> f64 {
+    points.windows(2).map(|pair| pair[0].distance(pair[1])).sum()
+}
+
+/// Spread `count` points evenly along the path
+fn resample(points: &[Point], count: usize) -> Vec<Point> {
+    let interval = path_length(points) / (count - 1) as f64;
+    let mut resampled = vec![points[0]];
+    let mut walked = 0.0;
+    let mut previous = points[0];
+
+    for &point in &points[1..] {
+        let mut segment = previous.distance(point);
+        let mut from = previous;
+        while walked + segment >= interval && segment > 0.0 {
+            let t = (interval - walked) / segment;
+            let inserted = Point::new(from.x + t * (point.x - from.x), from.y + t * (point.y - from.y));
+            resampled.push(inserted);
+            segment -= interval - walked;
+            walked = 0.0;
+            from = inserted;
+        }
+        walked += segment;
+        previous = point;
+    }
+
+    // Rounding can leave the last point out
+    while resampled.len() < count {
+        resampled.push(*points.last().unwrap_or(&points[0]));
+    }
+    resampled.truncate(count);
+    resampled
+}
+
+fn centroid(points: &[Point]) -> Point {
+    let n = points.len() as f64;
+    Point::new(
+        points.iter().map(|p| p.x).sum::<f64>() / n,
+        points.iter().map(|p| p.y).sum::<f64>() / n,
+    )
+}
+
+fn rotate(points: &[Point], angle: f64) -> Vec<Point> {
+    let center = centroid(points);
+    let (sin, cos) = angle.sin_cos();
+    points
+        .iter()
+        .map(|p| {
+            let (dx, dy) = (p.x - center.x, p.y - center.y);
+            Point::new(dx * cos - dy * sin + center.x, dx * sin + dy * cos + center.y)
+        })
+        .collect()
+}
+
+/// Scale uniformly so the larger side fills the square, keeping straight
+/// strokes straight, and move the centroid to the origin
+fn scale_and_center(points: &[Point]) -> Vec<Point> {
+    let (min_x, max_x) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.x), hi.max(p.x)));
+    let (min_y, max_y) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.y), hi.max(p.y)));
+    let scale = SQUARE_SIZE / (max_x - min_x).max(max_y - min_y).max(f64::EPSILON);
+
+    let scaled: Vec<Point> = points.iter().map(|p| Point::new(p.x * scale, p.y * scale)).collect();
+    let center = centroid(&scaled);
+    scaled.iter().map(|p| Point::new(p.x - center.x, p.y - center.y)).collect()
+}
+
+/// Resampled, scaled and centered path, ready to compare
+pub fn normalize(points: &[Point]) -> Result<Vec<Point>> {
+    if points.len() < 2 || path_length(points) <= 0.0 {
+        return Err(anyhow::anyhow!("Gesture path is too short"));
+    }
+    Ok(scale_and_center(&resample(points, RESAMPLE_POINTS)))
+}
+
+fn path_distance(a: &[Point], b: &[Point]) -> f64 {
+    a.iter().zip(b).map(|(p, q)| p.distance(*q)).sum::<f64>() / a.len() as f64
+}
+
+/// Smallest distance over rotations within `ANGLE_RANGE`, by golden section search
+fn distance_at_best_angle(points: &[Point], template: &[Point]) -> f64 {
+    let phi = 0.5 * (5f64.sqrt() - 1.0);
+    let (mut low, mut high) = (-ANGLE_RANGE, ANGLE_RANGE);
+    let mut x1 = phi * low + (1.0 - phi) * high;
+    let mut x2 = (1.0 - phi) * low + phi * high;
+    let mut f1 = path_distance(&rotate(points, x1), template);
+    let mut f2 = path_distance(&rotate(points, x2), template);
+
+    while (high - low).abs() > ANGLE_PRECISION {
+        if f1 < f2 {
+            high = x2;
+            x2 = x1;
+            f2 = f1;
+            x1 = phi * low + (1.0 - phi) * high;
+            f1 = path_distance(&rotate(points, x1), template);
+        } else {
+            low = x1;
+            x1 = x2;
+            f1 = f2;
+            x2 = (1.0 - phi) * low + phi * high;
+            f2 = path_distance(&rotate(points, x2), template);
+        }
+    }
+    f1.min(f2)
+}
+
+/// Minimum score a match needs for a `sensitivity` of 0 to 100. More
+/// sensitive means sloppier shapes are still recognized.
+pub fn threshold_for_sensitivity(sensitivity: u8) -> f64 {
+    0.9 - sensitivity.min(100) as f64 * 0.004
+}
+
+/// A $1-style unistroke recognizer. Unlike the original it is direction
+/// and orientation sensitive, so a stroke left and a stroke right differ,
+/// and it scales uniformly so straight strokes can be told apart.
+pub struct GestureRecognizer {
+    templates: Vec<(String, Vec<Point>)>,
+}
+
+impl GestureRecognizer {
+    pub fn new(templates: &[GestureTemplate]) -> Self {
+        let templates = templates
+            .iter()
+            .filter_map(|template| match normalize(&template.points) {
+                Ok(points) => Some((template.name.clone(), points)),
+                Err(e) => {
+                    log::warn!("Skipping gesture '{}': {}", template.name, e);
+                    None
+                }
+            })
+            .collect();
+        Self { templates }
+    }
+
+    /// Best matching template, if it scores at least `threshold`
+    pub fn recognize(&self, path: &[Point], threshold: f64) -> Option<GestureMatch> {
+        if path_length(path) < MIN_GESTURE_LENGTH {
+            return None;
+        }
+        let points = normalize(path).ok()?;
+        let half_diagonal = 0.5 * (2.0 * SQUARE_SIZE * SQUARE_SIZE).sqrt();
+
+        self.templates
+            .iter()
+            .map(|(name, template)| GestureMatch {
+                name: name.clone(),
+                score: 1.0 - distance_at_best_angle(&points, template) / half_diagonal,
+            })
+            .max_by(|a, b| a.score.total_cmp(&b.score))
+            .filter(|best| best.score >= threshold)
+    }
+}
+
+/// Straight strokes, up-then-down strokes and circles in both directions.
+/// Circles get a template for every eighth of a turn they may start at,
+/// since a $1 match depends on where the stroke starts.
+pub fn builtin_gestures() -> Vec<GestureTemplate> {
+    let polyline = |name: &str, corners: &[(f64, f64)]| GestureTemplate {
+        name: name.to_string(),
+        points: corners.iter().map(|&(x, y)| Point::new(x, y)).collect(),
+    };
+    // Screen coordinates: y grows downwards
+    let mut gestures = vec![
+        polyline("left", &[(100.0, 0.0), (0.0, 0.0)]),
+        polyline("right", &[(0.0, 0.0), (100.0, 0.0)]),
+        polyline("up", &[(0.0, 100.0), (0.0, 0.0)]),
+        polyline("down", &[(0.0, 0.0), (0.0, 100.0)]),
+        polyline("up_down", &[(0.0, 100.0), (20.0, 0.0), (40.0, 100.0)]),
+        polyline("down_up", &[(0.0, 0.0), (20.0, 100.0), (40.0, 0.0)]),
+        polyline("left_right", &[(100.0, 0.0), (0.0, 20.0), (100.0, 40.0)]),
+        polyline("right_left", &[(0.0, 0.0), (100.0, 20.0), (0.0, 40.0)]),
+    ];
+
+    for (name, direction) in [("circle_cw", 1.0), ("circle_ccw", -1.0)] {
+        for start in 0..8 {
+            let offset = start as f64 * std::f64::consts::FRAC_PI_4;
+            let points = (0..=32)
+                .map(|step| {
+                    let angle = offset + direction * step as f64 / 32.0 * std::f64::consts::TAU;
+                    Point::new(50.0 * angle.cos(), 50.0 * angle.sin())
+                })
+                .collect();
+            gestures.push(GestureTemplate { name: name.to_string(), points });
+        }
+    }
+    gestures
+}
+
+/// What a finished press of the gesture button turned out to be
+#[derive(Debug, Clone, PartialEq)]
+pub enum GestureOutcome {
+    Stroke(Vec<Point>),
+    /// Too short for a gesture; the click was swallowed and should be replayed
+    Click(MouseButton),
+}
+
+/// Records the pointer path while the gesture button is held. The press is
+/// swallowed so applications do not react to a drag they were not meant to see.
+pub struct GestureTracker {
+    button: MouseButton,
+    path: Option<Vec<Point>>,
+}
+
+impl GestureTracker {
+    pub fn new(button: MouseButton) -> Self {
+        Self { button, path: None }
+    }
+
+    pub fn is_tracking(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Whether the event was consumed, and the outcome once the button is released
+    pub fn handle(&mut self, event: &MouseEvent) -> (bool, Option<GestureOutcome>) {
+        let point = Point::new(event.x as f64, event.y as f64);
+        match (event.kind, self.path.as_mut()) {
+            (MouseEventKind::Down(button), None) if button == self.button => {
+                self.path = Some(vec![point]);
+                (true, None)
+            }
+            (MouseEventKind::Move, Some(path)) => {
+                if path.len() < MAX_PATH_POINTS {
+                    path.push(point);
+                }
+                (false, None)
+            }
+            (MouseEventKind::Up(button), Some(_)) if button == self.button => {
+                let mut path = self.path.take().unwrap_or_default();
+                path.push(point);
+                let outcome = if path_length(&path) < MIN_GESTURE_LENGTH {
+                    GestureOutcome::Click(button)
+                } else {
+                    GestureOutcome::Stroke(path)
+                };
+                (true, Some(outcome))
+            }
+            _ => (false, None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A hand-drawn stroke through `corners`: evenly spaced points along each
+    /// segment, each nudged a little off the line
+    fn stroke(corners: &[(f64, f64)]) -> Vec<Point> {
+        let mut points = Vec::new();
+        for pair in corners.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            for step in 0..20 {
+                let t = step as f64 / 20.0;
+                let wobble = if step % 2 == 0 { 3.0 } else { -3.0 };
+                points.push(Point::new(x0 + t * (x1 - x0) + wobble, y0 + t * (y1 - y0) - wobble));
+            }
+        }
+        let &(x, y) = corners.last().unwrap();
+        points.push(Point::new(x, y));
+        points
+    }
+
+    fn recognize(path: &[Point], sensitivity: u8) -> Option<String> {
+        GestureRecognizer::new(&builtin_gestures())
+            .recognize(path, threshold_for_sensitivity(sensitivity))
+            .map(|found| found.name)
+    }
+
+    #[test]
+    fn straight_strokes_are_told_apart() {
+        assert_eq!(recognize(&stroke(&[(500.0, 300.0), (380.0, 308.0)]), 50).as_deref(), Some("left"));
+        assert_eq!(recognize(&stroke(&[(100.0, 200.0), (260.0, 192.0)]), 50).as_deref(), Some("right"));
+    }
+
+    #[test]
+    fn up_down_stroke_is_recognized_at_any_size() {
+        assert_eq!(recognize(&stroke(&[(300.0, 400.0), (335.0, 240.0), (370.0, 410.0)]), 50).as_deref(), Some("up_down"));
+        assert_eq!(recognize(&stroke(&[(10.0, 80.0), (18.0, 20.0), (26.0, 82.0)]), 50).as_deref(), Some("up_down"));
+    }
+
+    #[test]
+    fn circles_are_recognized_by_direction_wherever_they_start() {
+        // Slightly squashed, starting at the left; y grows downwards, so
+        // increasing angles go clockwise on screen
+        let circle = |direction: f64| -> Vec<Point> {
+            (0..=40)
+                .map(|step| {
+                    let angle = std::f64::consts::PI + direction * step as f64 / 40.0 * std::f64::consts::TAU;
+                    Point::new(400.0 + 90.0 * angle.cos(), 300.0 + 75.0 * angle.sin())
+                })
+                .collect()
+        };
+        assert_eq!(recognize(&circle(1.0), 50).as_deref(), Some("circle_cw"));
+        assert_eq!(recognize(&circle(-1.0), 50).as_deref(), Some("circle_ccw"));
+    }
+
+    #[test]
+    fn sensitivity_sets_how_close_a_match_must_be() {
+        assert!(threshold_for_sensitivity(0) > threshold_for_sensitivity(50));
+        assert!(threshold_for_sensitivity(50) > threshold_for_sensitivity(100));
+        assert_eq!(threshold_for_sensitivity(255), threshold_for_sensitivity(100));
+
+        // A diagonal is only loosely like the vertical strokes
+        let diagonal = stroke(&[(0.0, 0.0), (150.0, 150.0)]);
+        assert_eq!(recognize(&diagonal, 0), None);
+        assert!(recognize(&diagonal, 100).is_some());
+    }
+
+    #[test]
+    fn short_paths_are_clicks() {
+        assert_eq!(recognize(&[Point::new(0.0, 0.0), Point::new(30.0, 0.0)], 100), None);
+
+        let event = |kind, x| MouseEvent { kind, x, y: 0, time: 0, modifiers: Default::default() };
+        let mut tracker = GestureTracker::new(MouseButton::Right);
+        assert_eq!(tracker.handle(&event(MouseEventKind::Down(MouseButton::Right), 0)), (true, None));
+        assert_eq!(tracker.handle(&event(MouseEventKind::Move, 10)), (false, None));
+        assert_eq!(
+            tracker.handle(&event(MouseEventKind::Up(MouseButton::Right), 12)),
+            (true, Some(GestureOutcome::Click(MouseButton::Right)))
+        );
+        assert!(!tracker.is_tracking());
+    }
+}
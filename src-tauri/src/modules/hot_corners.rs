@@ -0,0 +1,336 @@
+use super::actions::{self, Action};
+use super::monitors::{self, MonitorInfo};
+use super::window_ops;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use windows::Win32::Foundation::POINT;
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetAsyncKeyState, VK_LBUTTON, VK_MBUTTON, VK_RBUTTON};
+use windows::Win32::UI::Shell::{
+    SHQueryUserNotificationState, QUNS_BUSY, QUNS_PRESENTATION_MODE, QUNS_RUNNING_D3D_FULL_SCREEN,
+};
+use windows::Win32::UI::WindowsAndMessaging::{GetCursorPos, GetWindowLongW, GWL_STYLE, WS_CAPTION};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(25);
+/// Monitors are re-read this often, so plugging one in needs no restart
+const MONITOR_REFRESH: Duration = Duration::from_secs(2);
+/// Window classes of the desktop itself, which always covers its monitor
+const DESKTOP_CLASSES: &[&str] = &["Progman", "WorkerW"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenZone {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// An action fired when the cursor rests in a corner or against an edge.
+/// Only outer edges count; the cursor passes straight over one shared with
+/// another monitor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HotCorner {
+    pub zone: ScreenZone,
+    /// Monitor id like `\\.\DISPLAY2`; every monitor when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+    #[serde(deserialize_with = "actions::deserialize_action")]
+    pub action: Action,
+    /// Overrides the global dwell time for this trigger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dwell_ms: Option<u32>,
+}
+
+impl HotCorner {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(dwell) = self.dwell_ms {
+            check_dwell(dwell)?;
+        }
+        self.action
+            .validate()
+            .map_err(|e| anyhow::anyhow!("Hot corner {:?}: {}", self.zone, e))
+    }
+
+    fn applies_to(&self, zone: ScreenZone, monitor: &str) -> bool {
+        self.zone == zone && self.monitor.as_deref().is_none_or(|id| id == monitor)
+    }
+}
+
+fn check_dwell(dwell_ms: u32) -> Result<()> {
+    if dwell_ms > 5000 {
+        return Err(anyhow::anyhow!("Hot corner dwell time must be at most 5000 ms"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HotCornerOptions {
+    /// How long the cursor has to stay before the action fires
+    pub dwell_ms: u32,
+    /// Corners are squares this many pixels wide; edges are the last pixel
+    pub corner_size: i32,
+    /// Fire while a fullscreen app, game or presentation has the focus
+    pub in_fullscreen: bool,
+}
+
+impl Default for HotCornerOptions {
+    fn default() -> Self {
+        Self { dwell_ms: 300, corner_size: 8, in_fullscreen: false }
+    }
+}
+
+impl HotCornerOptions {
+    pub fn validate(&self) -> Result<()> {
+        check_dwell(self.dwell_ms)?;
+        if !(1..=100).contains(&self.corner_size) {
+            return Err(anyhow::anyhow!("Hot corner size must be between 1 and 100 pixels"));
+        }
+        Ok(())
+    }
+}
+
+/// The corner and edge under a point, on the monitor that holds it
+fn zones_at(x: i32, y: i32, monitors: &[MonitorInfo], corner_size: i32) -> Option<(&MonitorInfo, Option<ScreenZone>, Option<ScreenZone>)> {
+    let monitor = monitors.iter().find(|monitor| monitor.bounds.contains(x, y))?;
+    let bounds = monitor.bounds;
+    let off_screen = |x: i32, y: i32| !monitors.iter().any(|monitor| monitor.bounds.contains(x, y));
+
+    let near = |distance: i32, beyond: (i32, i32)| distance < corner_size && off_screen(beyond.0, beyond.1);
+    let left = near(x - bounds.x, (bounds.x - 1, y));
+    let right = near(bounds.right() - 1 - x, (bounds.right(), y));
+    let top = near(y - bounds.y, (x, bounds.y - 1));
+    let bottom = near(bounds.bottom() - 1 - y, (x, bounds.bottom()));
+    let corner = match (left, right, top, bottom) {
+        (true, _, true, _) => Some(ScreenZone::TopLeft),
+        (_, true, true, _) => Some(ScreenZone::TopRight),
+        (true, _, _, true) => Some(ScreenZone::BottomLeft),
+        (_, true, _, true) => Some(ScreenZone::BottomRight),
+        _ => None,
+    };
+
+    let edge = if left && x == bounds.x {
+        Some(ScreenZone::Left)
+    } else if right && x == bounds.right() - 1 {
+        Some(ScreenZone::Right)
+    } else if top && y == bounds.y {
+        Some(ScreenZone::Top)
+    } else if bottom && y == bounds.bottom() - 1 {
+        Some(ScreenZone::Bottom)
+    } else {
+        None
+    };
+    Some((monitor, corner, edge))
+}
+
+/// Index of the trigger for a point. A corner trigger wins over the edges
+/// it sits on.
+fn trigger_at(triggers: &[HotCorner], x: i32, y: i32, monitors: &[MonitorInfo], corner_size: i32) -> Option<usize> {
+    let (monitor, corner, edge) = zones_at(x, y, monitors, corner_size)?;
+    let find = |zone: ScreenZone| triggers.iter().position(|trigger| trigger.applies_to(zone, &monitor.id));
+    corner.and_then(find).or_else(|| edge.and_then(find))
+}
+
+/// Whether a fullscreen app, game or presentation has the focus
+fn fullscreen_focused() -> bool {
+    if let Ok(state) = unsafe { SHQueryUserNotificationState() } {
+        if [QUNS_BUSY, QUNS_RUNNING_D3D_FULL_SCREEN, QUNS_PRESENTATION_MODE].contains(&state) {
+            return true;
+        }
+    }
+
+    // Borderless "fullscreen" windows don't always tell the shell
+    let Ok(hwnd) = window_ops::foreground_window() else {
+        return false;
+    };
+    let (Ok(window), Ok(monitor)) = (window_ops::window_info(hwnd), monitors::monitor_for_window(hwnd)) else {
+        return false;
+    };
+    if DESKTOP_CLASSES.contains(&window.class_name.as_str()) {
+        return false;
+    }
+    // A maximized window covers its monitor too once the taskbar auto-hides,
+    // but keeps its title bar
+    let style = unsafe { GetWindowLongW(hwnd, GWL_STYLE) } as u32;
+    if style & WS_CAPTION.0 == WS_CAPTION.0 {
+        return false;
+    }
+    let Ok(rect) = window_ops::window_rect(hwnd) else {
+        return false;
+    };
+    let bounds = monitor.bounds;
+    rect.x <= bounds.x && rect.y <= bounds.y && rect.right() >= bounds.right() && rect.bottom() >= bounds.bottom()
+}
+
+fn button_held() -> bool {
+    [VK_LBUTTON, VK_RBUTTON, VK_MBUTTON]
+        .iter()
+        .any(|vk| unsafe { GetAsyncKeyState(vk.0 as i32) as u16 & 0x8000 != 0 })
+}
+
+/// Times how long the cursor stays on one trigger. Each visit fires at
+/// most once; the cursor has to leave and come back to fire again.
+#[derive(Debug, Default)]
+struct DwellTracker {
+    /// Trigger, when the cursor reached it, and whether it fired
+    current: Option<(usize, Instant, bool)>,
+}
+
+impl DwellTracker {
+    /// The trigger to fire now, if any
+    fn update(&mut self, trigger: Option<usize>, now: Instant, dwell: impl Fn(usize) -> Duration) -> Option<usize> {
+        let Some(index) = trigger else {
+            self.current = None;
+            return None;
+        };
+        let (current, since, fired) = match self.current {
+            Some((current, since, fired)) if current == index => (current, since, fired),
+            _ => (index, now, false),
+        };
+        let fire = !fired && now.duration_since(since) >= dwell(current);
+        self.current = Some((current, since, fired || fire));
+        fire.then_some(current)
+    }
+}
+
+/// Polls the cursor on its own thread and fires triggers it dwells on
+pub struct HotCornerWatcher {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl HotCornerWatcher {
+    pub fn start<F>(triggers: Vec<HotCorner>, options: HotCornerOptions, on_fire: F) -> Self
+    where
+        F: Fn(Action) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let handle = thread::spawn(move || {
+            let dwell = |index: usize| {
+                Duration::from_millis(triggers[index].dwell_ms.unwrap_or(options.dwell_ms) as u64)
+            };
+            let mut tracker = DwellTracker::default();
+            let mut monitors: Vec<MonitorInfo> = Vec::new();
+            let mut monitors_read: Option<Instant> = None;
+
+            while !flag.load(Ordering::SeqCst) {
+                thread::sleep(POLL_INTERVAL);
+                let now = Instant::now();
+                if monitors_read.is_none_or(|read| now.duration_since(read) >= MONITOR_REFRESH) {
+                    monitors = monitors::enumerate_monitors().unwrap_or_default();
+                    monitors_read = Some(now);
+                }
+
+                let mut cursor = POINT::default();
+                // Dragging to an edge is moving a window or a selection
+                let trigger = if unsafe { GetCursorPos(&mut cursor) }.is_ok() && !button_held() {
+                    trigger_at(&triggers, cursor.x, cursor.y, &monitors, options.corner_size)
+                } else {
+                    None
+                };
+
+                let Some(index) = tracker.update(trigger, now, dwell) else {
+                    continue;
+                };
+                if !options.in_fullscreen && fullscreen_focused() {
+                    log::debug!("Hot corner {:?} suppressed by a fullscreen app", triggers[index].zone);
+                    continue;
+                }
+                log::info!("Hot corner {:?} fired", triggers[index].zone);
+                on_fire(triggers[index].action.clone());
+            }
+        });
+
+        Self { stop, handle }
+    }
+
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let _ = self.handle.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::layout_engine::Rect;
+
+    fn monitor(id: &str, x: i32, width: i32, height: i32) -> MonitorInfo {
+        let bounds = Rect { x, y: 0, width, height };
+        MonitorInfo { id: id.to_string(), bounds, work_area: bounds, dpi: 96, scale: 1.0, primary: x == 0 }
+    }
+
+    /// A 1920x1080 monitor with a shorter 1280x1024 one to its right
+    fn zones(x: i32, y: i32) -> Option<(String, Option<ScreenZone>, Option<ScreenZone>)> {
+        let monitors = [monitor("DISPLAY1", 0, 1920, 1080), monitor("DISPLAY2", 1920, 1280, 1024)];
+        zones_at(x, y, &monitors, 8).map(|(monitor, corner, edge)| (monitor.id.clone(), corner, edge))
+    }
+
+    fn on(id: &str, corner: Option<ScreenZone>, edge: Option<ScreenZone>) -> Option<(String, Option<ScreenZone>, Option<ScreenZone>)> {
+        Some((id.to_string(), corner, edge))
+    }
+
+    #[test]
+    fn corners_and_edges_are_found_on_outer_edges() {
+        use ScreenZone::*;
+        assert_eq!(zones(0, 0), on("DISPLAY1", Some(TopLeft), Some(Left)));
+        assert_eq!(zones(7, 7), on("DISPLAY1", Some(TopLeft), None));
+        assert_eq!(zones(8, 7), on("DISPLAY1", None, None));
+        assert_eq!(zones(500, 0), on("DISPLAY1", None, Some(Top)));
+        assert_eq!(zones(500, 1079), on("DISPLAY1", None, Some(Bottom)));
+        assert_eq!(zones(0, 1079), on("DISPLAY1", Some(BottomLeft), Some(Left)));
+        assert_eq!(zones(500, 500), on("DISPLAY1", None, None));
+        assert_eq!(zones(3199, 1023), on("DISPLAY2", Some(BottomRight), Some(Right)));
+        assert_eq!(zones(3199, 0), on("DISPLAY2", Some(TopRight), Some(Right)));
+        assert_eq!(zones(5000, 0), None);
+    }
+
+    #[test]
+    fn shared_edges_are_not_triggers() {
+        use ScreenZone::*;
+        assert_eq!(zones(1919, 500), on("DISPLAY1", None, None));
+        assert_eq!(zones(1920, 500), on("DISPLAY2", None, None));
+        // The top corners along the shared edge are only top edges
+        assert_eq!(zones(1919, 0), on("DISPLAY1", None, Some(Top)));
+        assert_eq!(zones(1920, 0), on("DISPLAY2", None, Some(Top)));
+        // Below the shorter monitor the right edge of the first is outer again
+        assert_eq!(zones(1919, 1050), on("DISPLAY1", None, Some(Right)));
+        assert_eq!(zones(1919, 1079), on("DISPLAY1", Some(BottomRight), Some(Right)));
+        assert_eq!(zones(1920, 1023), on("DISPLAY2", None, Some(Bottom)));
+    }
+
+    #[test]
+    fn dwell_fires_once_per_visit() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        // The second trigger has a dwell time of its own
+        let dwell = |index: usize| Duration::from_millis(if index == 0 { 100 } else { 300 });
+        let mut tracker = DwellTracker::default();
+
+        assert_eq!(tracker.update(Some(0), at(0), dwell), None);
+        assert_eq!(tracker.update(Some(0), at(99), dwell), None);
+        assert_eq!(tracker.update(Some(0), at(100), dwell), Some(0));
+        assert_eq!(tracker.update(Some(0), at(500), dwell), None);
+
+        // Moving to another trigger starts over
+        assert_eq!(tracker.update(Some(1), at(510), dwell), None);
+        assert_eq!(tracker.update(Some(1), at(700), dwell), None);
+        assert_eq!(tracker.update(Some(1), at(810), dwell), Some(1));
+
+        // Leaving and coming back fires again, after a full dwell
+        assert_eq!(tracker.update(None, at(820), dwell), None);
+        assert_eq!(tracker.update(Some(1), at(830), dwell), None);
+        assert_eq!(tracker.update(Some(0), at(840), dwell), None);
+        assert_eq!(tracker.update(Some(0), at(900), dwell), None);
+        assert_eq!(tracker.update(Some(0), at(940), dwell), Some(0));
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    RegisterHotKey, SendInput, UnregisterHotKey, HOT_KEY_MODIFIERS, INPUT, INPUT_0,
+    INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_KEYUP, MOD_ALT, MOD_CONTROL,
+    MOD_NOREPEAT, MOD_SHIFT, MOD_WIN, VIRTUAL_KEY, VK_CONTROL, VK_LWIN, VK_MENU, VK_SHIFT,
+};
+use windows::Win32::UI::WindowsAndMessaging::{GetMessageW, PostThreadMessageW, MSG, WM_HOTKEY, WM_QUIT};
+
+/// A key plus modifiers, written like `Ctrl+Shift+V`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyChord {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub win: bool,
+    pub vk: u16,
+}
+
+const NAMED_KEYS: &[(&str, u16)] = &[
+    ("Space", 0x20),
+    ("Tab", 0x09),
+    ("Enter", 0x0D),
+    ("Esc", 0x1B),
+    ("Backspace", 0x08),
+    ("Delete", 0x2E),
+    ("Insert", 0x2D),
+    ("Home", 0x24),
+    ("End", 0x23),
+    ("PageUp", 0x21),
+    ("PageDown", 0x22),
+    ("Left", 0x25),
+    ("Up", 0x26),
+    ("Right", 0x27),
+    ("Down", 0x28),
+    ("PrintScreen", 0x2C),
+    ("CapsLock", 0x14),
+    ("LShift", 0xA0),
+    ("RShift", 0xA1),
+    ("LCtrl", 0xA2),
+    ("RCtrl", 0xA3),
+    ("LAlt", 0xA4),
+    ("RAlt", 0xA5),
+    ("LWin", 0x5B),
+    ("RWin", 0x5C),
+    ("Pause", 0x13),
+    ("VolumeMute", 0xAD),
+    ("VolumeDown", 0xAE),
+    ("VolumeUp", 0xAF),
+    ("MediaNext", 0xB0),
+    ("MediaPrev", 0xB1),
+    ("MediaPlayPause", 0xB3),
+];
+
+impl KeyChord {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut chord = KeyChord { ctrl: false, alt: false, shift: false, win: false, vk: 0 };
+
+        for part in text.split('+').map(str::trim) {
+            match part.to_lowercase().as_str() {
+                "ctrl" | "control" => chord.ctrl = true,
+                "alt" => chord.alt = true,
+                "shift" => chord.shift = true,
+                "win" | "super" | "meta" => chord.win = true,
+                _ if chord.vk != 0 => {
+                    return Err(anyhow::anyhow!("Key chord '{}' has more than one key", text));
+                }
+                _ => chord.vk = parse_key(part)?,
+            }
+        }
+
+        if chord.vk == 0 {
+            return Err(anyhow::anyhow!("Key chord '{}' has no key", text));
+        }
+        Ok(chord)
+    }
+
+    fn hotkey_modifiers(&self) -> HOT_KEY_MODIFIERS {
+        let mut modifiers = MOD_NOREPEAT;
+        if self.ctrl {
+            modifiers |= MOD_CONTROL;
+        }
+        if self.alt {
+            modifiers |= MOD_ALT;
+        }
+        if self.shift {
+            modifiers |= MOD_SHIFT;
+        }
+        if self.win {
+            modifiers |= MOD_WIN;
+        }
+        modifiers
+    }
+
+    fn modifier_keys(&self) -> Vec<VIRTUAL_KEY> {
+        [(self.ctrl, VK_CONTROL), (self.alt, VK_MENU), (self.shift, VK_SHIFT), (self.win, VK_LWIN)]
+            .into_iter()
+            .filter(|(held, _)| *held)
+            .map(|(_, key)| key)
+            .collect()
+    }
+
+    /// Synthesize pressing the chord: modifiers down, key down/up, modifiers up
+    pub fn send(&self) -> Result<()> {
+        let modifiers = self.modifier_keys();
+        let mut inputs: Vec<INPUT> = modifiers.iter().map(|vk| key_input(*vk, false)).collect();
+        inputs.push(key_input(VIRTUAL_KEY(self.vk), false));
+        inputs.push(key_input(VIRTUAL_KEY(self.vk), true));
+        inputs.extend(modifiers.iter().rev().map(|vk| key_input(*vk, true)));
+
+        send_inputs(&inputs)
+    }
+
+    /// Press (`down`) or release the chord without the matching other half,
+    /// for remapping a held button onto a held key combination
+    pub fn send_half(&self, down: bool) -> Result<()> {
+        let modifiers = self.modifier_keys();
+        let inputs: Vec<INPUT> = if down {
+            modifiers
+                .iter()
+                .map(|vk| key_input(*vk, false))
+                .chain(std::iter::once(key_input(VIRTUAL_KEY(self.vk), false)))
+                .collect()
+        } else {
+            std::iter::once(key_input(VIRTUAL_KEY(self.vk), true))
+                .chain(modifiers.iter().rev().map(|vk| key_input(*vk, true)))
+                .collect()
+        };
+
+        send_inputs(&inputs)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = Vec::new();
+        if self.ctrl {
+            parts.push("Ctrl".to_string());
+        }
+        if self.alt {
+            parts.push("Alt".to_string());
+        }
+        if self.shift {
+            parts.push("Shift".to_string());
+        }
+        if self.win {
+            parts.push("Win".to_string());
+        }
+        parts.push(key_name(self.vk));
+        write!(f, "{}", parts.join("+"))
+    }
+}
+
+impl TryFrom<String> for KeyChord {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        KeyChord::parse(&text)
+    }
+}
+
+impl From<KeyChord> for String {
+    fn from(chord: KeyChord) -> Self {
+        chord.to_string()
+    }
+}
+
+/// Virtual-key code for a key name as written by `key_name`
+pub fn parse_key(name: &str) -> Result<u16> {
+    let upper = name.to_uppercase();
+    let mut chars = upper.chars();
+
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_uppercase() || c.is_ascii_digit() {
+            return Ok(c as u16);
+        }
+    }
+
+    if let Some(n) = upper.strip_prefix('F').and_then(|n| n.parse::<u16>().ok()) {
+        if (1..=24).contains(&n) {
+            return Ok(0x70 + n - 1);
+        }
+    }
+
+    // Keys without a name are written as their code
+    if let Some(vk) = upper.strip_prefix("0X").and_then(|hex| u16::from_str_radix(hex, 16).ok()) {
+        if (1..=0xFE).contains(&vk) {
+            return Ok(vk);
+        }
+    }
+
+    NAMED_KEYS
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, vk)| *vk)
+        .ok_or_else(|| anyhow::anyhow!("Unknown key: {}", name))
+}
+
+pub fn key_name(vk: u16) -> String {
+    match vk {
+        0x30..=0x39 | 0x41..=0x5A => (vk as u8 as char).to_string(),
+        0x70..=0x87 => format!("F{}", vk - 0x70 + 1),
+        _ => NAMED_KEYS
+            .iter()
+            .find(|(_, code)| *code == vk)
+            .map(|(name, _)| name.to_string())
+            .unwrap_or_else(|| format!("0x{:02X}", vk)),
+    }
+}
+
+pub fn key_input(vk: VIRTUAL_KEY, up: bool) -> INPUT {
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: vk,
+                wScan: 0,
+                dwFlags: if up { KEYEVENTF_KEYUP } else { KEYBD_EVENT_FLAGS(0) },
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    }
+}
+
+/// Inject a batch of synthesized input events
+pub fn send_inputs(inputs: &[INPUT]) -> Result<()> {
+    let sent = unsafe { SendInput(inputs, std::mem::size_of::<INPUT>() as i32) };
+    if sent as usize != inputs.len() {
+        return Err(anyhow::anyhow!("SendInput injected {} of {} events", sent, inputs.len()));
+    }
+    Ok(())
+}
+
+/// Global hotkeys registered on a dedicated message-loop thread.
+/// The callback receives the index of the chord that fired.
+pub struct HotkeyListener {
+    thread_id: u32,
+    handle: JoinHandle<()>,
+}
+
+impl HotkeyListener {
+    pub fn start<F>(chords: Vec<KeyChord>, on_hotkey: F) -> Result<Self>
+    where
+        F: Fn(usize) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<Result<u32, String>>();
+
+        let handle = thread::spawn(move || unsafe {
+            // Hotkeys belong to the thread that registers them, so registration
+            // and the message loop must both live here.
+            for (index, chord) in chords.iter().enumerate() {
+                if let Err(e) = RegisterHotKey(HWND::default(), index as i32 + 1, chord.hotkey_modifiers(), chord.vk as u32) {
+                    for registered in 0..index {
+                        let _ = UnregisterHotKey(HWND::default(), registered as i32 + 1);
+                    }
+                    let _ = tx.send(Err(format!("Could not register hotkey {}: {}", chord, e)));
+                    return;
+                }
+            }
+            let _ = tx.send(Ok(GetCurrentThreadId()));
+
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, HWND::default(), 0, 0).as_bool() {
+                if msg.message == WM_HOTKEY {
+                    on_hotkey(msg.wParam.0.saturating_sub(1));
+                }
+            }
+
+            for index in 0..chords.len() {
+                let _ = UnregisterHotKey(HWND::default(), index as i32 + 1);
+            }
+        });
+
+        let thread_id = rx
+            .recv()
+            .map_err(|_| anyhow::anyhow!("Hotkey thread exited unexpectedly"))?
+            .map_err(|e| anyhow::anyhow!(e))?;
+
+        Ok(Self { thread_id, handle })
+    }
+
+    pub fn stop(self) {
+        unsafe {
+            let _ = PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+        }
+        let _ = self.handle.join();
+    }
+}
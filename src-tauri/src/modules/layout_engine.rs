@@ -0,0 +1,705 @@
+use std::fs;
+use std::path::Path;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Current layouts file format version
+pub const LAYOUTS_VERSION: u32 = 1;
+
+/// Smallest zone width or height, as a fraction of the work area
+pub const MIN_ZONE_SIZE: f64 = 0.05;
+
+/// Tolerance for comparing fractional edges
+const EDGE_TOLERANCE: f64 = 1e-6;
+
+/// Pixel rectangle in screen coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn right(&self) -> i32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    /// A divider between zones side by side; its position is an x fraction
+    Vertical,
+    /// A divider between zones stacked vertically; its position is a y fraction
+    Horizontal,
+}
+
+/// A boundary between zones the layout editor can drag
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Divider {
+    pub orientation: Orientation,
+    pub position: f64,
+}
+
+/// Rectangle expressed as fractions (0.0..=1.0) of the work area
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Zone {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Zone {
+    pub const fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Map to pixels. Edges are rounded independently so zones that share an
+    /// edge in fractions also share it in pixels, leaving no gaps.
+    pub fn to_rect(&self, area: Rect) -> Rect {
+        let edge_x = |f: f64| area.x + (f * area.width as f64).round() as i32;
+        let edge_y = |f: f64| area.y + (f * area.height as f64).round() as i32;
+
+        let (left, right) = (edge_x(self.x), edge_x(self.x + self.width));
+        let (top, bottom) = (edge_y(self.y), edge_y(self.y + self.height));
+
+        Rect {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+
+    /// Offset and size along the axis a divider of `orientation` moves on
+    fn span(&self, orientation: Orientation) -> (f64, f64) {
+        match orientation {
+            Orientation::Vertical => (self.x, self.width),
+            Orientation::Horizontal => (self.y, self.height),
+        }
+    }
+
+    fn set_span(&mut self, orientation: Orientation, start: f64, size: f64) {
+        match orientation {
+            Orientation::Vertical => (self.x, self.width) = (start, size),
+            Orientation::Horizontal => (self.y, self.height) = (start, size),
+        }
+    }
+
+    fn overlaps(&self, other: &Zone) -> bool {
+        self.x + EDGE_TOLERANCE < other.x + other.width
+            && other.x + EDGE_TOLERANCE < self.x + self.width
+            && self.y + EDGE_TOLERANCE < other.y + other.height
+            && other.y + EDGE_TOLERANCE < self.y + self.height
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+fn full_span() -> Vec<f64> {
+    vec![1.0]
+}
+
+/// How a layout divides the work area. Zone order is significant: it is the
+/// zone index used when placing windows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LayoutShape {
+    /// Explicit fractional rectangles
+    Zones { zones: Vec<Zone> },
+    /// Rows x columns with relative sizes; zones are numbered row by row
+    Grid {
+        #[serde(default = "full_span")]
+        columns: Vec<f64>,
+        #[serde(default = "full_span")]
+        rows: Vec<f64>,
+    },
+    /// A full-height column at `corner`'s side, with the rest of the width split
+    /// into a row at `corner`'s top or bottom and the remainder below or above it.
+    /// Zones: column, row, remainder.
+    LShape { corner: Corner, column: f64, row: f64 },
+    /// A bar along `edge` with thickness `bar`, the rest split in two at `split`.
+    /// Zones: bar, first half, second half.
+    TShape { edge: Edge, bar: f64, split: f64 },
+}
+
+impl Corner {
+    /// Whether the corner is on the left and on the top
+    fn sides(self) -> (bool, bool) {
+        match self {
+            Corner::TopLeft => (true, true),
+            Corner::TopRight => (false, true),
+            Corner::BottomLeft => (true, false),
+            Corner::BottomRight => (false, false),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layout {
+    pub name: String,
+    #[serde(flatten)]
+    pub shape: LayoutShape,
+    /// Allow explicit zones to overlap, e.g. a centered zone over two halves
+    #[serde(default)]
+    pub allow_overlap: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LayoutsFile {
+    version: u32,
+    layouts: Vec<Layout>,
+}
+
+impl Layout {
+    pub fn new(name: &str, shape: LayoutShape) -> Self {
+        Self { name: name.to_string(), shape, allow_overlap: false }
+    }
+
+    /// Fractional zones of this layout, in zone-index order
+    pub fn zones(&self) -> Vec<Zone> {
+        match &self.shape {
+            LayoutShape::Zones { zones } => zones.clone(),
+            LayoutShape::Grid { columns, rows } => {
+                let column_spans = spans(columns);
+                let row_spans = spans(rows);
+                row_spans
+                    .iter()
+                    .flat_map(|&(y, height)| {
+                        column_spans
+                            .iter()
+                            .map(move |&(x, width)| Zone::new(x, y, width, height))
+                    })
+                    .collect()
+            }
+            LayoutShape::LShape { corner, column, row } => {
+                let (left, top) = corner.sides();
+                let column_x = if left { 0.0 } else { 1.0 - column };
+                let rest_x = if left { *column } else { 0.0 };
+                let row_y = if top { 0.0 } else { 1.0 - row };
+                let rest_y = if top { *row } else { 0.0 };
+                let rest_width = 1.0 - column;
+
+                vec![
+                    Zone::new(column_x, 0.0, *column, 1.0),
+                    Zone::new(rest_x, row_y, rest_width, *row),
+                    Zone::new(rest_x, rest_y, rest_width, 1.0 - row),
+                ]
+            }
+            LayoutShape::TShape { edge, bar, split } => {
+                let rest = 1.0 - bar;
+                match edge {
+                    Edge::Top => vec![
+                        Zone::new(0.0, 0.0, 1.0, *bar),
+                        Zone::new(0.0, *bar, *split, rest),
+                        Zone::new(*split, *bar, 1.0 - split, rest),
+                    ],
+                    Edge::Bottom => vec![
+                        Zone::new(0.0, rest, 1.0, *bar),
+                        Zone::new(0.0, 0.0, *split, rest),
+                        Zone::new(*split, 0.0, 1.0 - split, rest),
+                    ],
+                    Edge::Left => vec![
+                        Zone::new(0.0, 0.0, *bar, 1.0),
+                        Zone::new(*bar, 0.0, rest, *split),
+                        Zone::new(*bar, *split, rest, 1.0 - split),
+                    ],
+                    Edge::Right => vec![
+                        Zone::new(rest, 0.0, *bar, 1.0),
+                        Zone::new(0.0, 0.0, rest, *split),
+                        Zone::new(0.0, *split, rest, 1.0 - split),
+                    ],
+                }
+            }
+        }
+    }
+
+    /// Reject definitions that cannot produce sensible zones
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Layout name must not be empty"));
+        }
+
+        let fraction = |value: f64, what: &str| -> Result<()> {
+            if !(value > 0.0 && value < 1.0) {
+                return Err(anyhow::anyhow!("Layout '{}': {} must be between 0 and 1", self.name, what));
+            }
+            Ok(())
+        };
+
+        match &self.shape {
+            LayoutShape::Zones { zones } if zones.is_empty() => {
+                return Err(anyhow::anyhow!("Layout '{}' has no zones", self.name));
+            }
+            LayoutShape::Grid { columns, rows } => {
+                if columns.is_empty() || rows.is_empty() {
+                    return Err(anyhow::anyhow!("Layout '{}' needs at least one row and column", self.name));
+                }
+                if columns.iter().chain(rows).any(|ratio| !(*ratio > 0.0 && ratio.is_finite())) {
+                    return Err(anyhow::anyhow!("Layout '{}': grid ratios must be positive", self.name));
+                }
+            }
+            LayoutShape::LShape { column, row, .. } => {
+                fraction(*column, "column")?;
+                fraction(*row, "row")?;
+            }
+            LayoutShape::TShape { bar, split, .. } => {
+                fraction(*bar, "bar")?;
+                fraction(*split, "split")?;
+            }
+            LayoutShape::Zones { .. } => {}
+        }
+
+        for (index, zone) in self.zones().iter().enumerate() {
+            let in_bounds = zone.x >= 0.0
+                && zone.y >= 0.0
+                && zone.width > 0.0
+                && zone.height > 0.0
+                && zone.x + zone.width <= 1.0 + f64::EPSILON
+                && zone.y + zone.height <= 1.0 + f64::EPSILON;
+            if !in_bounds {
+                return Err(anyhow::anyhow!("Layout '{}': zone {} is outside the work area", self.name, index));
+            }
+            if zone.width < MIN_ZONE_SIZE - EDGE_TOLERANCE || zone.height < MIN_ZONE_SIZE - EDGE_TOLERANCE {
+                return Err(anyhow::anyhow!("Layout '{}': zone {} is smaller than the minimum size", self.name, index));
+            }
+        }
+
+        if !self.allow_overlap {
+            let zones = self.zones();
+            for (first, a) in zones.iter().enumerate() {
+                if let Some(second) = zones.iter().skip(first + 1).position(|b| a.overlaps(b)) {
+                    return Err(anyhow::anyhow!(
+                        "Layout '{}': zones {} and {} overlap",
+                        self.name,
+                        first,
+                        first + 1 + second
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Boundaries between zones that can be moved with `set_divider`, in a
+    /// fixed order: grid columns then rows; for L and T shapes the column or
+    /// bar edge then the other split; for explicit zones every shared edge,
+    /// vertical ones first, left to right and top to bottom.
+    pub fn dividers(&self) -> Vec<Divider> {
+        let vertical = |position| Divider { orientation: Orientation::Vertical, position };
+        let horizontal = |position| Divider { orientation: Orientation::Horizontal, position };
+
+        match &self.shape {
+            LayoutShape::Grid { columns, rows } => spans(columns)
+                .iter()
+                .skip(1)
+                .map(|&(offset, _)| vertical(offset))
+                .chain(spans(rows).iter().skip(1).map(|&(offset, _)| horizontal(offset)))
+                .collect(),
+            LayoutShape::LShape { corner, column, row } => {
+                let (left, top) = corner.sides();
+                vec![
+                    vertical(if left { *column } else { 1.0 - column }),
+                    horizontal(if top { *row } else { 1.0 - row }),
+                ]
+            }
+            LayoutShape::TShape { edge, bar, split } => match edge {
+                Edge::Top => vec![horizontal(*bar), vertical(*split)],
+                Edge::Bottom => vec![horizontal(1.0 - bar), vertical(*split)],
+                Edge::Left => vec![vertical(*bar), horizontal(*split)],
+                Edge::Right => vec![vertical(1.0 - bar), horizontal(*split)],
+            },
+            LayoutShape::Zones { zones } => [Orientation::Vertical, Orientation::Horizontal]
+                .into_iter()
+                .flat_map(|orientation| {
+                    let mut edges: Vec<f64> = Vec::new();
+                    for a in zones {
+                        let (start, size) = a.span(orientation);
+                        let edge = start + size;
+                        let shared = zones
+                            .iter()
+                            .any(|b| (b.span(orientation).0 - edge).abs() < EDGE_TOLERANCE);
+                        if shared && !edges.iter().any(|known| (known - edge).abs() < EDGE_TOLERANCE) {
+                            edges.push(edge);
+                        }
+                    }
+                    edges.sort_by(f64::total_cmp);
+                    edges.into_iter().map(move |position| Divider { orientation, position })
+                })
+                .collect(),
+        }
+    }
+
+    /// Move divider `index` to `position`, resizing the zones on both sides
+    /// of it together. The position is clamped so no zone gets smaller than
+    /// `MIN_ZONE_SIZE`; the position actually used is returned.
+    pub fn set_divider(&mut self, index: usize, position: f64) -> Result<f64> {
+        let divider = *self
+            .dividers()
+            .get(index)
+            .ok_or_else(|| anyhow::anyhow!("Layout '{}' has no divider {}", self.name, index))?;
+        if !position.is_finite() {
+            return Err(anyhow::anyhow!("Divider position must be a number"));
+        }
+        let clamp = |low: f64, high: f64| -> Result<f64> {
+            if low > high {
+                return Err(anyhow::anyhow!("Divider {} of layout '{}' cannot move", index, self.name));
+            }
+            Ok(position.clamp(low, high))
+        };
+
+        match &mut self.shape {
+            LayoutShape::Grid { columns, rows } => {
+                let boundary_columns = columns.len().saturating_sub(1);
+                let (ratios, boundary) = if index < boundary_columns {
+                    (columns, index + 1)
+                } else {
+                    (rows, index - boundary_columns + 1)
+                };
+                let current = spans(ratios);
+                let before = current[boundary - 1].0;
+                let after = current[boundary].0 + current[boundary].1;
+                let position = clamp(before + MIN_ZONE_SIZE, after - MIN_ZONE_SIZE)?;
+
+                // Ratios become fractions so the moved boundary lands exactly
+                *ratios = current.iter().map(|&(_, size)| size).collect();
+                ratios[boundary - 1] = position - before;
+                ratios[boundary] = after - position;
+                Ok(position)
+            }
+            LayoutShape::LShape { corner, column, row } => {
+                let (left, top) = corner.sides();
+                let position = clamp(MIN_ZONE_SIZE, 1.0 - MIN_ZONE_SIZE)?;
+                match index {
+                    0 => *column = if left { position } else { 1.0 - position },
+                    _ => *row = if top { position } else { 1.0 - position },
+                }
+                Ok(position)
+            }
+            LayoutShape::TShape { edge, bar, split } => {
+                let position = clamp(MIN_ZONE_SIZE, 1.0 - MIN_ZONE_SIZE)?;
+                match (index, edge) {
+                    (0, Edge::Top | Edge::Left) => *bar = position,
+                    (0, Edge::Bottom | Edge::Right) => *bar = 1.0 - position,
+                    _ => *split = position,
+                }
+                Ok(position)
+            }
+            LayoutShape::Zones { zones } => {
+                let orientation = divider.orientation;
+                let old = divider.position;
+                let ends_at = |zone: &Zone| {
+                    let (start, size) = zone.span(orientation);
+                    (start + size - old).abs() < EDGE_TOLERANCE
+                };
+                let starts_at = |zone: &Zone| (zone.span(orientation).0 - old).abs() < EDGE_TOLERANCE;
+
+                let mut low = MIN_ZONE_SIZE;
+                let mut high = 1.0 - MIN_ZONE_SIZE;
+                for zone in zones.iter() {
+                    let (start, size) = zone.span(orientation);
+                    if ends_at(zone) {
+                        low = low.max(start + MIN_ZONE_SIZE);
+                    }
+                    if starts_at(zone) {
+                        high = high.min(start + size - MIN_ZONE_SIZE);
+                    }
+                }
+                let position = clamp(low, high)?;
+
+                for zone in zones.iter_mut() {
+                    let (start, size) = zone.span(orientation);
+                    if ends_at(zone) {
+                        zone.set_span(orientation, start, position - start);
+                    } else if starts_at(zone) {
+                        zone.set_span(orientation, position, start + size - position);
+                    }
+                }
+                Ok(position)
+            }
+        }
+    }
+}
+
+/// Normalize ratios into (offset, size) fractions that sum to 1
+fn spans(ratios: &[f64]) -> Vec<(f64, f64)> {
+    let total: f64 = ratios.iter().sum();
+    let mut offset = 0.0;
+    ratios
+        .iter()
+        .map(|ratio| {
+            let size = ratio / total;
+            let span = (offset, size);
+            offset += size;
+            span
+        })
+        .collect()
+}
+
+pub fn export_layouts(path: &Path, layouts: &[Layout]) -> Result<()> {
+    let file = LayoutsFile {
+        version: LAYOUTS_VERSION,
+        layouts: layouts.to_vec(),
+    };
+    fs::write(path, serde_json::to_string_pretty(&file)?)?;
+    log::info!("Exported {} layouts to {:?}", layouts.len(), path);
+    Ok(())
+}
+
+/// Read and validate layouts from a JSON file written by `export_layouts`
+pub fn import_layouts(path: &Path) -> Result<Vec<Layout>> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Could not read layouts file: {}", e))?;
+    let file: LayoutsFile = serde_json::from_str(&json)?;
+
+    if file.version > LAYOUTS_VERSION {
+        return Err(anyhow::anyhow!(
+            "Layouts file version {} is newer than supported version {}",
+            file.version,
+            LAYOUTS_VERSION
+        ));
+    }
+
+    for layout in &file.layouts {
+        layout.validate()?;
+    }
+    Ok(file.layouts)
+}
+
+/// Pixel rectangles for every zone of `layout` inside `work_area`
+pub fn compute_zones(layout: &Layout, work_area: Rect) -> Vec<Rect> {
+    layout.zones().iter().map(|zone| zone.to_rect(work_area)).collect()
+}
+
+pub fn is_builtin(name: &str) -> bool {
+    builtin_layouts().iter().any(|layout| layout.name == name)
+}
+
+/// Layouts that are always available, including the original MVP presets
+pub fn builtin_layouts() -> Vec<Layout> {
+    let single = |name: &str, zone: Zone| Layout::new(name, LayoutShape::Zones { zones: vec![zone] });
+    let columns = |name: &str, columns: Vec<f64>| Layout::new(name, LayoutShape::Grid { columns, rows: full_span() });
+
+    vec![
+        single("left", Zone::new(0.0, 0.0, 0.5, 1.0)),
+        single("right", Zone::new(0.5, 0.0, 0.5, 1.0)),
+        single("left-60", Zone::new(0.0, 0.0, 0.6, 1.0)),
+        single("right-60", Zone::new(0.4, 0.0, 0.6, 1.0)),
+        single("center", Zone::new(0.125, 0.125, 0.75, 0.75)),
+        columns("50-50", vec![50.0, 50.0]),
+        columns("60-40", vec![60.0, 40.0]),
+        columns("thirds", vec![1.0, 1.0, 1.0]),
+        Layout::new("quarters", LayoutShape::Grid { columns: vec![1.0, 1.0], rows: vec![1.0, 1.0] }),
+        Layout::new("l-shape", LayoutShape::LShape { corner: Corner::BottomLeft, column: 0.5, row: 0.35 }),
+        Layout::new("t-shape", LayoutShape::TShape { edge: Edge::Top, bar: 0.5, split: 0.5 }),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = Rect { x: 100, y: 40, width: 1001, height: 767 };
+
+    fn layout(name: &str) -> Layout {
+        builtin_layouts().into_iter().find(|layout| layout.name == name).unwrap()
+    }
+
+    fn area_of(rects: &[Rect]) -> i64 {
+        rects.iter().map(|rect| rect.width as i64 * rect.height as i64).sum()
+    }
+
+    fn rects_overlap(a: &Rect, b: &Rect) -> bool {
+        a.x < b.right() && b.x < a.right() && a.y < b.bottom() && b.y < a.bottom()
+    }
+
+    #[test]
+    fn builtin_layouts_are_valid() {
+        for layout in builtin_layouts() {
+            layout.validate().unwrap_or_else(|e| panic!("{}: {}", layout.name, e));
+        }
+    }
+
+    #[test]
+    fn multi_zone_layouts_tile_the_work_area_at_odd_sizes() {
+        for name in ["50-50", "60-40", "thirds", "quarters", "l-shape", "t-shape"] {
+            let rects = compute_zones(&layout(name), AREA);
+            assert_eq!(area_of(&rects), AREA.width as i64 * AREA.height as i64, "{} leaves gaps", name);
+            for (index, a) in rects.iter().enumerate() {
+                assert!(a.x >= AREA.x && a.right() <= AREA.right(), "{} zone {} out of bounds", name, index);
+                assert!(a.y >= AREA.y && a.bottom() <= AREA.bottom(), "{} zone {} out of bounds", name, index);
+                for b in &rects[index + 1..] {
+                    assert!(!rects_overlap(a, b), "{} zones overlap", name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn thirds_share_rounded_edges() {
+        let rects = compute_zones(&layout("thirds"), AREA);
+        assert_eq!(rects.iter().map(|rect| rect.width).collect::<Vec<_>>(), vec![334, 333, 334]);
+        assert_eq!(rects[0].right(), rects[1].x);
+        assert_eq!(rects[1].right(), rects[2].x);
+        assert!(rects.iter().all(|rect| rect.y == AREA.y && rect.height == AREA.height));
+    }
+
+    #[test]
+    fn grid_zones_are_numbered_row_by_row() {
+        let zones = Layout::new("grid", LayoutShape::Grid { columns: vec![3.0, 1.0], rows: vec![1.0, 1.0] }).zones();
+        assert_eq!(zones, vec![
+            Zone::new(0.0, 0.0, 0.75, 0.5),
+            Zone::new(0.75, 0.0, 0.25, 0.5),
+            Zone::new(0.0, 0.5, 0.75, 0.5),
+            Zone::new(0.75, 0.5, 0.25, 0.5),
+        ]);
+    }
+
+    #[test]
+    fn l_shape_row_spans_the_rest_of_the_width() {
+        let shape = LayoutShape::LShape { corner: Corner::TopRight, column: 0.25, row: 0.4 };
+        let zones = Layout::new("l", shape).zones();
+        assert_eq!(zones, vec![
+            Zone::new(0.75, 0.0, 0.25, 1.0),
+            Zone::new(0.0, 0.0, 0.75, 0.4),
+            Zone::new(0.0, 0.4, 0.75, 0.6),
+        ]);
+    }
+
+    #[test]
+    fn t_shape_puts_the_bar_along_its_edge() {
+        let shape = LayoutShape::TShape { edge: Edge::Right, bar: 0.3, split: 0.5 };
+        let zones = Layout::new("t", shape).zones();
+        assert_eq!(zones[0], Zone::new(0.7, 0.0, 0.3, 1.0));
+        assert_eq!(zones[1], Zone::new(0.0, 0.0, 0.7, 0.5));
+    }
+
+    #[test]
+    fn validate_rejects_bad_definitions() {
+        let zones = |zones: Vec<Zone>| Layout::new("custom", LayoutShape::Zones { zones });
+
+        assert!(zones(Vec::new()).validate().is_err());
+        assert!(zones(vec![Zone::new(0.6, 0.0, 0.5, 1.0)]).validate().is_err(), "out of bounds");
+        assert!(zones(vec![Zone::new(0.0, 0.0, 0.01, 1.0)]).validate().is_err(), "too small");
+
+        let overlapping = vec![Zone::new(0.0, 0.0, 0.6, 1.0), Zone::new(0.5, 0.0, 0.5, 1.0)];
+        assert!(zones(overlapping.clone()).validate().is_err());
+        let mut allowed = zones(overlapping);
+        allowed.allow_overlap = true;
+        assert!(allowed.validate().is_ok());
+
+        let touching = vec![Zone::new(0.0, 0.0, 0.5, 1.0), Zone::new(0.5, 0.0, 0.5, 1.0)];
+        assert!(zones(touching).validate().is_ok());
+
+        assert!(Layout::new("grid", LayoutShape::Grid { columns: vec![1.0, -1.0], rows: vec![1.0] }).validate().is_err());
+        assert!(Layout::new("l", LayoutShape::LShape { corner: Corner::TopLeft, column: 1.0, row: 0.5 }).validate().is_err());
+        assert!(Layout::new(" ", LayoutShape::Grid { columns: vec![1.0], rows: vec![1.0] }).validate().is_err());
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    fn positions(layout: &Layout) -> Vec<(Orientation, f64)> {
+        layout.dividers().iter().map(|divider| (divider.orientation, divider.position)).collect()
+    }
+
+    #[test]
+    fn grid_dividers_move_one_boundary() {
+        let mut grid = Layout::new("grid", LayoutShape::Grid { columns: vec![1.0, 1.0, 1.0], rows: vec![1.0, 1.0] });
+        let dividers = positions(&grid);
+        assert_eq!(dividers.iter().map(|(orientation, _)| *orientation).collect::<Vec<_>>(), vec![
+            Orientation::Vertical,
+            Orientation::Vertical,
+            Orientation::Horizontal,
+        ]);
+        assert_near(dividers[0].1, 1.0 / 3.0);
+        assert_near(dividers[1].1, 2.0 / 3.0);
+        assert_near(dividers[2].1, 0.5);
+
+        assert_near(grid.set_divider(0, 0.5).unwrap(), 0.5);
+        let zones = grid.zones();
+        assert_near(zones[0].width, 0.5);
+        assert_near(zones[1].x, 0.5);
+        assert_near(zones[1].x + zones[1].width, 2.0 / 3.0);
+
+        // Clamped short of the next boundary
+        assert_near(grid.set_divider(0, 0.95).unwrap(), 2.0 / 3.0 - MIN_ZONE_SIZE);
+        assert_near(grid.set_divider(2, 0.7).unwrap(), 0.7);
+        assert_near(grid.zones()[3].y, 0.7);
+    }
+
+    #[test]
+    fn l_shape_divider_follows_the_corner() {
+        let shape = LayoutShape::LShape { corner: Corner::TopRight, column: 0.25, row: 0.4 };
+        let mut layout = Layout::new("l", shape);
+        assert_near(positions(&layout)[0].1, 0.75);
+
+        layout.set_divider(0, 0.6).unwrap();
+        assert_near(layout.zones()[0].x, 0.6);
+        assert_near(layout.zones()[0].width, 0.4);
+    }
+
+    #[test]
+    fn zone_dividers_resize_the_zones_on_both_sides() {
+        let zones = vec![
+            Zone::new(0.0, 0.0, 0.5, 1.0),
+            Zone::new(0.5, 0.0, 0.5, 0.5),
+            Zone::new(0.5, 0.5, 0.5, 0.5),
+        ];
+        let mut layout = Layout::new("zones", LayoutShape::Zones { zones });
+        assert_eq!(positions(&layout), vec![(Orientation::Vertical, 0.5), (Orientation::Horizontal, 0.5)]);
+
+        assert_near(layout.set_divider(0, 0.3).unwrap(), 0.3);
+        let zones = layout.zones();
+        assert_near(zones[0].width, 0.3);
+        for zone in &zones[1..] {
+            assert_near(zone.x, 0.3);
+            assert_near(zone.width, 0.7);
+        }
+
+        assert_near(layout.set_divider(1, 0.0).unwrap(), MIN_ZONE_SIZE);
+        assert_near(layout.zones()[1].height, MIN_ZONE_SIZE);
+        layout.validate().unwrap();
+    }
+
+    #[test]
+    fn set_divider_rejects_bad_requests() {
+        let mut layout = layout("50-50");
+        assert!(layout.set_divider(1, 0.5).is_err());
+        assert!(layout.set_divider(0, f64::NAN).is_err());
+        assert_eq!(layout, self::layout("50-50"));
+    }
+}
@@ -0,0 +1,482 @@
+use super::hotkey::{self, HotkeyListener, KeyChord};
+use super::mouse_hook::{self, Modifiers, MouseButton, MouseEventKind};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY;
+use windows::Win32::UI::WindowsAndMessaging::{
+    CallNextHookEx, GetMessageW, PostThreadMessageW, SetCursorPos, SetWindowsHookExW,
+    UnhookWindowsHookEx, HHOOK, KBDLLHOOKSTRUCT, LLKHF_INJECTED, LLMHF_INJECTED, MSG,
+    MSLLHOOKSTRUCT, WH_KEYBOARD_LL, WH_MOUSE_LL, WM_KEYDOWN, WM_KEYUP, WM_QUIT, WM_SYSKEYDOWN,
+    WM_SYSKEYUP,
+};
+
+/// Pointer moves closer together than this are not recorded
+const MOVE_INTERVAL_MS: u32 = 20;
+/// How often playback checks for the abort hotkey during a wait
+const ABORT_POLL: Duration = Duration::from_millis(10);
+pub const MAX_REPEAT: u32 = 1000;
+const MIN_SPEED: f64 = 0.1;
+const MAX_SPEED: f64 = 10.0;
+
+/// Modifier keys, in the form recorded by the low-level hook
+const MODIFIER_KEYS: [u16; 11] = [0x10, 0x11, 0x12, 0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5, 0x5B, 0x5C];
+
+/// One recorded event. Stored as a one-key object so macros stay easy to
+/// read and edit by hand, like `{"key_down": "LCtrl"}` or `{"wait": 120}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroStep {
+    /// Milliseconds at normal speed
+    Wait(u32),
+    /// Key named like in a key chord: `A`, `F5`, `LShift`, `Enter`, or a code like `0xBA`
+    KeyDown(String),
+    KeyUp(String),
+    ButtonDown(MouseButton),
+    ButtonUp(MouseButton),
+    /// Screen coordinates
+    MoveTo(i32, i32),
+    /// One notch is 120; positive is away from the user
+    Wheel(i16),
+    /// Positive is to the right
+    TiltWheel(i16),
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+fn default_repeat() -> u32 {
+    1
+}
+
+/// A named, recorded sequence of mouse and keyboard events
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Macro {
+    pub name: String,
+    /// Playback speed; 2.0 plays twice as fast
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+    pub steps: Vec<MacroStep>,
+}
+
+impl Macro {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Macro name must not be empty"));
+        }
+        if !(MIN_SPEED..=MAX_SPEED).contains(&self.speed) {
+            return Err(anyhow::anyhow!("Macro '{}' speed must be between {} and {}", self.name, MIN_SPEED, MAX_SPEED));
+        }
+        if !(1..=MAX_REPEAT).contains(&self.repeat) {
+            return Err(anyhow::anyhow!("Macro '{}' repeat must be between 1 and {}", self.name, MAX_REPEAT));
+        }
+        for step in &self.steps {
+            if let MacroStep::KeyDown(key) | MacroStep::KeyUp(key) = step {
+                hotkey::parse_key(key).map_err(|e| anyhow::anyhow!("Macro '{}': {}", self.name, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Only one macro plays at a time; two would fight over the same keys
+static PLAYING: AtomicBool = AtomicBool::new(false);
+
+struct PlayingGuard;
+
+impl Drop for PlayingGuard {
+    fn drop(&mut self) {
+        PLAYING.store(false, Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Held {
+    Key(u16),
+    Button(MouseButton),
+}
+
+/// Play a macro on the calling thread, `repeat` times at its speed. Pressing
+/// `abort` stops it between steps. Keys and buttons still held when it ends
+/// are released, so an aborted or hand-edited macro never leaves one stuck.
+pub fn play(recorded: &Macro, abort: KeyChord) -> Result<()> {
+    recorded.validate()?;
+    if PLAYING.swap(true, Ordering::SeqCst) {
+        return Err(anyhow::anyhow!("Another macro is already playing"));
+    }
+    let _guard = PlayingGuard;
+
+    let aborted = Arc::new(AtomicBool::new(false));
+    let flag = aborted.clone();
+    let listener = HotkeyListener::start(vec![abort], move |_| flag.store(true, Ordering::SeqCst))?;
+
+    let mut held = HashSet::new();
+    let result = play_steps(recorded, &aborted, &mut held);
+
+    for item in held {
+        let input = match item {
+            Held::Key(vk) => hotkey::key_input(VIRTUAL_KEY(vk), true),
+            Held::Button(button) => mouse_hook::button_input(button, true),
+        };
+        if let Err(e) = hotkey::send_inputs(&[input]) {
+            log::warn!("Could not release held input after macro: {}", e);
+        }
+    }
+    listener.stop();
+
+    if aborted.load(Ordering::SeqCst) {
+        log::info!("Macro '{}' aborted", recorded.name);
+    }
+    result
+}
+
+fn play_steps(recorded: &Macro, aborted: &AtomicBool, held: &mut HashSet<Held>) -> Result<()> {
+    for _ in 0..recorded.repeat {
+        for step in &recorded.steps {
+            if aborted.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            let input = match step {
+                MacroStep::Wait(ms) => {
+                    let mut remaining = Duration::from_millis(*ms as u64).div_f64(recorded.speed);
+                    while !remaining.is_zero() && !aborted.load(Ordering::SeqCst) {
+                        let slice = remaining.min(ABORT_POLL);
+                        thread::sleep(slice);
+                        remaining -= slice;
+                    }
+                    continue;
+                }
+                MacroStep::MoveTo(x, y) => {
+                    unsafe { SetCursorPos(*x, *y)? };
+                    continue;
+                }
+                MacroStep::KeyDown(key) | MacroStep::KeyUp(key) => {
+                    let vk = hotkey::parse_key(key)?;
+                    let up = matches!(step, MacroStep::KeyUp(_));
+                    if up {
+                        held.remove(&Held::Key(vk));
+                    } else {
+                        held.insert(Held::Key(vk));
+                    }
+                    hotkey::key_input(VIRTUAL_KEY(vk), up)
+                }
+                MacroStep::ButtonDown(button) => {
+                    held.insert(Held::Button(*button));
+                    mouse_hook::button_input(*button, false)
+                }
+                MacroStep::ButtonUp(button) => {
+                    held.remove(&Held::Button(*button));
+                    mouse_hook::button_input(*button, true)
+                }
+                MacroStep::Wheel(delta) => mouse_hook::wheel_input(*delta, false),
+                MacroStep::TiltWheel(delta) => mouse_hook::wheel_input(*delta, true),
+            };
+            hotkey::send_inputs(&[input])?;
+        }
+    }
+    Ok(())
+}
+
+/// Called from the recording thread when the stop hotkey is pressed
+pub type RecordingStopped = Box<dyn FnOnce() + Send>;
+
+struct Recording {
+    steps: Vec<MacroStep>,
+    stop: KeyChord,
+    on_stop: Option<RecordingStopped>,
+    last_time: Option<u32>,
+    last_move_time: u32,
+    /// Pointer position as of the last recorded step
+    recorded_position: Option<(i32, i32)>,
+}
+
+impl Recording {
+    fn push(&mut self, time: u32, step: MacroStep) {
+        if let Some(last) = self.last_time {
+            let elapsed = time.wrapping_sub(last);
+            if elapsed > 0 {
+                self.steps.push(MacroStep::Wait(elapsed));
+            }
+        }
+        self.last_time = Some(time);
+        self.steps.push(step);
+    }
+
+    fn move_to(&mut self, time: u32, x: i32, y: i32) {
+        if self.recorded_position != Some((x, y)) {
+            self.recorded_position = Some((x, y));
+            self.last_move_time = time;
+            self.push(time, MacroStep::MoveTo(x, y));
+        }
+    }
+
+    fn mouse(&mut self, kind: MouseEventKind, time: u32, x: i32, y: i32) {
+        if kind == MouseEventKind::Move {
+            if time.wrapping_sub(self.last_move_time) >= MOVE_INTERVAL_MS {
+                self.move_to(time, x, y);
+            }
+            return;
+        }
+        // Clicks and scrolls happen where the pointer is, even between samples
+        self.move_to(time, x, y);
+        let step = match kind {
+            MouseEventKind::Down(button) => MacroStep::ButtonDown(button),
+            MouseEventKind::Up(button) => MacroStep::ButtonUp(button),
+            MouseEventKind::Wheel(delta) => MacroStep::Wheel(delta),
+            MouseEventKind::TiltWheel(delta) => MacroStep::TiltWheel(delta),
+            MouseEventKind::Move => return,
+        };
+        self.push(time, step);
+    }
+
+    /// Whether the key is swallowed, which only the stop hotkey is
+    fn key(&mut self, vk: u16, up: bool, time: u32) -> bool {
+        if self.on_stop.is_none() {
+            return false;
+        }
+        let modifiers = Modifiers::current();
+        let stop = &self.stop;
+        if !up
+            && vk == stop.vk
+            && (modifiers.ctrl, modifiers.alt, modifiers.shift, modifiers.win) == (stop.ctrl, stop.alt, stop.shift, stop.win)
+        {
+            // The modifiers of the stop hotkey are not part of the macro
+            while let Some(step) = self.steps.last() {
+                let trailing = match step {
+                    MacroStep::Wait(_) => true,
+                    MacroStep::KeyDown(key) => hotkey::parse_key(key).is_ok_and(|vk| MODIFIER_KEYS.contains(&vk)),
+                    _ => false,
+                };
+                if !trailing {
+                    break;
+                }
+                self.steps.pop();
+            }
+            if let Some(on_stop) = self.on_stop.take() {
+                on_stop();
+            }
+            return true;
+        }
+
+        let name = hotkey::key_name(vk);
+        self.push(time, if up { MacroStep::KeyUp(name) } else { MacroStep::KeyDown(name) });
+        false
+    }
+}
+
+thread_local! {
+    // Low-level hooks are called on the thread that installed them
+    static RECORDING: RefCell<Option<Recording>> = const { RefCell::new(None) };
+}
+
+unsafe extern "system" fn record_mouse_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code >= 0 {
+        let info = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+        if info.flags & LLMHF_INJECTED == 0 {
+            if let Some(kind) = mouse_hook::decode(wparam.0 as u32, info) {
+                RECORDING.with(|recording| {
+                    if let Ok(mut recording) = recording.try_borrow_mut() {
+                        if let Some(recording) = recording.as_mut().filter(|r| r.on_stop.is_some()) {
+                            recording.mouse(kind, info.time, info.pt.x, info.pt.y);
+                        }
+                    }
+                });
+            }
+        }
+    }
+    CallNextHookEx(HHOOK::default(), code, wparam, lparam)
+}
+
+unsafe extern "system" fn record_key_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code >= 0 {
+        let info = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+        let up = match wparam.0 as u32 {
+            WM_KEYDOWN | WM_SYSKEYDOWN => Some(false),
+            WM_KEYUP | WM_SYSKEYUP => Some(true),
+            _ => None,
+        };
+        if let (Some(up), true) = (up, info.flags.0 & LLKHF_INJECTED.0 == 0) {
+            let swallow = RECORDING.with(|recording| match recording.try_borrow_mut() {
+                Ok(mut recording) => recording.as_mut().is_some_and(|r| r.key(info.vkCode as u16, up, info.time)),
+                Err(_) => false,
+            });
+            if swallow {
+                return LRESULT(1);
+            }
+        }
+    }
+    CallNextHookEx(HHOOK::default(), code, wparam, lparam)
+}
+
+/// Records physical mouse and keyboard input on a dedicated hook thread
+/// until stopped, either by `stop` or by pressing the stop hotkey, which
+/// calls `on_stop` and ignores everything after it.
+pub struct MacroRecorder {
+    thread_id: u32,
+    handle: JoinHandle<Vec<MacroStep>>,
+}
+
+impl MacroRecorder {
+    pub fn start(stop: KeyChord, on_stop: RecordingStopped) -> Result<Self> {
+        let (tx, rx) = mpsc::channel::<Result<u32, String>>();
+
+        let handle = thread::spawn(move || unsafe {
+            RECORDING.with(|recording| {
+                *recording.borrow_mut() = Some(Recording {
+                    steps: Vec::new(),
+                    stop,
+                    on_stop: Some(on_stop),
+                    last_time: None,
+                    last_move_time: 0,
+                    recorded_position: None,
+                })
+            });
+
+            let hooks = GetModuleHandleW(None).and_then(|module| {
+                let mouse = SetWindowsHookExW(WH_MOUSE_LL, Some(record_mouse_proc), module, 0)?;
+                match SetWindowsHookExW(WH_KEYBOARD_LL, Some(record_key_proc), module, 0) {
+                    Ok(keyboard) => Ok((mouse, keyboard)),
+                    Err(e) => {
+                        let _ = UnhookWindowsHookEx(mouse);
+                        Err(e)
+                    }
+                }
+            });
+            let (mouse, keyboard) = match hooks {
+                Ok(hooks) => hooks,
+                Err(e) => {
+                    let _ = tx.send(Err(format!("Could not install recording hooks: {}", e)));
+                    return Vec::new();
+                }
+            };
+            let _ = tx.send(Ok(GetCurrentThreadId()));
+
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, HWND::default(), 0, 0).as_bool() {}
+
+            let _ = UnhookWindowsHookEx(keyboard);
+            let _ = UnhookWindowsHookEx(mouse);
+            RECORDING.with(|recording| recording.borrow_mut().take().map(|r| r.steps).unwrap_or_default())
+        });
+
+        let thread_id = rx
+            .recv()
+            .map_err(|_| anyhow::anyhow!("Macro recording thread exited unexpectedly"))?
+            .map_err(|e| anyhow::anyhow!(e))?;
+
+        Ok(Self { thread_id, handle })
+    }
+
+    /// Stop recording and return what was recorded
+    pub fn stop(self) -> Vec<MacroStep> {
+        unsafe {
+            let _ = PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+        }
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HAND_WRITTEN: &str = r#"{
+        "name": "Copy line",
+        "steps": [
+            { "key_down": "Home" },
+            { "key_down": "LShift" },
+            { "key_down": "End" },
+            { "key_up": "end" },
+            { "key_up": "LShift" },
+            { "wait": 120 },
+            { "move_to": [640, -20] },
+            { "button_down": "x1" },
+            { "button_up": "x1" },
+            { "wheel": -120 },
+            { "tilt_wheel": 120 },
+            { "key_down": "0xBA" }
+        ]
+    }"#;
+
+    #[test]
+    fn hand_written_macros_parse_with_defaults() {
+        let parsed: Macro = serde_json::from_str(HAND_WRITTEN).unwrap();
+        assert_eq!(parsed.name, "Copy line");
+        assert_eq!(parsed.speed, 1.0);
+        assert_eq!(parsed.repeat, 1);
+        assert_eq!(parsed.steps[3], MacroStep::KeyUp("end".to_string()));
+        assert_eq!(parsed.steps[6], MacroStep::MoveTo(640, -20));
+        assert_eq!(parsed.steps[7], MacroStep::ButtonDown(MouseButton::X1));
+        assert_eq!(parsed.steps[9], MacroStep::Wheel(-120));
+        parsed.validate().unwrap();
+    }
+
+    #[test]
+    fn macros_print_as_one_key_objects_and_round_trip() {
+        let recorded = Macro {
+            name: "Scroll".to_string(),
+            speed: 2.0,
+            repeat: 3,
+            steps: vec![
+                MacroStep::KeyDown(hotkey::key_name(0xA2)),
+                MacroStep::Wait(50),
+                MacroStep::MoveTo(10, 20),
+                MacroStep::TiltWheel(-120),
+                MacroStep::KeyUp(hotkey::key_name(0xBA)),
+            ],
+        };
+        let printed = serde_json::to_value(&recorded).unwrap();
+        assert_eq!(
+            printed,
+            serde_json::json!({
+                "name": "Scroll",
+                "speed": 2.0,
+                "repeat": 3,
+                "steps": [
+                    { "key_down": "LCtrl" },
+                    { "wait": 50 },
+                    { "move_to": [10, 20] },
+                    { "tilt_wheel": -120 },
+                    { "key_up": "0xBA" },
+                ],
+            })
+        );
+
+        let parsed: Macro = serde_json::from_value(printed).unwrap();
+        assert_eq!(parsed, recorded);
+        parsed.validate().unwrap();
+    }
+
+    #[test]
+    fn bad_macros_are_rejected() {
+        let parse = |steps: &str| serde_json::from_str::<Macro>(&format!(r#"{{ "name": "m", "steps": {} }}"#, steps));
+        assert!(parse(r#"[{ "jump": 1 }]"#).is_err());
+        assert!(parse(r#"[{ "wait": 1, "wheel": 120 }]"#).is_err());
+        assert!(parse(r#"[{ "wait": -5 }]"#).is_err());
+        assert!(parse(r#"[{ "button_down": "fourth" }]"#).is_err());
+        assert!(parse(r#"[{ "move_to": [1] }]"#).is_err());
+        assert!(parse(r#"["wait"]"#).is_err());
+        assert!(serde_json::from_str::<Macro>(r#"{ "name": "m" }"#).is_err());
+
+        let valid: Macro = serde_json::from_str(HAND_WRITTEN).unwrap();
+        let unknown_key = Macro { steps: vec![MacroStep::KeyDown("Hyper".to_string())], ..valid.clone() };
+        assert!(unknown_key.validate().is_err());
+        assert!(Macro { name: " ".to_string(), ..valid.clone() }.validate().is_err());
+        assert!(Macro { speed: 0.0, ..valid.clone() }.validate().is_err());
+        assert!(Macro { speed: 20.0, ..valid.clone() }.validate().is_err());
+        assert!(Macro { repeat: 0, ..valid.clone() }.validate().is_err());
+        assert!(Macro { repeat: MAX_REPEAT + 1, ..valid }.validate().is_err());
+    }
+}
@@ -60,6 +60,33 @@ impl ModuleManager {
 // Re-export modules
 pub mod process_controller;
 pub mod dynamic_split;
+pub mod layout_engine;
+pub mod monitors;
+pub mod window_ops;
+pub mod window_events;
+pub mod window_rules;
+pub mod window_history;
+pub mod focus_modes;
+pub mod workspace_snapshots;
+pub mod window_backend;
+pub mod tiling;
+pub mod snap_overlay;
 pub mod taskbar_customizer;
+pub mod actions;
+pub mod app_launcher;
 pub mod mouse_action_mapper;
-pub mod clipboard_history;
\ No newline at end of file
+pub mod mouse_hook;
+pub mod mouse_bindings;
+pub mod mouse_remap;
+pub mod hot_corners;
+pub mod gestures;
+pub mod macros;
+pub mod screenshots;
+pub mod screenshot_pipeline;
+pub mod annotations;
+pub mod clipboard_history;
+pub mod clipboard_archive;
+pub mod clipboard_sync;
+pub mod snippets;
+pub mod hotkey;
+pub mod quick_paste;
\ No newline at end of file
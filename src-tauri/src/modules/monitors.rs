@@ -0,0 +1,104 @@
+use super::layout_engine::Rect;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use windows::Win32::Foundation::{BOOL, HWND, LPARAM, RECT};
+use windows::Win32::Graphics::Gdi::{
+    EnumDisplayMonitors, GetMonitorInfoW, MonitorFromWindow, HDC, HMONITOR, MONITORINFO,
+    MONITORINFOEXW, MONITOR_DEFAULTTONEAREST,
+};
+use windows::Win32::UI::HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
+use windows::Win32::UI::WindowsAndMessaging::MONITORINFOF_PRIMARY;
+
+/// DPI at 100% scaling
+const BASE_DPI: u32 = 96;
+
+/// A display with its full bounds and the work area left over after the
+/// taskbar and docked app bars. Coordinates are physical pixels; the app runs
+/// per-monitor DPI aware, so no further scaling is needed when placing windows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonitorInfo {
+    /// Device name such as `\\.\DISPLAY1`, used as the key for per-monitor settings
+    pub id: String,
+    pub bounds: Rect,
+    pub work_area: Rect,
+    pub dpi: u32,
+    pub scale: f64,
+    pub primary: bool,
+}
+
+impl MonitorInfo {
+    fn from_handle(monitor: HMONITOR) -> Result<Self> {
+        let mut info = MONITORINFOEXW::default();
+        info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+
+        unsafe {
+            if !GetMonitorInfoW(monitor, &mut info as *mut MONITORINFOEXW as *mut MONITORINFO).as_bool() {
+                return Err(anyhow::anyhow!("GetMonitorInfoW failed"));
+            }
+        }
+
+        let (mut dpi_x, mut dpi_y) = (BASE_DPI, BASE_DPI);
+        if unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) }.is_err() {
+            dpi_x = BASE_DPI;
+        }
+
+        let name_len = info.szDevice.iter().position(|&c| c == 0).unwrap_or(info.szDevice.len());
+
+        Ok(Self {
+            id: String::from_utf16_lossy(&info.szDevice[..name_len]),
+            bounds: info.monitorInfo.rcMonitor.into(),
+            work_area: info.monitorInfo.rcWork.into(),
+            dpi: dpi_x,
+            scale: dpi_x as f64 / BASE_DPI as f64,
+            primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+        })
+    }
+}
+
+unsafe extern "system" fn collect_monitor(monitor: HMONITOR, _hdc: HDC, _clip: *mut RECT, data: LPARAM) -> BOOL {
+    let handles = &mut *(data.0 as *mut Vec<HMONITOR>);
+    handles.push(monitor);
+    BOOL(1)
+}
+
+/// All attached monitors ordered left to right, then top to bottom. That
+/// order is what "next monitor" follows.
+pub fn enumerate_monitors() -> Result<Vec<MonitorInfo>> {
+    let mut handles: Vec<HMONITOR> = Vec::new();
+    unsafe {
+        if !EnumDisplayMonitors(HDC::default(), None, Some(collect_monitor), LPARAM(&mut handles as *mut _ as isize)).as_bool() {
+            return Err(anyhow::anyhow!("EnumDisplayMonitors failed"));
+        }
+    }
+
+    let mut monitors = handles
+        .into_iter()
+        .map(MonitorInfo::from_handle)
+        .collect::<Result<Vec<_>>>()?;
+    monitors.sort_by_key(|monitor| (monitor.bounds.x, monitor.bounds.y));
+
+    if monitors.is_empty() {
+        return Err(anyhow::anyhow!("No monitors found"));
+    }
+    Ok(monitors)
+}
+
+/// The monitor showing most of `hwnd`, or the nearest one if it is off-screen
+pub fn monitor_for_window(hwnd: HWND) -> Result<MonitorInfo> {
+    let monitor = unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) };
+    MonitorInfo::from_handle(monitor)
+}
+
+/// Map `rect` from one work area to another, keeping its relative position and size
+pub fn translate_rect(rect: Rect, from: Rect, to: Rect) -> Rect {
+    let scale_x = to.width as f64 / from.width.max(1) as f64;
+    let scale_y = to.height as f64 / from.height.max(1) as f64;
+
+    Rect {
+        x: to.x + ((rect.x - from.x) as f64 * scale_x).round() as i32,
+        y: to.y + ((rect.y - from.y) as f64 * scale_y).round() as i32,
+        width: (rect.width as f64 * scale_x).round() as i32,
+        height: (rect.height as f64 * scale_y).round() as i32,
+    }
+}
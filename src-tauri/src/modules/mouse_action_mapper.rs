@@ -1,79 +1,498 @@
+use super::actions::{self, Action};
+use super::gestures::{self, GestureOutcome, GestureRecognizer, GestureTemplate, GestureTracker, Point};
+use super::hot_corners::{HotCorner, HotCornerOptions, HotCornerWatcher};
+use super::hotkey::KeyChord;
+use super::macros::{Macro, MacroRecorder};
+use super::mouse_bindings::{self, BindingMatcher, MouseBinding, MouseInput, MouseProfile};
+use super::mouse_hook::{self, MouseButton, MouseEventKind, MouseHook};
+use super::mouse_remap::{RemapConfig, RemapOutcome, Remapper};
+use super::screenshot_pipeline::PostAction;
+use super::screenshots::{CaptureTarget, ScreenshotOptions};
+use super::window_events::{WindowEvent, WindowEventHandler, WindowEventKind, WindowEventWatcher};
+use super::window_ops;
 use super::Module;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use anyhow::Result;
 use serde_json::Value;
-use std::process::Command;
 
-use windows::Win32::UI::Input::KeyboardAndMouse::{
-    keybd_event, KEYEVENTF_KEYUP, VK_SNAPSHOT,
-};
+/// Callback that runs a bound action. Called from the mouse hook thread,
+/// so it must hand the work off rather than run it inline.
+pub type MouseActionHandler = Arc<dyn Fn(Action) + Send + Sync>;
 
-#[derive(Debug, Clone)]
-pub enum ActionType {
-    Screenshot,
-    LaunchApp(String),
-    WindowLayout(String),
+/// Callback run when the stop hotkey ends a macro recording. Called from
+/// the recording hook thread, like `MouseActionHandler`.
+pub type MacroStopHandler = Arc<dyn Fn() + Send + Sync>;
+
+/// Callback that receives a finished gesture stroke. Called from the mouse
+/// hook thread, like `MouseActionHandler`.
+pub type GestureHandler = Arc<dyn Fn(Vec<Point>) + Send + Sync>;
+
+/// What the hook matches against. Shared with the hook thread so a focus
+/// change can swap in another profile without restarting the hook.
+struct ActiveBindings {
+    matcher: BindingMatcher,
+    actions: Vec<Action>,
+    remapper: Remapper,
+}
+
+impl ActiveBindings {
+    fn set(&mut self, bindings: &[MouseBinding], remap: RemapConfig) {
+        self.matcher.set_bindings(bindings);
+        self.actions = bindings.iter().map(|binding| binding.action.clone()).collect();
+        self.remapper.set_config(remap);
+    }
 }
 
 pub struct MouseActionMapper {
     enabled: bool,
     sensitivity: u8,
-    actions: HashMap<String, String>,
+    /// Global bindings, used where the active profile binds nothing
+    bindings: Vec<MouseBinding>,
+    /// Global remaps, chords and wheel settings
+    remap: RemapConfig,
+    profiles: Vec<MouseProfile>,
+    /// Name of the profile matching the foreground window, `None` for global
+    active_profile: Option<String>,
+    active_bindings: Option<Arc<Mutex<ActiveBindings>>>,
+    event_handler: Option<WindowEventHandler>,
+    event_watcher: Option<WindowEventWatcher>,
+    hook: Option<MouseHook>,
+    action_handler: Option<MouseActionHandler>,
+    /// Button held to draw gestures; `None` turns gestures off
+    gesture_button: Option<MouseButton>,
+    /// Gesture name to action
+    gestures: HashMap<String, Action>,
+    custom_gestures: Vec<GestureTemplate>,
+    recognizer: GestureRecognizer,
+    /// Name the next stroke is saved under instead of being recognized
+    recording_gesture: Option<String>,
+    gesture_handler: Option<GestureHandler>,
+    macros: Vec<Macro>,
+    /// Stops a recording, and a macro while it plays
+    macro_hotkey: KeyChord,
+    /// Name the recording is saved under, and the recorder
+    macro_recording: Option<(String, MacroRecorder)>,
+    macro_stop_handler: Option<MacroStopHandler>,
+    hot_corners: Vec<HotCorner>,
+    hot_corner_options: HotCornerOptions,
+    hot_corner_watcher: Option<HotCornerWatcher>,
+    screenshot: ScreenshotOptions,
     settings: HashMap<String, Value>,
 }
 
 impl MouseActionMapper {
     pub fn new() -> Self {
-        let mut actions = HashMap::new();
-        actions.insert("middle_click".to_string(), "screenshot".to_string());
-        
+        let bindings = vec![MouseBinding {
+            input: MouseInput::parse("middle_click").expect("valid default binding"),
+            action: Action::Screenshot { target: CaptureTarget::FullScreen, pipeline: None },
+            pass_through: false,
+        }];
+
         Self {
             enabled: false,
             sensitivity: 50,
-            actions,
+            bindings,
+            remap: RemapConfig::default(),
+            profiles: Vec::new(),
+            active_profile: None,
+            active_bindings: None,
+            event_handler: None,
+            event_watcher: None,
+            hook: None,
+            action_handler: None,
+            gesture_button: None,
+            gestures: HashMap::new(),
+            custom_gestures: Vec::new(),
+            recognizer: GestureRecognizer::new(&gestures::builtin_gestures()),
+            recording_gesture: None,
+            gesture_handler: None,
+            macros: Vec::new(),
+            macro_hotkey: KeyChord::parse("Ctrl+Shift+F12").expect("valid default hotkey"),
+            macro_recording: None,
+            macro_stop_handler: None,
+            hot_corners: Vec::new(),
+            hot_corner_options: HotCornerOptions::default(),
+            hot_corner_watcher: None,
+            screenshot: ScreenshotOptions::default(),
             settings: HashMap::new(),
         }
     }
 
-    /// Take a screenshot using Windows Print Screen key
-    pub fn take_screenshot(&self) -> Result<()> {
-        unsafe {
-            // Simulate Print Screen key press
-            keybd_event(VK_SNAPSHOT.0 as u8, 0, Default::default(), 0);
-            keybd_event(VK_SNAPSHOT.0 as u8, 0, KEYEVENTF_KEYUP, 0);
+    /// Install the callback that dispatches bound actions
+    pub fn set_action_handler(&mut self, handler: MouseActionHandler) {
+        self.action_handler = Some(handler);
+    }
+
+    /// Install the callback that hands finished strokes back to `handle_gesture`
+    pub fn set_gesture_handler(&mut self, handler: GestureHandler) {
+        self.gesture_handler = Some(handler);
+    }
+
+    /// Install the callback that feeds focus changes to `handle_window_event`
+    pub fn set_window_event_handler(&mut self, handler: WindowEventHandler) {
+        self.event_handler = Some(handler);
+    }
+
+    /// Install the callback that finishes a recording when its hotkey is pressed
+    pub fn set_macro_stop_handler(&mut self, handler: MacroStopHandler) {
+        self.macro_stop_handler = Some(handler);
+    }
+
+    pub fn get_macros(&self) -> Vec<Macro> {
+        self.macros.clone()
+    }
+
+    /// Start recording mouse and keyboard input as a macro named `name`,
+    /// until the macro hotkey is pressed or `finish_macro_recording` is called
+    pub fn start_macro_recording(&mut self, name: &str) -> Result<()> {
+        if name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Macro name must not be empty"));
+        }
+        if self.macro_recording.is_some() {
+            return Err(anyhow::anyhow!("A macro is already being recorded"));
+        }
+        let handler = self
+            .macro_stop_handler
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Macro recording is not available"))?;
+
+        let recorder = MacroRecorder::start(self.macro_hotkey, Box::new(move || handler()))?;
+        self.macro_recording = Some((name.to_string(), recorder));
+        log::info!("Recording macro '{}', press {} to stop", name, self.macro_hotkey);
+        Ok(())
+    }
+
+    /// Stop recording and save the macro, replacing one with the same name
+    pub fn finish_macro_recording(&mut self) -> Result<Macro> {
+        let (name, recorder) = self
+            .macro_recording
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("No macro is being recorded"))?;
+        let recorded = Macro { name, speed: 1.0, repeat: 1, steps: recorder.stop() };
+        log::info!("Recorded macro '{}' with {} steps", recorded.name, recorded.steps.len());
+        self.save_macro(recorded.clone())?;
+        Ok(recorded)
+    }
+
+    /// Add a macro or replace the one with the same name, e.g. after editing
+    pub fn save_macro(&mut self, updated: Macro) -> Result<()> {
+        updated.validate()?;
+        match self.macros.iter_mut().find(|existing| existing.name == updated.name) {
+            Some(existing) => *existing = updated,
+            None => self.macros.push(updated),
+        }
+        self.store_macros()
+    }
+
+    pub fn delete_macro(&mut self, name: &str) -> Result<()> {
+        let before = self.macros.len();
+        self.macros.retain(|existing| existing.name != name);
+        if self.macros.len() == before {
+            return Err(anyhow::anyhow!("No macro named '{}'", name));
+        }
+        self.store_macros()
+    }
+
+    /// The macro to play and the hotkey that aborts it. Playback runs
+    /// without this module's lock, so a hotkey can stop it.
+    pub fn macro_for_playback(&self, name: &str) -> Result<(Macro, KeyChord)> {
+        self.macros
+            .iter()
+            .find(|existing| existing.name == name)
+            .map(|found| (found.clone(), self.macro_hotkey))
+            .ok_or_else(|| anyhow::anyhow!("No macro named '{}'", name))
+    }
+
+    fn store_macros(&mut self) -> Result<()> {
+        self.settings.insert("macros".to_string(), serde_json::to_value(&self.macros)?);
+        Ok(())
+    }
+
+    /// Names of all gestures that can be bound, built-in and recorded
+    pub fn get_gesture_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for template in gestures::builtin_gestures().iter().chain(&self.custom_gestures) {
+            if !names.contains(&template.name) {
+                names.push(template.name.clone());
+            }
+        }
+        names
+    }
+
+    /// Save the next stroke drawn with the gesture button as a gesture named `name`
+    pub fn record_gesture(&mut self, name: &str) -> Result<()> {
+        if name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Gesture name must not be empty"));
         }
-        log::info!("Screenshot captured");
+        if self.gesture_button.is_none() || self.hook.is_none() {
+            return Err(anyhow::anyhow!("Gestures need a gesture button and the module enabled"));
+        }
+        self.recording_gesture = Some(name.to_string());
         Ok(())
     }
 
-    /// Launch an application by path or command
-    pub fn launch_app(&self, app_path: &str) -> Result<()> {
-        // Handle common app shortcuts
-        let command = match app_path.to_lowercase().as_str() {
-            "notepad" => "notepad.exe",
-            "calculator" | "calc" => "calc.exe",
-            "explorer" => "explorer.exe",
-            "cmd" => "cmd.exe",
-            "powershell" => "powershell.exe",
-            _ => app_path,
+    pub fn is_recording_gesture(&self) -> bool {
+        self.recording_gesture.is_some()
+    }
+
+    pub fn delete_gesture(&mut self, name: &str) -> Result<()> {
+        let before = self.custom_gestures.len();
+        self.custom_gestures.retain(|template| template.name != name);
+        if self.custom_gestures.len() == before {
+            return Err(anyhow::anyhow!("No recorded gesture named '{}'", name));
+        }
+        self.gestures.remove(name);
+        self.rebuild_recognizer();
+        self.store_gestures()
+    }
+
+    /// Record or recognize a finished stroke. Returns the action bound to
+    /// it, which the caller runs once this module's lock is released.
+    pub fn handle_gesture(&mut self, path: Vec<Point>) -> Result<Option<Action>> {
+        if let Some(name) = self.recording_gesture.take() {
+            gestures::normalize(&path)?;
+            self.custom_gestures.push(GestureTemplate { name: name.clone(), points: path });
+            self.rebuild_recognizer();
+            self.store_gestures()?;
+            log::info!("Recorded gesture '{}'", name);
+            return Ok(None);
+        }
+
+        let threshold = gestures::threshold_for_sensitivity(self.sensitivity);
+        let Some(matched) = self.recognizer.recognize(&path, threshold) else {
+            log::debug!("Gesture not recognized");
+            return Ok(None);
         };
+        log::info!("Recognized gesture '{}' ({:.2})", matched.name, matched.score);
+        Ok(self.gestures.get(&matched.name).cloned())
+    }
 
-        Command::new(command)
-            .spawn()
-            .map_err(|e| anyhow::anyhow!("Failed to launch app: {}", e))?;
-        
-        log::info!("Launched app: {}", command);
+    fn rebuild_recognizer(&mut self) {
+        let mut templates = gestures::builtin_gestures();
+        templates.extend(self.custom_gestures.iter().cloned());
+        self.recognizer = GestureRecognizer::new(&templates);
+    }
+
+    fn store_gestures(&mut self) -> Result<()> {
+        self.settings.insert("custom_gestures".to_string(), serde_json::to_value(&self.custom_gestures)?);
+        self.settings.insert("gestures".to_string(), serde_json::to_value(&self.gestures)?);
         Ok(())
     }
 
-    /// Get available actions
-    pub fn get_available_actions(&self) -> Vec<String> {
-        vec![
-            "screenshot".to_string(),
-            "notepad".to_string(),
-            "calculator".to_string(),
-            "explorer".to_string(),
-        ]
+    /// Where screenshots go. Capturing runs without this module's lock,
+    /// since picking a region waits on the user.
+    pub fn screenshot_options(&self) -> ScreenshotOptions {
+        self.screenshot.clone()
+    }
+
+    pub fn get_bindings(&self) -> Vec<MouseBinding> {
+        self.bindings.clone()
+    }
+
+    pub fn get_profiles(&self) -> Vec<MouseProfile> {
+        self.profiles.clone()
+    }
+
+    pub fn active_profile(&self) -> Option<String> {
+        self.active_profile.clone()
+    }
+
+    pub fn get_remap(&self) -> RemapConfig {
+        self.remap.clone()
+    }
+
+    fn current_profile(&self) -> Option<&MouseProfile> {
+        self.active_profile
+            .as_ref()
+            .and_then(|name| self.profiles.iter().find(|profile| &profile.name == name))
+    }
+
+    /// Global bindings overlaid with the active profile's
+    fn effective_bindings(&self) -> Vec<MouseBinding> {
+        match self.current_profile() {
+            Some(profile) => mouse_bindings::merge_bindings(&profile.bindings, &self.bindings),
+            None => self.bindings.clone(),
+        }
+    }
+
+    /// Global remaps overlaid with the active profile's
+    fn effective_remap(&self) -> RemapConfig {
+        match self.current_profile() {
+            Some(profile) => self.remap.overlay(&profile.remap),
+            None => self.remap.clone(),
+        }
+    }
+
+    /// Switch to the profile matching a window, or to the global bindings
+    fn select_profile(&mut self, window: Option<&window_ops::WindowInfo>) {
+        let profile = window
+            .and_then(|window| mouse_bindings::find_profile(&self.profiles, window))
+            .map(|profile| profile.name.clone());
+        if profile == self.active_profile {
+            return;
+        }
+        log::info!("Mouse profile: {}", profile.as_deref().unwrap_or("global"));
+        self.active_profile = profile;
+
+        let (bindings, remap) = (self.effective_bindings(), self.effective_remap());
+        if let Some(active) = &self.active_bindings {
+            if let Ok(mut active) = active.lock() {
+                active.set(&bindings, remap);
+            }
+        }
+    }
+
+    fn select_foreground_profile(&mut self) {
+        let window = window_ops::foreground_window()
+            .and_then(window_ops::window_info)
+            .ok();
+        self.select_profile(window.as_ref());
+    }
+
+    /// Follow the foreground application
+    pub fn handle_window_event(&mut self, event: WindowEvent) {
+        if event.kind != WindowEventKind::Focused {
+            return;
+        }
+        let window = window_ops::window_info(window_ops::raw_to_hwnd(event.hwnd)).ok();
+        self.select_profile(window.as_ref());
+    }
+
+    fn start_event_watcher(&mut self) -> Result<()> {
+        if self.event_watcher.is_some() || self.profiles.is_empty() {
+            return Ok(());
+        }
+        let Some(handler) = self.event_handler.clone() else {
+            return Ok(());
+        };
+        self.event_watcher = Some(WindowEventWatcher::start(move |event| handler(event))?);
+        Ok(())
+    }
+
+    fn stop_event_watcher(&mut self) {
+        if let Some(watcher) = self.event_watcher.take() {
+            watcher.stop();
+        }
+    }
+
+    fn start_hook(&mut self) -> Result<()> {
+        let unused = self.bindings.is_empty()
+            && self.remap.is_empty()
+            && self
+                .profiles
+                .iter()
+                .all(|profile| profile.bindings.is_empty() && profile.remap.is_empty());
+        if self.hook.is_some() || (unused && self.gesture_button.is_none()) {
+            return Ok(());
+        }
+        let (Some(handler), Some(gesture_handler)) = (self.action_handler.clone(), self.gesture_handler.clone()) else {
+            return Ok(());
+        };
+
+        let bindings = self.effective_bindings();
+        let active = Arc::new(Mutex::new(ActiveBindings {
+            matcher: BindingMatcher::from_system(&bindings),
+            actions: bindings.iter().map(|binding| binding.action.clone()).collect(),
+            remapper: Remapper::start(self.effective_remap()),
+        }));
+        self.active_bindings = Some(active.clone());
+        let mut tracker = self.gesture_button.map(GestureTracker::new);
+        self.hook = Some(MouseHook::start(move |event| {
+            let Ok(mut active) = active.lock() else {
+                return false;
+            };
+            // Remapped buttons are never the gesture button, and their
+            // releases must get through even in the middle of a stroke
+            match active.remapper.handle(event) {
+                RemapOutcome::Swallow => return true,
+                RemapOutcome::Chord(action) => {
+                    handler(action);
+                    return true;
+                }
+                RemapOutcome::Pass => {}
+            }
+
+            // A stroke in progress owns the pointer until its button is
+            // released. Releases still reach the matcher, so a press it
+            // swallowed before the stroke began has its release swallowed too.
+            let drawing = tracker.as_ref().is_some_and(GestureTracker::is_tracking);
+            if !drawing || matches!(event.kind, MouseEventKind::Up(_)) {
+                let (matched, swallow) = active.matcher.handle(event);
+                if let Some(index) = matched {
+                    handler(active.actions[index].clone());
+                    return swallow;
+                }
+                if swallow || active.remapper.adjust_wheel(event) {
+                    return true;
+                }
+            }
+            drop(active);
+
+            let Some(tracker) = tracker.as_mut() else {
+                return false;
+            };
+            let (consumed, outcome) = tracker.handle(event);
+            match outcome {
+                Some(GestureOutcome::Stroke(path)) => gesture_handler(path),
+                Some(GestureOutcome::Click(button)) => {
+                    // Replaying from inside the hook would deadlock on the input queue
+                    std::thread::spawn(move || {
+                        if let Err(e) = mouse_hook::send_click(button) {
+                            log::warn!("Could not replay click: {}", e);
+                        }
+                    });
+                }
+                None => {}
+            }
+            consumed
+        })?);
+        Ok(())
+    }
+
+    fn start_hot_corners(&mut self) {
+        if self.hot_corner_watcher.is_some() || self.hot_corners.is_empty() {
+            return;
+        }
+        let Some(handler) = self.action_handler.clone() else {
+            return;
+        };
+        self.hot_corner_watcher = Some(HotCornerWatcher::start(
+            self.hot_corners.clone(),
+            self.hot_corner_options,
+            move |action| handler(action),
+        ));
+    }
+
+    fn stop_hot_corners(&mut self) {
+        if let Some(watcher) = self.hot_corner_watcher.take() {
+            watcher.stop();
+        }
+    }
+
+    /// Stopping drops the remapper, which releases anything it still holds
+    fn stop_hook(&mut self) {
+        if let Some(hook) = self.hook.take() {
+            hook.stop();
+        }
+        self.active_bindings = None;
+    }
+
+    /// Gestures own their button, so it cannot also be remapped or held for chords
+    /// Check that `button` is free to draw gestures under the given remaps
+    fn check_gesture_button(button: Option<MouseButton>, remap: &RemapConfig, profiles: &[MouseProfile]) -> Result<()> {
+        let Some(button) = button else {
+            return Ok(());
+        };
+        let configs = std::iter::once(remap).chain(profiles.iter().map(|profile| &profile.remap));
+        for config in configs {
+            let taken = config.remaps.iter().any(|remap| remap.from == button)
+                || config.chords.iter().any(|chord| chord.hold == button);
+            if taken {
+                return Err(anyhow::anyhow!("The gesture button {} is also remapped or held for a chord", button.name()));
+            }
+        }
+        Ok(())
     }
 }
 
@@ -92,15 +511,25 @@ impl Module for MouseActionMapper {
     
     fn enable(&mut self) -> anyhow::Result<()> {
         log::info!("Enabling Mouse Action Mapper module");
+        self.select_foreground_profile();
+        self.start_hook()?;
+        self.start_event_watcher()?;
+        self.start_hot_corners();
         self.enabled = true;
-        // TODO: Setup mouse hooks
         Ok(())
     }
     
     fn disable(&mut self) -> anyhow::Result<()> {
         log::info!("Disabling Mouse Action Mapper module");
+        self.stop_hook();
+        self.stop_event_watcher();
+        self.stop_hot_corners();
+        self.active_profile = None;
+        if let Some((name, recorder)) = self.macro_recording.take() {
+            recorder.stop();
+            log::info!("Discarded unfinished macro recording '{}'", name);
+        }
         self.enabled = false;
-        // TODO: Cleanup mouse hooks
         Ok(())
     }
     
@@ -109,7 +538,234 @@ impl Module for MouseActionMapper {
     }
     
     fn update_settings(&mut self, settings: HashMap<String, serde_json::Value>) -> anyhow::Result<()> {
-        self.settings = settings.into_iter().collect();
-        Ok(())
+        // Parse and check every key before touching the module, so a bad
+        // value leaves all settings as they were
+        let bindings = match settings.get("bindings") {
+            Some(bindings) => {
+                let bindings: Vec<MouseBinding> = serde_json::from_value(bindings.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid bindings: {}", e))?;
+                for binding in &bindings {
+                    binding.validate()?;
+                }
+                Some(bindings)
+            }
+            None => None,
+        };
+        let profiles = match settings.get("profiles") {
+            Some(profiles) => {
+                let profiles: Vec<MouseProfile> = serde_json::from_value(profiles.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid profiles: {}", e))?;
+                for profile in &profiles {
+                    profile.validate()?;
+                }
+                Some(profiles)
+            }
+            None => None,
+        };
+
+        let mut remap = self.remap.clone();
+        if let Some(remaps) = settings.get("remaps") {
+            remap.remaps = serde_json::from_value(remaps.clone())
+                .map_err(|e| anyhow::anyhow!("Invalid remaps: {}", e))?;
+        }
+        if let Some(chords) = settings.get("chords") {
+            remap.chords = serde_json::from_value(chords.clone())
+                .map_err(|e| anyhow::anyhow!("Invalid chords: {}", e))?;
+        }
+        if let Some(wheel) = settings.get("wheel") {
+            remap.wheel = serde_json::from_value(wheel.clone())
+                .map_err(|e| anyhow::anyhow!("Invalid wheel: {}", e))?;
+        }
+        remap.validate()?;
+
+        let gesture_button: Option<MouseButton> = match settings.get("gesture_button") {
+            Some(button) => serde_json::from_value(button.clone())
+                .map_err(|e| anyhow::anyhow!("Invalid gesture_button: {}", e))?,
+            None => self.gesture_button,
+        };
+        Self::check_gesture_button(gesture_button, &remap, profiles.as_deref().unwrap_or(&self.profiles))?;
+        let gestures = match settings.get("gestures") {
+            Some(gestures) => {
+                let gestures = actions::deserialize_action_map(gestures.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid gestures: {}", e))?;
+                for (name, action) in &gestures {
+                    action
+                        .validate()
+                        .map_err(|e| anyhow::anyhow!("Gesture '{}': {}", name, e))?;
+                }
+                Some(gestures)
+            }
+            None => None,
+        };
+        let custom_gestures: Option<Vec<GestureTemplate>> = match settings.get("custom_gestures") {
+            Some(custom) => Some(
+                serde_json::from_value(custom.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid custom_gestures: {}", e))?,
+            ),
+            None => None,
+        };
+
+        let macros = match settings.get("macros") {
+            Some(macros) => {
+                let macros: Vec<Macro> = serde_json::from_value(macros.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid macros: {}", e))?;
+                for recorded in &macros {
+                    recorded.validate()?;
+                }
+                Some(macros)
+            }
+            None => None,
+        };
+        let macro_hotkey = match settings.get("macro_hotkey").and_then(|value| value.as_str()) {
+            Some(hotkey) => Some(KeyChord::parse(hotkey)?),
+            None => None,
+        };
+
+        let hot_corners = match settings.get("hot_corners") {
+            Some(corners) => {
+                let corners: Vec<HotCorner> = serde_json::from_value(corners.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid hot_corners: {}", e))?;
+                for corner in &corners {
+                    corner.validate()?;
+                }
+                Some(corners)
+            }
+            None => None,
+        };
+        let mut corner_options = self.hot_corner_options;
+        if let Some(dwell) = settings.get("hot_corner_dwell_ms") {
+            corner_options.dwell_ms = serde_json::from_value(dwell.clone())
+                .map_err(|e| anyhow::anyhow!("Invalid hot_corner_dwell_ms: {}", e))?;
+        }
+        if let Some(size) = settings.get("hot_corner_size") {
+            corner_options.corner_size = serde_json::from_value(size.clone())
+                .map_err(|e| anyhow::anyhow!("Invalid hot_corner_size: {}", e))?;
+        }
+        if let Some(in_fullscreen) = settings.get("hot_corners_in_fullscreen") {
+            corner_options.in_fullscreen = serde_json::from_value(in_fullscreen.clone())
+                .map_err(|e| anyhow::anyhow!("Invalid hot_corners_in_fullscreen: {}", e))?;
+        }
+        corner_options.validate()?;
+
+        let mut screenshot = self.screenshot.clone();
+        if let Some(folder) = settings.get("screenshot_folder") {
+            screenshot.folder = folder.as_str().filter(|folder| !folder.trim().is_empty()).map(str::to_string);
+        }
+        if let Some(template) = settings.get("screenshot_filename").and_then(|value| value.as_str()) {
+            screenshot.filename_template = template.to_string();
+        }
+        if let Some(pipeline) = settings.get("screenshot_pipeline") {
+            screenshot.pipeline = serde_json::from_value(pipeline.clone())
+                .map_err(|e| anyhow::anyhow!("Invalid screenshot_pipeline: {}", e))?;
+        } else if let Some(copy) = settings.get("screenshot_clipboard") {
+            // Settings saved before the pipeline existed only had this switch
+            let copy = copy.as_bool()
+                .ok_or_else(|| anyhow::anyhow!("Invalid screenshot_clipboard: expected a boolean"))?;
+            screenshot.pipeline = if copy {
+                vec![PostAction::Save, PostAction::CopyToClipboard]
+            } else {
+                vec![PostAction::Save]
+            };
+        }
+        screenshot.validate()?;
+
+        // Everything is valid; apply it
+        if let Some(sensitivity) = settings.get("sensitivity").and_then(|value| value.as_u64()) {
+            self.sensitivity = sensitivity.min(100) as u8;
+        }
+        let mut hook_changed = false;
+        if let Some(bindings) = bindings {
+            hook_changed |= bindings != self.bindings;
+            self.bindings = bindings;
+        }
+        if let Some(profiles) = profiles {
+            hook_changed |= profiles != self.profiles;
+            self.profiles = profiles;
+        }
+        hook_changed |= remap != self.remap;
+        self.remap = remap;
+        hook_changed |= gesture_button != self.gesture_button;
+        self.gesture_button = gesture_button;
+        if let Some(gestures) = gestures {
+            self.gestures = gestures;
+        }
+        if let Some(custom) = custom_gestures {
+            self.custom_gestures = custom;
+            self.rebuild_recognizer();
+        }
+        if let Some(macros) = macros {
+            self.macros = macros;
+        }
+        if let Some(hotkey) = macro_hotkey {
+            self.macro_hotkey = hotkey;
+        }
+
+        let mut corners_changed = false;
+        if let Some(corners) = hot_corners {
+            corners_changed |= corners != self.hot_corners;
+            self.hot_corners = corners;
+        }
+        corners_changed |= corner_options != self.hot_corner_options;
+        self.hot_corner_options = corner_options;
+        self.screenshot = screenshot;
+
+        // The hot corner thread owns a copy of its triggers
+        if corners_changed && self.enabled {
+            self.stop_hot_corners();
+            self.start_hot_corners();
+        }
+
+        // The hook thread owns a copy of the bindings and gesture button
+        if hook_changed && self.enabled {
+            self.stop_hook();
+            self.active_profile = None;
+            self.select_foreground_profile();
+            self.start_hook()?;
+            if self.profiles.is_empty() {
+                self.stop_event_watcher();
+            } else {
+                self.start_event_watcher()?;
+            }
+        }
+
+        // Keys left out of a partial update keep their stored values
+        self.settings.extend(settings);
+        // Store actions in their typed form, so legacy names are upgraded on save
+        self.settings.insert("bindings".to_string(), serde_json::to_value(&self.bindings)?);
+        self.settings.insert("profiles".to_string(), serde_json::to_value(&self.profiles)?);
+        self.settings.insert("remaps".to_string(), serde_json::to_value(&self.remap.remaps)?);
+        self.settings.insert("chords".to_string(), serde_json::to_value(&self.remap.chords)?);
+        self.settings.insert("wheel".to_string(), serde_json::to_value(self.remap.wheel)?);
+        self.settings.insert("hot_corners".to_string(), serde_json::to_value(&self.hot_corners)?);
+        self.settings.insert("screenshot_pipeline".to_string(), serde_json::to_value(&self.screenshot.pipeline)?);
+        self.settings.remove("screenshot_clipboard");
+        self.store_gestures()?;
+        self.store_macros()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_bad_setting_leaves_every_other_setting_unchanged() {
+        let mut mapper = MouseActionMapper::new();
+        let before = mapper.get_settings();
+
+        let settings = HashMap::from([
+            ("sensitivity".to_string(), serde_json::json!(30)),
+            ("hot_corner_dwell_ms".to_string(), serde_json::json!(900)),
+            ("screenshot_pipeline".to_string(), serde_json::json!([{ "type": "copy_to_clipboard" }])),
+            ("macro_hotkey".to_string(), serde_json::json!("Ctrl+Nonsense")),
+        ]);
+        assert!(mapper.update_settings(settings).is_err());
+        assert_ne!(mapper.sensitivity, 30);
+        assert_ne!(mapper.hot_corner_options.dwell_ms, 900);
+        assert_eq!(mapper.get_settings(), before);
+
+        // A partial update keeps the keys it leaves out
+        mapper.update_settings(HashMap::from([("sensitivity".to_string(), serde_json::json!(30))])).unwrap();
+        mapper.update_settings(HashMap::from([("hot_corner_dwell_ms".to_string(), serde_json::json!(900))])).unwrap();
+        assert_eq!(mapper.get_settings().get("sensitivity"), Some(&serde_json::json!(30)));
+    }
+}
@@ -0,0 +1,388 @@
+use super::actions::{self, Action};
+use super::mouse_hook::{Modifiers, MouseButton, MouseEvent, MouseEventKind};
+use super::mouse_remap::RemapConfig;
+use super::window_ops::{WindowInfo, WindowMatcher};
+use std::collections::HashSet;
+use std::fmt;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use windows::Win32::UI::Input::KeyboardAndMouse::GetDoubleClickTime;
+use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_CXDOUBLECLK, SM_CYDOUBLECLK};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseTrigger {
+    Click(MouseButton),
+    DoubleClick(MouseButton),
+    WheelUp,
+    WheelDown,
+    TiltLeft,
+    TiltRight,
+}
+
+/// A mouse trigger plus modifiers, written like `Ctrl+x1_click` or
+/// `middle_double_click`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MouseInput {
+    pub modifiers: Modifiers,
+    pub trigger: MouseTrigger,
+}
+
+impl MouseInput {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut modifiers = Modifiers::default();
+        let mut trigger = None;
+
+        for part in text.split('+').map(str::trim) {
+            match part.to_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "alt" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                "win" | "super" | "meta" => modifiers.win = true,
+                _ if trigger.is_some() => {
+                    return Err(anyhow::anyhow!("Mouse input '{}' has more than one trigger", text));
+                }
+                name => trigger = Some(parse_trigger(name).ok_or_else(|| anyhow::anyhow!("Unknown mouse input: {}", part))?),
+            }
+        }
+
+        let trigger = trigger.ok_or_else(|| anyhow::anyhow!("Mouse input '{}' has no trigger", text))?;
+        Ok(Self { modifiers, trigger })
+    }
+}
+
+fn parse_trigger(name: &str) -> Option<MouseTrigger> {
+    match name {
+        "wheel_up" => return Some(MouseTrigger::WheelUp),
+        "wheel_down" => return Some(MouseTrigger::WheelDown),
+        "tilt_left" => return Some(MouseTrigger::TiltLeft),
+        "tilt_right" => return Some(MouseTrigger::TiltRight),
+        _ => {}
+    }
+    if let Some(button) = name.strip_suffix("_double_click") {
+        return MouseButton::from_name(button).map(MouseTrigger::DoubleClick);
+    }
+    name.strip_suffix("_click")
+        .and_then(MouseButton::from_name)
+        .map(MouseTrigger::Click)
+}
+
+impl fmt::Display for MouseInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let m = self.modifiers;
+        for (held, name) in [(m.ctrl, "Ctrl"), (m.alt, "Alt"), (m.shift, "Shift"), (m.win, "Win")] {
+            if held {
+                write!(f, "{}+", name)?;
+            }
+        }
+        match self.trigger {
+            MouseTrigger::Click(button) => write!(f, "{}_click", button.name()),
+            MouseTrigger::DoubleClick(button) => write!(f, "{}_double_click", button.name()),
+            MouseTrigger::WheelUp => write!(f, "wheel_up"),
+            MouseTrigger::WheelDown => write!(f, "wheel_down"),
+            MouseTrigger::TiltLeft => write!(f, "tilt_left"),
+            MouseTrigger::TiltRight => write!(f, "tilt_right"),
+        }
+    }
+}
+
+impl TryFrom<String> for MouseInput {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        MouseInput::parse(&text)
+    }
+}
+
+impl From<MouseInput> for String {
+    fn from(input: MouseInput) -> Self {
+        input.to_string()
+    }
+}
+
+/// A mouse input mapped to an action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MouseBinding {
+    pub input: MouseInput,
+    #[serde(deserialize_with = "actions::deserialize_action")]
+    pub action: Action,
+    /// Let applications see the input as well; by default it is swallowed
+    #[serde(default)]
+    pub pass_through: bool,
+}
+
+impl MouseBinding {
+    pub fn validate(&self) -> Result<()> {
+        self.action
+            .validate()
+            .map_err(|e| anyhow::anyhow!("Binding for {}: {}", self.input, e))?;
+        // Swallowing every plain left or right click would make the mouse unusable
+        let plain_primary = self.input.modifiers == Modifiers::default()
+            && matches!(
+                self.input.trigger,
+                MouseTrigger::Click(MouseButton::Left | MouseButton::Right)
+            );
+        if plain_primary && !self.pass_through {
+            return Err(anyhow::anyhow!("{} can only be bound with pass_through", self.input));
+        }
+        Ok(())
+    }
+}
+
+/// Bindings that apply while a matching application is in the foreground.
+/// Inputs the profile does not bind fall back to the global bindings, and
+/// the same goes for its remaps, chords and wheel settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MouseProfile {
+    pub name: String,
+    #[serde(flatten)]
+    pub matcher: WindowMatcher,
+    #[serde(default)]
+    pub bindings: Vec<MouseBinding>,
+    #[serde(flatten)]
+    pub remap: RemapConfig,
+}
+
+impl MouseProfile {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Profile name must not be empty"));
+        }
+        if self.matcher == WindowMatcher::default() {
+            return Err(anyhow::anyhow!("Profile '{}' must match on exe, class or title", self.name));
+        }
+        for binding in &self.bindings {
+            binding
+                .validate()
+                .map_err(|e| anyhow::anyhow!("Profile '{}': {}", self.name, e))?;
+        }
+        self.remap
+            .validate()
+            .map_err(|e| anyhow::anyhow!("Profile '{}': {}", self.name, e))
+    }
+}
+
+/// The first profile matching `window`; earlier profiles win
+pub fn find_profile<'a>(profiles: &'a [MouseProfile], window: &WindowInfo) -> Option<&'a MouseProfile> {
+    profiles.iter().find(|profile| profile.matcher.matches(window))
+}
+
+/// A profile's bindings followed by the global ones it does not override
+pub fn merge_bindings(profile: &[MouseBinding], global: &[MouseBinding]) -> Vec<MouseBinding> {
+    let mut merged = profile.to_vec();
+    merged.extend(
+        global
+            .iter()
+            .filter(|binding| !profile.iter().any(|own| own.input == binding.input))
+            .cloned(),
+    );
+    merged
+}
+
+/// Turns raw hook events into binding matches. Runs inside the hook, so it
+/// only keeps a little state and never blocks.
+pub struct BindingMatcher {
+    bindings: Vec<(MouseInput, bool)>,
+    double_click_time: u32,
+    /// Largest movement between the clicks of a double click
+    double_click_size: (i32, i32),
+    last_down: Option<(MouseButton, u32, i32, i32)>,
+    /// Buttons whose press was swallowed, so their release is swallowed too
+    swallowed: HashSet<MouseButton>,
+}
+
+impl BindingMatcher {
+    pub fn new(bindings: &[MouseBinding], double_click_time: u32, double_click_size: (i32, i32)) -> Self {
+        Self {
+            bindings: Self::inputs(bindings),
+            double_click_time,
+            double_click_size,
+            last_down: None,
+            swallowed: HashSet::new(),
+        }
+    }
+
+    /// Use the system's double-click time and distance
+    pub fn from_system(bindings: &[MouseBinding]) -> Self {
+        let (time, width, height) = unsafe {
+            (
+                GetDoubleClickTime(),
+                GetSystemMetrics(SM_CXDOUBLECLK),
+                GetSystemMetrics(SM_CYDOUBLECLK),
+            )
+        };
+        Self::new(bindings, time, (width / 2, height / 2))
+    }
+
+    fn inputs(bindings: &[MouseBinding]) -> Vec<(MouseInput, bool)> {
+        bindings
+            .iter()
+            .map(|binding| (binding.input, !binding.pass_through))
+            .collect()
+    }
+
+    /// Match against other bindings from now on. Buttons already swallowed
+    /// still have their release swallowed.
+    pub fn set_bindings(&mut self, bindings: &[MouseBinding]) {
+        self.bindings = Self::inputs(bindings);
+    }
+
+    fn find(&self, modifiers: Modifiers, trigger: MouseTrigger) -> Option<usize> {
+        self.bindings
+            .iter()
+            .position(|(input, _)| input.modifiers == modifiers && input.trigger == trigger)
+    }
+
+    /// Index of the binding the event triggers, if any, and whether to swallow
+    /// the event. A double click with no double-click binding counts as a
+    /// second click, and the first click of a double click always fires.
+    pub fn handle(&mut self, event: &MouseEvent) -> (Option<usize>, bool) {
+        let trigger = match event.kind {
+            MouseEventKind::Move => return (None, false),
+            MouseEventKind::Up(button) => return (None, self.swallowed.remove(&button)),
+            MouseEventKind::Down(button) => {
+                let double = self.last_down.is_some_and(|(last, time, x, y)| {
+                    last == button
+                        && event.time.wrapping_sub(time) <= self.double_click_time
+                        && (event.x - x).abs() <= self.double_click_size.0
+                        && (event.y - y).abs() <= self.double_click_size.1
+                });
+                self.last_down = if double { None } else { Some((button, event.time, event.x, event.y)) };
+
+                let matched = if double {
+                    self.find(event.modifiers, MouseTrigger::DoubleClick(button))
+                        .or_else(|| self.find(event.modifiers, MouseTrigger::Click(button)))
+                } else {
+                    self.find(event.modifiers, MouseTrigger::Click(button))
+                };
+                let swallow = matched.is_some_and(|index| self.bindings[index].1);
+                if swallow {
+                    self.swallowed.insert(button);
+                }
+                return (matched, swallow);
+            }
+            MouseEventKind::Wheel(delta) if delta > 0 => MouseTrigger::WheelUp,
+            MouseEventKind::Wheel(_) => MouseTrigger::WheelDown,
+            MouseEventKind::TiltWheel(delta) if delta > 0 => MouseTrigger::TiltRight,
+            MouseEventKind::TiltWheel(_) => MouseTrigger::TiltLeft,
+        };
+
+        let matched = self.find(event.modifiers, trigger);
+        (matched, matched.is_some_and(|index| self.bindings[index].1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CTRL: Modifiers = Modifiers { ctrl: true, alt: false, shift: false, win: false };
+    const NONE: Modifiers = Modifiers { ctrl: false, alt: false, shift: false, win: false };
+
+    fn binding(input: &str, pass_through: bool) -> MouseBinding {
+        serde_json::from_value(serde_json::json!({
+            "input": input,
+            "action": { "type": "screenshot" },
+            "pass_through": pass_through,
+        }))
+        .unwrap()
+    }
+
+    fn matcher(inputs: &[&str]) -> BindingMatcher {
+        let bindings: Vec<MouseBinding> = inputs.iter().map(|input| binding(input, false)).collect();
+        BindingMatcher::new(&bindings, 500, (4, 4))
+    }
+
+    fn event(kind: MouseEventKind, time: u32, x: i32, modifiers: Modifiers) -> MouseEvent {
+        MouseEvent { kind, x, y: 0, time, modifiers }
+    }
+
+    fn down(button: MouseButton, time: u32, x: i32) -> MouseEvent {
+        event(MouseEventKind::Down(button), time, x, NONE)
+    }
+
+    #[test]
+    fn mouse_inputs_parse_and_print() {
+        let input = MouseInput::parse(" shift + CTRL + X1_Click ").unwrap();
+        assert_eq!(input.trigger, MouseTrigger::Click(MouseButton::X1));
+        assert!(input.modifiers.ctrl && input.modifiers.shift && !input.modifiers.alt);
+        assert_eq!(input.to_string(), "Ctrl+Shift+x1_click");
+        assert_eq!(MouseInput::parse("middle_double_click").unwrap().trigger, MouseTrigger::DoubleClick(MouseButton::Middle));
+        assert_eq!(MouseInput::parse("Win+tilt_left").unwrap().to_string(), "Win+tilt_left");
+
+        assert!(MouseInput::parse("Ctrl").is_err());
+        assert!(MouseInput::parse("x1_click+x2_click").is_err());
+        assert!(MouseInput::parse("x3_click").is_err());
+    }
+
+    #[test]
+    fn double_clicks_need_the_same_button_inside_the_time_and_distance() {
+        let mut matcher = matcher(&["middle_click", "middle_double_click"]);
+        assert_eq!(matcher.handle(&down(MouseButton::Middle, 1000, 0)), (Some(0), true));
+        assert_eq!(matcher.handle(&down(MouseButton::Middle, 1500, 4)), (Some(1), true));
+        // A third click starts over rather than making another double click
+        assert_eq!(matcher.handle(&down(MouseButton::Middle, 1600, 4)), (Some(0), true));
+
+        // Too slow
+        assert_eq!(matcher.handle(&down(MouseButton::Middle, 2101, 4)), (Some(0), true));
+        // Too far
+        assert_eq!(matcher.handle(&down(MouseButton::Middle, 2200, 9)), (Some(0), true));
+        // Another button in between
+        matcher.handle(&down(MouseButton::X1, 2250, 9));
+        assert_eq!(matcher.handle(&down(MouseButton::Middle, 2300, 9)), (Some(0), true));
+
+        // The message clock wraps around
+        matcher.handle(&down(MouseButton::Middle, u32::MAX - 100, 0));
+        assert_eq!(matcher.handle(&down(MouseButton::Middle, 100, 0)), (Some(1), true));
+    }
+
+    #[test]
+    fn a_double_click_without_its_own_binding_is_a_second_click() {
+        let mut matcher = matcher(&["x2_click"]);
+        assert_eq!(matcher.handle(&down(MouseButton::X2, 0, 0)), (Some(0), true));
+        assert_eq!(matcher.handle(&down(MouseButton::X2, 100, 0)), (Some(0), true));
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly() {
+        let mut matcher = matcher(&["Ctrl+x1_click", "x1_click", "Ctrl+Shift+wheel_up"]);
+        let ctrl_shift = Modifiers { shift: true, ..CTRL };
+        let ctrl_alt = Modifiers { alt: true, ..CTRL };
+
+        assert_eq!(matcher.handle(&event(MouseEventKind::Down(MouseButton::X1), 0, 0, CTRL)).0, Some(0));
+        assert_eq!(matcher.handle(&event(MouseEventKind::Down(MouseButton::X1), 1000, 0, NONE)).0, Some(1));
+        assert_eq!(matcher.handle(&event(MouseEventKind::Down(MouseButton::X1), 2000, 0, ctrl_alt)), (None, false));
+        assert_eq!(matcher.handle(&event(MouseEventKind::Wheel(120), 3000, 0, ctrl_shift)), (Some(2), true));
+        assert_eq!(matcher.handle(&event(MouseEventKind::Wheel(120), 3100, 0, CTRL)), (None, false));
+    }
+
+    #[test]
+    fn swallowed_presses_swallow_their_release() {
+        let bindings = vec![binding("x2_click", true), binding("middle_click", false)];
+        let mut matcher = BindingMatcher::new(&bindings, 500, (4, 4));
+
+        assert_eq!(matcher.handle(&down(MouseButton::X2, 0, 0)), (Some(0), false));
+        assert_eq!(matcher.handle(&event(MouseEventKind::Up(MouseButton::X2), 10, 0, NONE)), (None, false));
+
+        assert_eq!(matcher.handle(&down(MouseButton::Middle, 1000, 0)), (Some(1), true));
+        // New bindings do not leave the button stuck down for applications
+        matcher.set_bindings(&[]);
+        assert_eq!(matcher.handle(&event(MouseEventKind::Up(MouseButton::Middle), 1010, 0, NONE)), (None, true));
+        assert_eq!(matcher.handle(&down(MouseButton::Middle, 3000, 0)), (None, false));
+        assert_eq!(matcher.handle(&event(MouseEventKind::Up(MouseButton::Middle), 3010, 0, NONE)), (None, false));
+    }
+
+    #[test]
+    fn side_buttons_wheel_and_tilt_match_their_own_bindings() {
+        let mut matcher = matcher(&["x1_click", "x2_click", "tilt_left", "tilt_right", "wheel_down"]);
+        assert_eq!(matcher.handle(&down(MouseButton::X1, 0, 0)).0, Some(0));
+        assert_eq!(matcher.handle(&down(MouseButton::X2, 10, 0)).0, Some(1));
+        assert_eq!(matcher.handle(&event(MouseEventKind::TiltWheel(-120), 20, 0, NONE)).0, Some(2));
+        assert_eq!(matcher.handle(&event(MouseEventKind::TiltWheel(120), 30, 0, NONE)).0, Some(3));
+        assert_eq!(matcher.handle(&event(MouseEventKind::Wheel(-120), 40, 0, NONE)).0, Some(4));
+        assert_eq!(matcher.handle(&event(MouseEventKind::Wheel(120), 50, 0, NONE)), (None, false));
+        assert_eq!(matcher.handle(&down(MouseButton::Middle, 60, 0)), (None, false));
+        assert_eq!(matcher.handle(&event(MouseEventKind::Move, 70, 0, NONE)), (None, false));
+    }
+}
@@ -0,0 +1,255 @@
+use super::hotkey;
+use std::cell::RefCell;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetAsyncKeyState, INPUT, INPUT_0, INPUT_MOUSE, MOUSEEVENTF_HWHEEL, MOUSEEVENTF_LEFTDOWN,
+    MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_RIGHTDOWN,
+    MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_WHEEL, MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, MOUSEINPUT,
+    MOUSE_EVENT_FLAGS, VK_CONTROL, VK_LWIN, VK_MENU, VK_RWIN, VK_SHIFT,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CallNextHookEx, GetMessageW, PostThreadMessageW, SetWindowsHookExW, UnhookWindowsHookEx,
+    HHOOK, LLMHF_INJECTED, MSG, MSLLHOOKSTRUCT, WH_MOUSE_LL, WM_LBUTTONDOWN, WM_LBUTTONUP,
+    WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_QUIT,
+    WM_RBUTTONDOWN, WM_RBUTTONUP, WM_XBUTTONDOWN, WM_XBUTTONUP,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    X1,
+    X2,
+}
+
+impl MouseButton {
+    pub const ALL: [MouseButton; 5] = [
+        MouseButton::Left,
+        MouseButton::Right,
+        MouseButton::Middle,
+        MouseButton::X1,
+        MouseButton::X2,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MouseButton::Left => "left",
+            MouseButton::Right => "right",
+            MouseButton::Middle => "middle",
+            MouseButton::X1 => "x1",
+            MouseButton::X2 => "x2",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|button| button.name().eq_ignore_ascii_case(name))
+    }
+}
+
+pub fn button_input(button: MouseButton, up: bool) -> INPUT {
+    let (flags, data) = match (button, up) {
+        (MouseButton::Left, false) => (MOUSEEVENTF_LEFTDOWN, 0),
+        (MouseButton::Left, true) => (MOUSEEVENTF_LEFTUP, 0),
+        (MouseButton::Right, false) => (MOUSEEVENTF_RIGHTDOWN, 0),
+        (MouseButton::Right, true) => (MOUSEEVENTF_RIGHTUP, 0),
+        (MouseButton::Middle, false) => (MOUSEEVENTF_MIDDLEDOWN, 0),
+        (MouseButton::Middle, true) => (MOUSEEVENTF_MIDDLEUP, 0),
+        (MouseButton::X1, false) => (MOUSEEVENTF_XDOWN, 1),
+        (MouseButton::X1, true) => (MOUSEEVENTF_XUP, 1),
+        (MouseButton::X2, false) => (MOUSEEVENTF_XDOWN, 2),
+        (MouseButton::X2, true) => (MOUSEEVENTF_XUP, 2),
+    };
+    mouse_input(flags, data)
+}
+
+fn mouse_input(flags: MOUSE_EVENT_FLAGS, data: u32) -> INPUT {
+    INPUT {
+        r#type: INPUT_MOUSE,
+        Anonymous: INPUT_0 {
+            mi: MOUSEINPUT {
+                dx: 0,
+                dy: 0,
+                mouseData: data,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    }
+}
+
+/// One notch is 120; positive is away from the user, or right when `horizontal`
+pub fn wheel_input(delta: i16, horizontal: bool) -> INPUT {
+    let flags = if horizontal { MOUSEEVENTF_HWHEEL } else { MOUSEEVENTF_WHEEL };
+    mouse_input(flags, delta as i32 as u32)
+}
+
+/// Synthesize a full click at the current pointer position. Injected input
+/// is ignored by the hook, so this never loops back.
+pub fn send_click(button: MouseButton) -> Result<()> {
+    hotkey::send_inputs(&[button_input(button, false), button_input(button, true)])
+}
+
+/// Keyboard modifiers held while a mouse event happened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub win: bool,
+}
+
+impl Modifiers {
+    pub fn current() -> Self {
+        let held = |vk: i32| unsafe { GetAsyncKeyState(vk) as u16 & 0x8000 != 0 };
+        Self {
+            ctrl: held(VK_CONTROL.0 as i32),
+            alt: held(VK_MENU.0 as i32),
+            shift: held(VK_SHIFT.0 as i32),
+            win: held(VK_LWIN.0 as i32) || held(VK_RWIN.0 as i32),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEventKind {
+    Down(MouseButton),
+    Up(MouseButton),
+    Move,
+    /// Vertical wheel; positive is away from the user
+    Wheel(i16),
+    /// Horizontal wheel or tilt; positive is to the right
+    TiltWheel(i16),
+}
+
+/// A physical mouse event seen by the hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub kind: MouseEventKind,
+    pub x: i32,
+    pub y: i32,
+    /// Milliseconds, from the system's message clock
+    pub time: u32,
+    /// Not read for moves, which are far too frequent for it
+    pub modifiers: Modifiers,
+}
+
+/// Returns true to swallow the event so no application sees it
+type HookCallback = Box<dyn FnMut(&MouseEvent) -> bool>;
+
+thread_local! {
+    // Low-level hooks are called on the thread that installed them
+    static CALLBACK: RefCell<Option<HookCallback>> = RefCell::new(None);
+}
+
+/// What a low-level mouse hook message means, if it is one we handle
+pub fn decode(message: u32, info: &MSLLHOOKSTRUCT) -> Option<MouseEventKind> {
+    let high_word = (info.mouseData >> 16) as u16;
+    let x_button = || match high_word {
+        1 => Some(MouseButton::X1),
+        2 => Some(MouseButton::X2),
+        _ => None,
+    };
+
+    Some(match message {
+        WM_MOUSEMOVE => MouseEventKind::Move,
+        WM_LBUTTONDOWN => MouseEventKind::Down(MouseButton::Left),
+        WM_LBUTTONUP => MouseEventKind::Up(MouseButton::Left),
+        WM_RBUTTONDOWN => MouseEventKind::Down(MouseButton::Right),
+        WM_RBUTTONUP => MouseEventKind::Up(MouseButton::Right),
+        WM_MBUTTONDOWN => MouseEventKind::Down(MouseButton::Middle),
+        WM_MBUTTONUP => MouseEventKind::Up(MouseButton::Middle),
+        WM_XBUTTONDOWN => MouseEventKind::Down(x_button()?),
+        WM_XBUTTONUP => MouseEventKind::Up(x_button()?),
+        WM_MOUSEWHEEL => MouseEventKind::Wheel(high_word as i16),
+        WM_MOUSEHWHEEL => MouseEventKind::TiltWheel(high_word as i16),
+        _ => return None,
+    })
+}
+
+unsafe extern "system" fn mouse_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code >= 0 {
+        let info = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+        // Input we or other tools synthesize is never re-interpreted
+        if info.flags & LLMHF_INJECTED == 0 {
+            if let Some(kind) = decode(wparam.0 as u32, info) {
+                let event = MouseEvent {
+                    kind,
+                    x: info.pt.x,
+                    y: info.pt.y,
+                    time: info.time,
+                    modifiers: if kind == MouseEventKind::Move { Modifiers::default() } else { Modifiers::current() },
+                };
+                let swallow = CALLBACK.with(|callback| match callback.try_borrow_mut() {
+                    Ok(mut callback) => callback.as_mut().is_some_and(|callback| callback(&event)),
+                    Err(_) => false,
+                });
+                if swallow {
+                    return LRESULT(1);
+                }
+            }
+        }
+    }
+    CallNextHookEx(HHOOK::default(), code, wparam, lparam)
+}
+
+/// A global low-level mouse hook on a dedicated message-loop thread. The
+/// callback sees every physical mouse event and decides whether to swallow
+/// it; Windows drops hooks that stall, so it must return quickly.
+pub struct MouseHook {
+    thread_id: u32,
+    handle: JoinHandle<()>,
+}
+
+impl MouseHook {
+    pub fn start<F>(on_event: F) -> Result<Self>
+    where
+        F: FnMut(&MouseEvent) -> bool + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<Result<u32, String>>();
+
+        let handle = thread::spawn(move || unsafe {
+            CALLBACK.with(|callback| *callback.borrow_mut() = Some(Box::new(on_event)));
+
+            let hook = match GetModuleHandleW(None)
+                .and_then(|module| SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_proc), module, 0))
+            {
+                Ok(hook) => hook,
+                Err(e) => {
+                    let _ = tx.send(Err(format!("Could not install mouse hook: {}", e)));
+                    return;
+                }
+            };
+            let _ = tx.send(Ok(GetCurrentThreadId()));
+
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, HWND::default(), 0, 0).as_bool() {}
+
+            let _ = UnhookWindowsHookEx(hook);
+            CALLBACK.with(|callback| *callback.borrow_mut() = None);
+        });
+
+        let thread_id = rx
+            .recv()
+            .map_err(|_| anyhow::anyhow!("Mouse hook thread exited unexpectedly"))?
+            .map_err(|e| anyhow::anyhow!(e))?;
+
+        Ok(Self { thread_id, handle })
+    }
+
+    pub fn stop(self) {
+        unsafe {
+            let _ = PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+        }
+        let _ = self.handle.join();
+    }
+}
@@ -0,0 +1,472 @@
+use super::actions::{self, Action};
+use super::hotkey::{self, KeyChord};
+use super::mouse_bindings::{MouseInput, MouseTrigger};
+use super::mouse_hook::{self, Modifiers, MouseButton, MouseEvent, MouseEventKind};
+use std::collections::HashSet;
+use std::sync::{mpsc, Mutex, Once};
+use std::thread;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Buttons and keys the output thread has pressed and not yet released.
+/// Kept globally so a panic anywhere can still release them. Nothing runs
+/// when the process is killed or crashes hard, though; then they stay held
+/// until pressed and released again.
+static PRESSED: Mutex<Vec<RemapTarget>> = Mutex::new(Vec::new());
+static RELEASE_ON_PANIC: Once = Once::new();
+
+/// What a remapped button becomes, written like `{"button": "middle"}` or
+/// `{"keys": "Ctrl+C"}`. It is held for as long as the button is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemapTarget {
+    Button(MouseButton),
+    Keys(KeyChord),
+}
+
+impl RemapTarget {
+    fn send(self, down: bool) -> Result<()> {
+        match self {
+            RemapTarget::Button(button) => hotkey::send_inputs(&[mouse_hook::button_input(button, !down)]),
+            RemapTarget::Keys(chord) => chord.send_half(down),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ButtonRemap {
+    pub from: MouseButton,
+    pub to: RemapTarget,
+}
+
+impl ButtonRemap {
+    pub fn validate(&self) -> Result<()> {
+        // Without a left button there is no way to click the settings back
+        if self.from == MouseButton::Left {
+            return Err(anyhow::anyhow!("The left button cannot be remapped"));
+        }
+        if self.to == RemapTarget::Button(self.from) {
+            return Err(anyhow::anyhow!("{} is remapped onto itself", self.from.name()));
+        }
+        Ok(())
+    }
+}
+
+/// An action for an input made while another button is held, like
+/// `right` + `wheel_up`. The held button's own click is replayed on release
+/// if no chord fired, so holding it to drag does not work.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MouseChord {
+    pub hold: MouseButton,
+    pub input: MouseInput,
+    #[serde(deserialize_with = "actions::deserialize_action")]
+    pub action: Action,
+}
+
+impl MouseChord {
+    pub fn validate(&self) -> Result<()> {
+        if self.hold == MouseButton::Left {
+            return Err(anyhow::anyhow!("Chords cannot be held on the left button"));
+        }
+        match self.input.trigger {
+            MouseTrigger::DoubleClick(_) => {
+                return Err(anyhow::anyhow!("Chords cannot use double clicks: {}", self.input));
+            }
+            MouseTrigger::Click(button) if button == self.hold => {
+                return Err(anyhow::anyhow!("Chord on {} needs a different second input", self.hold.name()));
+            }
+            _ => {}
+        }
+        self.action
+            .validate()
+            .map_err(|e| anyhow::anyhow!("Chord {}+{}: {}", self.hold.name(), self.input, e))
+    }
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WheelSettings {
+    /// Multiplies every wheel step
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    #[serde(default)]
+    pub invert: bool,
+    #[serde(default)]
+    pub invert_horizontal: bool,
+}
+
+impl Default for WheelSettings {
+    fn default() -> Self {
+        Self { speed: default_speed(), invert: false, invert_horizontal: false }
+    }
+}
+
+impl WheelSettings {
+    pub fn validate(&self) -> Result<()> {
+        if !(0.1..=10.0).contains(&self.speed) {
+            return Err(anyhow::anyhow!("Wheel speed must be between 0.1 and 10"));
+        }
+        Ok(())
+    }
+
+    /// The delta to send instead, or `None` to leave the event alone
+    fn adjust(&self, delta: i16, horizontal: bool) -> Option<i16> {
+        if *self == Self::default() {
+            return None;
+        }
+        let invert = if horizontal { self.invert_horizontal } else { self.invert };
+        let scaled = (delta as f64 * self.speed).round().clamp(i16::MIN as f64 + 1.0, i16::MAX as f64) as i16;
+        // Slow speeds still move at least a little
+        let scaled = if scaled == 0 { delta.signum() } else { scaled };
+        Some(if invert { -scaled } else { scaled })
+    }
+}
+
+/// Remaps, chords and wheel settings, globally or for a profile
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemapConfig {
+    pub remaps: Vec<ButtonRemap>,
+    pub chords: Vec<MouseChord>,
+    /// `None` in a profile keeps the global settings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wheel: Option<WheelSettings>,
+}
+
+impl RemapConfig {
+    pub fn validate(&self) -> Result<()> {
+        for (index, remap) in self.remaps.iter().enumerate() {
+            remap.validate()?;
+            if self.remaps[..index].iter().any(|earlier| earlier.from == remap.from) {
+                return Err(anyhow::anyhow!("{} is remapped more than once", remap.from.name()));
+            }
+        }
+        for chord in &self.chords {
+            chord.validate()?;
+            if self.remaps.iter().any(|remap| remap.from == chord.hold) {
+                return Err(anyhow::anyhow!("{} is both remapped and held for a chord", chord.hold.name()));
+            }
+        }
+        self.wheel.as_ref().map_or(Ok(()), WheelSettings::validate)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaps.is_empty() && self.chords.is_empty() && self.wheel.is_none_or(|wheel| wheel == WheelSettings::default())
+    }
+
+    /// A profile's settings over these: its remaps and chords replace those on
+    /// the same button and input, and its wheel settings replace these
+    pub fn overlay(&self, profile: &RemapConfig) -> RemapConfig {
+        let mut remaps = profile.remaps.clone();
+        remaps.extend(self.remaps.iter().filter(|remap| !profile.remaps.iter().any(|own| own.from == remap.from)));
+        let mut chords = profile.chords.clone();
+        chords.extend(
+            self.chords
+                .iter()
+                .filter(|chord| {
+                    !profile.chords.iter().any(|own| own.hold == chord.hold && own.input == chord.input)
+                })
+                .cloned(),
+        );
+        RemapConfig { remaps, chords, wheel: profile.wheel.or(self.wheel) }
+    }
+}
+
+/// Input for the output thread to synthesize
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Output {
+    Press(RemapTarget),
+    Release(RemapTarget),
+    Click(MouseButton),
+    Wheel(i16, bool),
+}
+
+/// Release every remapped button and key still held. Exiting the process
+/// skips the remapper's own cleanup, so the app calls this on exit and the
+/// panic hook calls it too. A killed process or a crash that aborts without
+/// unwinding gets neither, and leaves whatever was held pressed.
+pub fn release_all() {
+    let Ok(mut pressed) = PRESSED.lock() else {
+        return;
+    };
+    for target in pressed.drain(..).rev() {
+        if let Err(e) = target.send(false) {
+            log::warn!("Could not release remapped input: {}", e);
+        }
+    }
+}
+
+/// Synthesizes remapped input in order, away from the hook thread, where
+/// injecting would deadlock on the input queue. Releases anything still
+/// held when the remapper is dropped.
+fn run_output(outputs: mpsc::Receiver<Output>) {
+    for output in outputs {
+        let result = match output {
+            Output::Press(target) => {
+                let sent = target.send(true);
+                if sent.is_ok() {
+                    if let Ok(mut pressed) = PRESSED.lock() {
+                        pressed.push(target);
+                    }
+                }
+                sent
+            }
+            Output::Release(target) => {
+                if let Ok(mut pressed) = PRESSED.lock() {
+                    if let Some(index) = pressed.iter().rposition(|held| *held == target) {
+                        pressed.remove(index);
+                    }
+                }
+                target.send(false)
+            }
+            Output::Click(button) => mouse_hook::send_click(button),
+            Output::Wheel(delta, horizontal) => hotkey::send_inputs(&[mouse_hook::wheel_input(delta, horizontal)]),
+        };
+        if let Err(e) = result {
+            log::warn!("Could not send remapped input: {}", e);
+        }
+    }
+    release_all();
+}
+
+/// What the hook should do with an event
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemapOutcome {
+    /// Not remapped; bindings and applications see it
+    Pass,
+    Swallow,
+    /// A chord fired; the event is swallowed
+    Chord(Action),
+}
+
+/// Applies remaps, chords and wheel settings inside the mouse hook. Like the
+/// binding matcher it only keeps a little state and never blocks.
+pub struct Remapper {
+    config: RemapConfig,
+    /// What each physical button held down was turned into. Releases follow
+    /// this rather than the config, which may change while a button is held.
+    held: Vec<(MouseButton, RemapTarget)>,
+    /// Chord button held, and whether a chord fired while it was
+    chord_hold: Option<(MouseButton, bool)>,
+    /// Buttons pressed as part of a chord, whose release is swallowed too
+    swallowed: HashSet<MouseButton>,
+    output: mpsc::Sender<Output>,
+}
+
+impl Remapper {
+    pub fn start(config: RemapConfig) -> Self {
+        // Panics that abort rather than unwind still run the hook; crashes
+        // outside Rust, like an access violation, don't
+        RELEASE_ON_PANIC.call_once(|| {
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                release_all();
+                previous(info);
+            }));
+        });
+
+        let (output, outputs) = mpsc::channel();
+        thread::spawn(move || run_output(outputs));
+        Self {
+            config,
+            held: Vec::new(),
+            chord_hold: None,
+            swallowed: HashSet::new(),
+            output,
+        }
+    }
+
+    /// Use other settings from now on, e.g. for another profile
+    pub fn set_config(&mut self, config: RemapConfig) {
+        self.config = config;
+    }
+
+    fn send(&self, output: Output) {
+        let _ = self.output.send(output);
+    }
+
+    fn chord(&self, hold: MouseButton, modifiers: Modifiers, trigger: MouseTrigger) -> Option<Action> {
+        self.config
+            .chords
+            .iter()
+            .find(|chord| chord.hold == hold && chord.input.trigger == trigger && chord.input.modifiers == modifiers)
+            .map(|chord| chord.action.clone())
+    }
+
+    /// Handle buttons and, while a chord button is held, the wheel
+    pub fn handle(&mut self, event: &MouseEvent) -> RemapOutcome {
+        match event.kind {
+            MouseEventKind::Move => RemapOutcome::Pass,
+            MouseEventKind::Down(button) => {
+                if let Some(remap) = self.config.remaps.iter().find(|remap| remap.from == button) {
+                    self.held.push((button, remap.to));
+                    self.send(Output::Press(remap.to));
+                    return RemapOutcome::Swallow;
+                }
+                if let Some((hold, _)) = self.chord_hold {
+                    if let Some(action) = self.chord(hold, event.modifiers, MouseTrigger::Click(button)) {
+                        self.chord_hold = Some((hold, true));
+                        self.swallowed.insert(button);
+                        return RemapOutcome::Chord(action);
+                    }
+                    return RemapOutcome::Pass;
+                }
+                if self.config.chords.iter().any(|chord| chord.hold == button) {
+                    self.chord_hold = Some((button, false));
+                    return RemapOutcome::Swallow;
+                }
+                RemapOutcome::Pass
+            }
+            MouseEventKind::Up(button) => {
+                if let Some(index) = self.held.iter().position(|(held, _)| *held == button) {
+                    let (_, target) = self.held.remove(index);
+                    self.send(Output::Release(target));
+                    return RemapOutcome::Swallow;
+                }
+                if let Some((hold, fired)) = self.chord_hold {
+                    if hold == button {
+                        self.chord_hold = None;
+                        if !fired {
+                            self.send(Output::Click(button));
+                        }
+                        return RemapOutcome::Swallow;
+                    }
+                }
+                if self.swallowed.remove(&button) {
+                    return RemapOutcome::Swallow;
+                }
+                RemapOutcome::Pass
+            }
+            MouseEventKind::Wheel(delta) | MouseEventKind::TiltWheel(delta) => {
+                let Some((hold, _)) = self.chord_hold else {
+                    return RemapOutcome::Pass;
+                };
+                let trigger = match event.kind {
+                    MouseEventKind::Wheel(_) if delta > 0 => MouseTrigger::WheelUp,
+                    MouseEventKind::Wheel(_) => MouseTrigger::WheelDown,
+                    _ if delta > 0 => MouseTrigger::TiltRight,
+                    _ => MouseTrigger::TiltLeft,
+                };
+                match self.chord(hold, event.modifiers, trigger) {
+                    Some(action) => {
+                        self.chord_hold = Some((hold, true));
+                        RemapOutcome::Chord(action)
+                    }
+                    None => RemapOutcome::Pass,
+                }
+            }
+        }
+    }
+
+    /// Apply the wheel settings to a wheel event nothing else took. Returns
+    /// true if the event was replaced and should be swallowed.
+    pub fn adjust_wheel(&mut self, event: &MouseEvent) -> bool {
+        let wheel = self.config.wheel.unwrap_or_default();
+        let (delta, horizontal) = match event.kind {
+            MouseEventKind::Wheel(delta) => (delta, false),
+            MouseEventKind::TiltWheel(delta) => (delta, true),
+            _ => return false,
+        };
+        match wheel.adjust(delta, horizontal) {
+            Some(adjusted) => {
+                self.send(Output::Wheel(adjusted, horizontal));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::mouse_bindings::MouseInput;
+
+    /// A remapper whose output is collected instead of synthesized
+    fn remapper(config: RemapConfig) -> (Remapper, mpsc::Receiver<Output>) {
+        let (output, outputs) = mpsc::channel();
+        let remapper = Remapper { config, held: Vec::new(), chord_hold: None, swallowed: HashSet::new(), output };
+        (remapper, outputs)
+    }
+
+    fn event(kind: MouseEventKind) -> MouseEvent {
+        MouseEvent { kind, x: 0, y: 0, time: 0, modifiers: Modifiers::default() }
+    }
+
+    fn remap(from: MouseButton, to: RemapTarget) -> RemapConfig {
+        RemapConfig { remaps: vec![ButtonRemap { from, to }], ..RemapConfig::default() }
+    }
+
+    fn chord(hold: MouseButton, input: &str, action: Action) -> MouseChord {
+        MouseChord { hold, input: MouseInput::parse(input).unwrap(), action }
+    }
+
+    #[test]
+    fn release_follows_the_press_across_a_config_change() {
+        let copy = RemapTarget::Keys(KeyChord::parse("Ctrl+C").unwrap());
+        let (mut remapper, outputs) = remapper(remap(MouseButton::Middle, copy));
+
+        assert_eq!(remapper.handle(&event(MouseEventKind::Down(MouseButton::Middle))), RemapOutcome::Swallow);
+        // A profile switch while the button is down remaps it elsewhere
+        remapper.set_config(remap(MouseButton::Middle, RemapTarget::Button(MouseButton::Right)));
+        assert_eq!(remapper.handle(&event(MouseEventKind::Up(MouseButton::Middle))), RemapOutcome::Swallow);
+        assert_eq!(outputs.try_iter().collect::<Vec<_>>(), vec![Output::Press(copy), Output::Release(copy)]);
+
+        remapper.handle(&event(MouseEventKind::Down(MouseButton::Middle)));
+        assert_eq!(outputs.try_recv().unwrap(), Output::Press(RemapTarget::Button(MouseButton::Right)));
+
+        // Removing the remap mid-press still releases what was pressed
+        remapper.set_config(RemapConfig::default());
+        assert_eq!(remapper.handle(&event(MouseEventKind::Up(MouseButton::Middle))), RemapOutcome::Swallow);
+        assert_eq!(outputs.try_recv().unwrap(), Output::Release(RemapTarget::Button(MouseButton::Right)));
+        assert_eq!(remapper.handle(&event(MouseEventKind::Down(MouseButton::Middle))), RemapOutcome::Pass);
+    }
+
+    #[test]
+    fn chords_fire_and_swallow_their_inputs() {
+        let config = RemapConfig {
+            chords: vec![
+                chord(MouseButton::Right, "wheel_up", Action::ToggleTaskbar),
+                chord(MouseButton::Right, "middle_click", Action::OpenUrl { url: "https://example.com".to_string() }),
+            ],
+            ..RemapConfig::default()
+        };
+        let (mut remapper, outputs) = remapper(config);
+
+        assert_eq!(remapper.handle(&event(MouseEventKind::Down(MouseButton::Right))), RemapOutcome::Swallow);
+        assert_eq!(remapper.handle(&event(MouseEventKind::Wheel(120))), RemapOutcome::Chord(Action::ToggleTaskbar));
+        // No chord for this one, so it scrolls as usual
+        assert_eq!(remapper.handle(&event(MouseEventKind::Wheel(-120))), RemapOutcome::Pass);
+        assert_eq!(
+            remapper.handle(&event(MouseEventKind::Down(MouseButton::Middle))),
+            RemapOutcome::Chord(Action::OpenUrl { url: "https://example.com".to_string() })
+        );
+        assert_eq!(remapper.handle(&event(MouseEventKind::Up(MouseButton::Middle))), RemapOutcome::Swallow);
+        assert_eq!(remapper.handle(&event(MouseEventKind::Up(MouseButton::Right))), RemapOutcome::Swallow);
+        // A chord fired, so the right click is not replayed
+        assert!(outputs.try_recv().is_err());
+
+        // Outside the chord the wheel and middle button are left alone
+        assert_eq!(remapper.handle(&event(MouseEventKind::Wheel(120))), RemapOutcome::Pass);
+        assert_eq!(remapper.handle(&event(MouseEventKind::Down(MouseButton::Middle))), RemapOutcome::Pass);
+        assert_eq!(remapper.handle(&event(MouseEventKind::Up(MouseButton::Middle))), RemapOutcome::Pass);
+    }
+
+    #[test]
+    fn held_button_is_clicked_on_release_when_no_chord_fired() {
+        let config = RemapConfig {
+            chords: vec![chord(MouseButton::Right, "wheel_up", Action::ToggleTaskbar)],
+            ..RemapConfig::default()
+        };
+        let (mut remapper, outputs) = remapper(config);
+
+        assert_eq!(remapper.handle(&event(MouseEventKind::Down(MouseButton::Right))), RemapOutcome::Swallow);
+        assert_eq!(remapper.handle(&event(MouseEventKind::Move)), RemapOutcome::Pass);
+        assert!(outputs.try_recv().is_err());
+        assert_eq!(remapper.handle(&event(MouseEventKind::Up(MouseButton::Right))), RemapOutcome::Swallow);
+        assert_eq!(outputs.try_iter().collect::<Vec<_>>(), vec![Output::Click(MouseButton::Right)]);
+    }
+}
@@ -0,0 +1,202 @@
+use super::clipboard_history::ClipboardItem;
+use super::hotkey::{HotkeyListener, KeyChord};
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use anyhow::Result;
+
+use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, IsWindow, SetForegroundWindow};
+
+pub const DEFAULT_QUICK_PASTE_HOTKEY: &str = "Ctrl+Shift+V";
+pub const DEFAULT_QUICK_PASTE_LIMIT: usize = 9;
+
+/// Delay between handing focus back and injecting the paste, so the target
+/// window is active when the keystrokes arrive
+const FOCUS_SETTLE: Duration = Duration::from_millis(60);
+
+/// Callback that shows the picker window. Runs on the hotkey thread.
+pub type QuickPasteHandler = Arc<dyn Fn() + Send + Sync>;
+
+/// Global-hotkey picker for pasting recent clipboard items into the window
+/// that was focused when the hotkey was pressed
+pub struct QuickPaste {
+    hotkey: Option<KeyChord>,
+    limit: usize,
+    handler: Option<QuickPasteHandler>,
+    listener: Option<HotkeyListener>,
+    target: Arc<AtomicIsize>,
+}
+
+impl QuickPaste {
+    pub fn new() -> Self {
+        Self {
+            hotkey: KeyChord::parse(DEFAULT_QUICK_PASTE_HOTKEY).ok(),
+            limit: DEFAULT_QUICK_PASTE_LIMIT,
+            handler: None,
+            listener: None,
+            target: Arc::new(AtomicIsize::new(0)),
+        }
+    }
+
+    pub fn hotkey(&self) -> Option<KeyChord> {
+        self.hotkey
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.max(1);
+    }
+
+    pub fn set_handler(&mut self, handler: QuickPasteHandler) {
+        self.handler = Some(handler);
+    }
+
+    /// Change the hotkey (`None` turns quick paste off), re-registering if running
+    pub fn set_hotkey(&mut self, hotkey: Option<KeyChord>) -> Result<()> {
+        if self.hotkey == hotkey {
+            return Ok(());
+        }
+        let running = self.listener.is_some();
+        self.stop();
+        self.hotkey = hotkey;
+        if running {
+            self.start()?;
+        }
+        Ok(())
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        if self.listener.is_some() {
+            return Ok(());
+        }
+        let (Some(hotkey), Some(handler)) = (self.hotkey, self.handler.clone()) else {
+            return Ok(());
+        };
+
+        let target = self.target.clone();
+        self.listener = Some(HotkeyListener::start(vec![hotkey], move |_| {
+            remember_target(&target);
+            handler();
+        })?);
+
+        log::info!("Quick paste hotkey registered: {}", hotkey);
+        Ok(())
+    }
+
+    /// Show the picker without the hotkey, e.g. from a bound action
+    pub fn open(&self) -> Result<()> {
+        let handler = self
+            .handler
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Quick paste is not available"))?;
+        remember_target(&self.target);
+        handler();
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(listener) = self.listener.take() {
+            listener.stop();
+            log::info!("Quick paste hotkey unregistered");
+        }
+    }
+
+    /// Give focus back to the window that was active when the picker opened
+    pub fn restore_focus(&self) -> Result<()> {
+        let raw = self.target.swap(0, Ordering::SeqCst);
+        if raw == 0 {
+            return Err(anyhow::anyhow!("No quick paste target window"));
+        }
+
+        unsafe {
+            let hwnd = HWND(raw as *mut _);
+            if !IsWindow(hwnd).as_bool() {
+                return Err(anyhow::anyhow!("Quick paste target window is gone"));
+            }
+            if !SetForegroundWindow(hwnd).as_bool() {
+                return Err(anyhow::anyhow!("Could not refocus quick paste target window"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Refocus the target window and paste the clipboard into it with Ctrl+V
+    pub fn paste_into_target(&self) -> Result<()> {
+        self.restore_focus()?;
+        thread::sleep(FOCUS_SETTLE);
+        KeyChord::parse("Ctrl+V")?.send()
+    }
+}
+
+/// Remember where to paste before the picker steals focus
+fn remember_target(target: &AtomicIsize) {
+    let foreground = unsafe { GetForegroundWindow() };
+    target.store(foreground.0 as isize, Ordering::SeqCst);
+}
+
+/// Order items by fuzzy match against `query` (best first, ties keep history
+/// order) and keep the first `limit`. An empty query returns the most recent.
+/// Positions in the result are what the picker's number keys select.
+pub fn rank_items(items: &[ClipboardItem], query: &str, limit: usize) -> Vec<ClipboardItem> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return items.iter().take(limit).cloned().collect();
+    }
+
+    let mut scored: Vec<(i64, usize)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| {
+            let best = std::iter::once(item.content.as_str())
+                .chain(item.abbreviation.as_deref())
+                .filter_map(|text| fuzzy_score(&query, &text.to_lowercase()))
+                .max()?;
+            Some((best, index))
+        })
+        .collect();
+    scored.sort_by_key(|&(score, index)| (std::cmp::Reverse(score), index));
+
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, index)| items[index].clone())
+        .collect()
+}
+
+/// Subsequence match score, or `None` if `query` is not a subsequence of `text`.
+/// Consecutive matches and matches at word starts score higher; gaps cost a little.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let mut score = 0i64;
+    let mut text_chars = text.chars().enumerate();
+    let mut previous_match: Option<usize> = None;
+    let mut previous_char: Option<char> = None;
+
+    for q in query.chars() {
+        loop {
+            let (position, c) = text_chars.next()?;
+            let at_word_start = previous_char.is_none_or(|p| !p.is_alphanumeric());
+            previous_char = Some(c);
+
+            if c == q {
+                score += 1;
+                if at_word_start {
+                    score += 8;
+                }
+                match previous_match {
+                    Some(last) if last + 1 == position => score += 5,
+                    Some(last) => score -= ((position - last - 1) as i64).min(5),
+                    None => score -= (position as i64).min(10),
+                }
+                previous_match = Some(position);
+                break;
+            }
+        }
+    }
+
+    Some(score)
+}
@@ -0,0 +1,401 @@
+use super::actions;
+use super::annotations::{self, Annotation};
+use super::screenshots::{self, CaptureTarget, CapturedImage, ScreenshotOptions};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use anyhow::Result;
+use arboard::Clipboard;
+use serde::{Deserialize, Serialize};
+
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+/// Replaced with the quoted path of the saved image in `run_command` steps
+const PATH_PLACEHOLDER: &str = "{path}";
+
+/// A step run after a capture. Steps run in order, so an `annotate` before
+/// `save` ends up in the saved file. Steps that need a file save the image
+/// first if no `save` step has yet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PostAction {
+    Annotate {
+        shapes: Vec<Annotation>,
+    },
+    CopyToClipboard,
+    Save,
+    /// Add the saved file to the clipboard history as an image item
+    AddToHistory,
+    /// Run through `cmd /C`; `{path}` is replaced with the quoted file path,
+    /// which is appended when the command has no placeholder
+    RunCommand {
+        command: String,
+    },
+    /// POST the PNG to an HTTP endpoint as `image/png`
+    Upload {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        /// Copy the response to the clipboard, for endpoints that answer
+        /// with the uploaded image's URL
+        #[serde(default)]
+        copy_response: bool,
+    },
+}
+
+impl PostAction {
+    fn name(&self) -> &'static str {
+        match self {
+            PostAction::Annotate { .. } => "annotate",
+            PostAction::CopyToClipboard => "copy_to_clipboard",
+            PostAction::Save => "save",
+            PostAction::AddToHistory => "add_to_history",
+            PostAction::RunCommand { .. } => "run_command",
+            PostAction::Upload { .. } => "upload",
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            PostAction::Annotate { shapes } => shapes.iter().try_for_each(Annotation::validate),
+            PostAction::CopyToClipboard | PostAction::Save | PostAction::AddToHistory => Ok(()),
+            PostAction::RunCommand { command } => {
+                if command.trim().is_empty() {
+                    return Err(anyhow::anyhow!("Screenshot command must not be empty"));
+                }
+                Ok(())
+            }
+            PostAction::Upload { url, headers, .. } => {
+                parse_http_url(url)?;
+                for (name, value) in headers {
+                    let invalid = |text: &str| text.contains(['\r', '\n']);
+                    if name.trim().is_empty() || name.contains(':') || invalid(name) || invalid(value) {
+                        return Err(anyhow::anyhow!("Invalid upload header: {}", name));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// What a capture goes through when no binding gives its own steps
+pub fn default_pipeline() -> Vec<PostAction> {
+    vec![PostAction::Save]
+}
+
+pub fn validate_pipeline(steps: &[PostAction]) -> Result<()> {
+    steps.iter().try_for_each(PostAction::validate)
+}
+
+/// Adds a saved image to the clipboard history
+pub type HistorySink<'a> = &'a dyn Fn(&Path) -> Result<()>;
+
+/// The image as it goes through the steps, and where it was saved
+struct PipelineRun<'a> {
+    image: CapturedImage,
+    options: &'a ScreenshotOptions,
+    saved: Option<PathBuf>,
+    /// Changed since it was saved
+    dirty: bool,
+}
+
+impl PipelineRun<'_> {
+    fn ensure_saved(&mut self) -> Result<PathBuf> {
+        match &self.saved {
+            Some(path) if !self.dirty => Ok(path.clone()),
+            Some(path) => {
+                fs::write(path, screenshots::encode_png(&self.image)?)?;
+                self.dirty = false;
+                Ok(path.clone())
+            }
+            None => {
+                let path = screenshots::save_png(&self.image, self.options)?;
+                self.saved = Some(path.clone());
+                self.dirty = false;
+                Ok(path)
+            }
+        }
+    }
+
+    fn run_step(&mut self, step: &PostAction, history: HistorySink) -> Result<()> {
+        match step {
+            PostAction::Annotate { shapes } => {
+                annotations::annotate(&mut self.image, shapes)?;
+                self.dirty = true;
+            }
+            PostAction::CopyToClipboard => screenshots::copy_image_to_clipboard(&self.image)?,
+            PostAction::Save => {
+                self.ensure_saved()?;
+            }
+            PostAction::AddToHistory => history(&self.ensure_saved()?)?,
+            PostAction::RunCommand { command } => {
+                let path = self.ensure_saved()?;
+                let quoted = format!("\"{}\"", path.display());
+                let command = if command.contains(PATH_PLACEHOLDER) {
+                    command.replace(PATH_PLACEHOLDER, &quoted)
+                } else {
+                    format!("{} {}", command, quoted)
+                };
+                actions::run_command(&command, None)?;
+            }
+            PostAction::Upload { url, headers, copy_response } => {
+                let response = upload_png(url, headers, &screenshots::encode_png(&self.image)?)?;
+                log::info!("Uploaded screenshot to {}", url);
+                if *copy_response && !response.is_empty() {
+                    Clipboard::new()?.set_text(response)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Put a capture through the steps in order, stopping at the first that
+/// fails. Returns where it was saved, if any step saved it.
+pub fn run_pipeline(
+    image: CapturedImage,
+    steps: &[PostAction],
+    options: &ScreenshotOptions,
+    history: HistorySink,
+) -> Result<Option<PathBuf>> {
+    validate_pipeline(steps)?;
+    let mut run = PipelineRun { image, options, saved: None, dirty: false };
+    for (index, step) in steps.iter().enumerate() {
+        run.run_step(step, history)
+            .map_err(|e| anyhow::anyhow!("Screenshot step {} ({}) failed: {}", index + 1, step.name(), e))?;
+    }
+    // A later annotation still reaches the file the earlier steps saved
+    if run.saved.is_some() && run.dirty {
+        run.ensure_saved()?;
+    }
+    Ok(run.saved)
+}
+
+/// Capture a target and put it through the steps. Returns the saved path,
+/// or `None` if nothing was saved or the user cancelled the region.
+pub fn take_screenshot(
+    target: &CaptureTarget,
+    steps: &[PostAction],
+    options: &ScreenshotOptions,
+    history: HistorySink,
+) -> Result<Option<PathBuf>> {
+    let Some(image) = screenshots::capture(target)? else {
+        log::info!("Screenshot cancelled");
+        return Ok(None);
+    };
+    let path = run_pipeline(image, steps, options, history)?;
+    if let Some(path) = &path {
+        log::info!("Screenshot saved to {}", path.display());
+    }
+    Ok(path)
+}
+
+/// Draw shapes onto a saved PNG in place
+pub fn annotate_file(path: &Path, shapes: &[Annotation]) -> Result<()> {
+    let mut image = screenshots::load_png(path)?;
+    annotations::annotate(&mut image, shapes)?;
+    fs::write(path, screenshots::encode_png(&image)?)?;
+    Ok(())
+}
+
+/// Host, port and path of a plain `http://` URL. There is no TLS client,
+/// so uploads go to local or proxied endpoints.
+fn parse_http_url(url: &str) -> Result<(String, u16, String)> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow::anyhow!("Upload URL must start with http://: {}", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    // IPv6 literals are bracketed, `[::1]:8080`; the host comes back without brackets
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(bracketed) => {
+            let (host, after) = bracketed
+                .split_once(']')
+                .ok_or_else(|| anyhow::anyhow!("Unclosed IPv6 address in {}", url))?;
+            match after {
+                "" => (host, None),
+                _ => (host, Some(after.strip_prefix(':').ok_or_else(|| anyhow::anyhow!("Invalid port in {}", url))?)),
+            }
+        }
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse().map_err(|_| anyhow::anyhow!("Invalid port in {}", url))?,
+        None => 80,
+    };
+    if host.is_empty() {
+        return Err(anyhow::anyhow!("Upload URL has no host: {}", url));
+    }
+    Ok((host.to_string(), port, path.to_string()))
+}
+
+/// Body of a chunked response
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|pair| pair == b"\r\n")
+            .ok_or_else(|| anyhow::anyhow!("Malformed chunked response"))?;
+        let size_text = String::from_utf8_lossy(&body[..line_end]);
+        let size = usize::from_str_radix(size_text.split(';').next().unwrap_or("").trim(), 16)
+            .map_err(|_| anyhow::anyhow!("Malformed chunk size"))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(out);
+        }
+        if body.len() < size {
+            return Err(anyhow::anyhow!("Chunked response ended early"));
+        }
+        out.extend_from_slice(&body[..size]);
+        body = body.get(size + 2..).unwrap_or_default();
+    }
+}
+
+/// POST a PNG and return the response body, trimmed
+pub fn upload_png(url: &str, headers: &HashMap<String, String>, png: &[u8]) -> Result<String> {
+    let (host, port, path) = parse_http_url(url)?;
+    let addr = (host.as_str(), port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Could not resolve {}", host))?;
+    let mut stream = TcpStream::connect_timeout(&addr, UPLOAD_TIMEOUT)?;
+    stream.set_read_timeout(Some(UPLOAD_TIMEOUT))?;
+    stream.set_write_timeout(Some(UPLOAD_TIMEOUT))?;
+
+    let host_header = if host.contains(':') { format!("[{}]", host) } else { host };
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: image/png\r\nContent-Length: {}\r\nConnection: close\r\n",
+        path,
+        host_header,
+        port,
+        png.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name.trim(), value.trim()));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+    stream.write_all(png)?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| anyhow::anyhow!("Malformed HTTP response"))?;
+    let head = String::from_utf8_lossy(&response[..header_end]).to_string();
+    let body = &response[header_end + 4..];
+
+    let status_line = head.lines().next().unwrap_or_default();
+    let status: u16 = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| anyhow::anyhow!("Malformed HTTP status: {}", status_line))?;
+    if !(200..300).contains(&status) {
+        return Err(anyhow::anyhow!("Upload rejected: {}", status_line));
+    }
+
+    let chunked = head.lines().any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("transfer-encoding") && value.trim().eq_ignore_ascii_case("chunked")
+        })
+    });
+    let body = if chunked { decode_chunked(body)? } else { body.to_vec() };
+    Ok(String::from_utf8_lossy(&body).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serves one request on a loopback port with `reply`, and hands back
+    /// the URL to post to and the request as it arrived
+    fn serve_once(reply: &'static str) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/upload", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            // Headers, then as many body bytes as they announced
+            loop {
+                let read = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(header_end) = text.find("\r\n\r\n") {
+                    let length: usize = text[..header_end]
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Content-Length: "))
+                        .and_then(|length| length.parse().ok())
+                        .unwrap_or(0);
+                    if request.len() >= header_end + 4 + length || read == 0 {
+                        break;
+                    }
+                }
+            }
+            stream.write_all(reply.as_bytes()).unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn urls_are_split_into_host_port_and_path() {
+        let parse = |url| parse_http_url(url).unwrap();
+        assert_eq!(parse("http://example.com"), ("example.com".to_string(), 80, "/".to_string()));
+        assert_eq!(parse("http://example.com:8080/up?x=1"), ("example.com".to_string(), 8080, "/up?x=1".to_string()));
+        assert_eq!(parse("http://[::1]:8080/up"), ("::1".to_string(), 8080, "/up".to_string()));
+        assert_eq!(parse("http://[fe80::1]"), ("fe80::1".to_string(), 80, "/".to_string()));
+
+        assert!(parse_http_url("https://example.com").is_err());
+        assert!(parse_http_url("http://:80/").is_err());
+        assert!(parse_http_url("http://example.com:port/").is_err());
+        assert!(parse_http_url("http://[::1:8080/").is_err());
+        assert!(parse_http_url("http://[::1]8080/").is_err());
+    }
+
+    #[test]
+    fn upload_returns_a_sized_body() {
+        let (url, server) = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 27\r\n\r\nhttps://img.example/a.png\r\n");
+        let headers = HashMap::from([("Authorization".to_string(), "Bearer token".to_string())]);
+        let link = upload_png(&url, &headers, b"\x89PNG fake").unwrap();
+        assert_eq!(link, "https://img.example/a.png");
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /upload HTTP/1.1\r\n"));
+        assert!(request.contains("Content-Type: image/png\r\n"));
+        assert!(request.contains("Content-Length: 9\r\n"));
+        assert!(request.contains("Authorization: Bearer token\r\n"));
+        assert!(request.ends_with("\r\n\r\n\u{fffd}PNG fake"));
+    }
+
+    #[test]
+    fn upload_decodes_a_chunked_body() {
+        let (url, server) = serve_once(
+            "HTTP/1.1 201 Created\r\nTransfer-Encoding: chunked\r\n\r\n8\r\nhttps://\r\n11;ext=1\r\nimg.example/b.png\r\n0\r\n\r\n",
+        );
+        let link = upload_png(&url, &HashMap::new(), b"png").unwrap();
+        assert_eq!(link, "https://img.example/b.png");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn upload_fails_on_an_error_status() {
+        let (url, server) = serve_once("HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n");
+        let error = upload_png(&url, &HashMap::new(), b"png").unwrap_err();
+        assert!(error.to_string().contains("403 Forbidden"));
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,496 @@
+use super::layout_engine::Rect;
+use super::monitors;
+use super::screenshot_pipeline::{self, PostAction};
+use super::snippets::{self, ExpansionContext};
+use super::window_ops;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use anyhow::Result;
+use arboard::{Clipboard, ImageData};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use windows::core::w;
+use windows::Win32::Foundation::{COLORREF, HWND, LPARAM, LRESULT, RECT, WPARAM};
+use windows::Win32::Graphics::Gdi::{
+    BeginPaint, BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, CreateSolidBrush, DeleteDC,
+    DeleteObject, EndPaint, FillRect, FrameRect, GetDC, GetDIBits, InvalidateRect, ReleaseDC,
+    SelectObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, CAPTUREBLT, DIB_RGB_COLORS, PAINTSTRUCT,
+    SRCCOPY,
+};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::Input::KeyboardAndMouse::{ReleaseCapture, SetCapture, SetFocus, VK_ESCAPE};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageW,
+    GetSystemMetrics, LoadCursorW, PostQuitMessage, RegisterClassW, SetForegroundWindow,
+    SetLayeredWindowAttributes, ShowWindow, TranslateMessage, IDC_CROSS, LWA_ALPHA, LWA_COLORKEY,
+    MSG, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN, SW_SHOW,
+    WM_ERASEBKGND, WM_KEYDOWN, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MOUSEMOVE, WM_PAINT,
+    WM_RBUTTONDOWN, WNDCLASSW, WS_EX_LAYERED, WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_POPUP,
+};
+
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "Screenshot {date} {time}";
+/// Let the compositor drop the selection overlay before the screen is read
+const OVERLAY_CLEAR: Duration = Duration::from_millis(150);
+const OVERLAY_ALPHA: u8 = 110;
+/// Painted in this colour means fully transparent
+const TRANSPARENT_KEY: COLORREF = COLORREF(0x00FF00FF);
+const SHADE_COLOR: COLORREF = COLORREF(0x00000000);
+const BORDER_COLOR: COLORREF = COLORREF(0x00D77800);
+/// Selections smaller than this on either side count as a cancelled click
+const MIN_REGION_SIZE: i32 = 4;
+
+/// What part of the screen to capture
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum CaptureTarget {
+    /// Every monitor, as one image
+    #[default]
+    FullScreen,
+    /// One monitor by id, like `\\.\DISPLAY1`; the primary one when unset
+    Monitor {
+        #[serde(default)]
+        id: Option<String>,
+    },
+    ActiveWindow,
+    /// A rectangle the user drags out on screen
+    Region,
+}
+
+/// Where and how captures are saved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScreenshotOptions {
+    /// Folder for saved images; the user's Pictures\WinShaper when unset
+    pub folder: Option<String>,
+    /// File name without extension; takes the `{date}`, `{time}` and
+    /// `{uuid}` placeholders of snippets
+    pub filename_template: String,
+    /// Steps for captures whose trigger does not give its own
+    pub pipeline: Vec<PostAction>,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        Self {
+            folder: None,
+            filename_template: DEFAULT_FILENAME_TEMPLATE.to_string(),
+            pipeline: screenshot_pipeline::default_pipeline(),
+        }
+    }
+}
+
+impl ScreenshotOptions {
+    pub fn validate(&self) -> Result<()> {
+        if self.filename_template.trim().is_empty() {
+            return Err(anyhow::anyhow!("Screenshot file name template must not be empty"));
+        }
+        if !snippets::required_inputs(&self.filename_template).is_empty() {
+            return Err(anyhow::anyhow!("Screenshot file names cannot use {{input}} placeholders"));
+        }
+        snippets::validate(&self.filename_template)?;
+        screenshot_pipeline::validate_pipeline(&self.pipeline)
+    }
+
+    fn folder(&self) -> Result<PathBuf> {
+        match &self.folder {
+            Some(folder) => Ok(PathBuf::from(folder)),
+            None => dirs::picture_dir()
+                .map(|pictures| pictures.join("WinShaper"))
+                .ok_or_else(|| anyhow::anyhow!("Could not find the Pictures folder")),
+        }
+    }
+}
+
+/// Pixels of a capture, row by row from the top, four bytes per pixel
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedImage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl CapturedImage {
+    pub fn to_image_data(&self) -> ImageData<'_> {
+        ImageData {
+            width: self.width as usize,
+            height: self.height as usize,
+            bytes: Cow::Borrowed(&self.rgba),
+        }
+    }
+}
+
+/// Copy a rectangle of the screen, in physical pixels
+pub fn capture_rect(rect: Rect) -> Result<CapturedImage> {
+    if rect.width <= 0 || rect.height <= 0 {
+        return Err(anyhow::anyhow!("Nothing to capture in an empty rectangle"));
+    }
+
+    let mut info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: rect.width,
+            // Negative height asks for rows top to bottom
+            biHeight: -rect.height,
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut pixels = vec![0u8; rect.width as usize * rect.height as usize * 4];
+
+    unsafe {
+        let screen = GetDC(HWND::default());
+        let memory = CreateCompatibleDC(screen);
+        let bitmap = CreateCompatibleBitmap(screen, rect.width, rect.height);
+        let previous = SelectObject(memory, bitmap);
+
+        // CAPTUREBLT includes layered windows such as tooltips and menus
+        let copied = BitBlt(memory, 0, 0, rect.width, rect.height, screen, rect.x, rect.y, SRCCOPY | CAPTUREBLT);
+        SelectObject(memory, previous);
+        let lines = GetDIBits(
+            memory,
+            bitmap,
+            0,
+            rect.height as u32,
+            Some(pixels.as_mut_ptr() as *mut _),
+            &mut info,
+            DIB_RGB_COLORS,
+        );
+
+        let _ = DeleteObject(bitmap);
+        let _ = DeleteDC(memory);
+        ReleaseDC(HWND::default(), screen);
+
+        copied.map_err(|e| anyhow::anyhow!("Could not copy the screen: {}", e))?;
+        if lines != rect.height {
+            return Err(anyhow::anyhow!("Could not read the captured pixels"));
+        }
+    }
+
+    // GDI gives BGRX; the alpha byte is undefined
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+        pixel[3] = 255;
+    }
+
+    Ok(CapturedImage { width: rect.width as u32, height: rect.height as u32, rgba: pixels })
+}
+
+fn virtual_screen() -> Rect {
+    unsafe {
+        Rect {
+            x: GetSystemMetrics(SM_XVIRTUALSCREEN),
+            y: GetSystemMetrics(SM_YVIRTUALSCREEN),
+            width: GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            height: GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        }
+    }
+}
+
+/// Screen rectangle for a target, or `None` if the user cancelled the region
+pub fn target_rect(target: &CaptureTarget) -> Result<Option<Rect>> {
+    match target {
+        CaptureTarget::FullScreen => Ok(Some(virtual_screen())),
+        CaptureTarget::Monitor { id } => {
+            let monitors = monitors::enumerate_monitors()?;
+            let monitor = match id {
+                Some(id) => monitors.iter().find(|monitor| &monitor.id == id),
+                None => monitors.iter().find(|monitor| monitor.primary),
+            };
+            monitor
+                .map(|monitor| Some(monitor.bounds))
+                .ok_or_else(|| anyhow::anyhow!("No monitor {}", id.as_deref().unwrap_or("marked primary")))
+        }
+        CaptureTarget::ActiveWindow => {
+            let hwnd = window_ops::foreground_window()?;
+            Ok(Some(window_ops::visible_rect(hwnd)?))
+        }
+        CaptureTarget::Region => select_region(),
+    }
+}
+
+/// Capture a target, or `None` if the user cancelled the region
+pub fn capture(target: &CaptureTarget) -> Result<Option<CapturedImage>> {
+    match target_rect(target)? {
+        Some(rect) => capture_rect(rect).map(Some),
+        None => Ok(None),
+    }
+}
+
+pub fn encode_png(image: &CapturedImage) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.rgba)?;
+    writer.finish()?;
+    Ok(out)
+}
+
+/// Read a PNG back, e.g. to annotate it or put it on the clipboard
+pub fn load_png(path: &Path) -> Result<CapturedImage> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    // Palettes, grey and odd bit depths all come out as 8-bit channels
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer)?;
+    buffer.truncate(frame.buffer_size());
+
+    let rgba = match frame.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return Err(anyhow::anyhow!("Unsupported PNG colour type in {}", path.display())),
+    };
+    Ok(CapturedImage { width: frame.width, height: frame.height, rgba })
+}
+
+pub fn copy_image_to_clipboard(image: &CapturedImage) -> Result<()> {
+    Clipboard::new()?.set_image(image.to_image_data())?;
+    Ok(())
+}
+
+/// File name from the template, with characters Windows rejects replaced
+pub fn expand_filename(template: &str) -> Result<String> {
+    let inputs = HashMap::new();
+    let context = ExpansionContext { now: Local::now(), clipboard: None, inputs: &inputs };
+    let name: String = snippets::expand(template, &context)?
+        .chars()
+        .map(|c| if c.is_control() || "<>:\"/\\|?*".contains(c) { '-' } else { c })
+        .collect();
+    let name = name.trim().trim_end_matches('.').to_string();
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("Screenshot file name template produced an empty name"));
+    }
+    Ok(name)
+}
+
+/// First `name.png` in `folder` that does not exist yet, adding ` (2)` and so on
+fn unused_path(folder: &Path, name: &str) -> PathBuf {
+    let mut path = folder.join(format!("{}.png", name));
+    let mut counter = 2;
+    while path.exists() {
+        path = folder.join(format!("{} ({}).png", name, counter));
+        counter += 1;
+    }
+    path
+}
+
+/// Save a capture as PNG following the options, and return its path
+pub fn save_png(image: &CapturedImage, options: &ScreenshotOptions) -> Result<PathBuf> {
+    let folder = options.folder()?;
+    fs::create_dir_all(&folder)?;
+    let path = unused_path(&folder, &expand_filename(&options.filename_template)?);
+    fs::write(&path, encode_png(image)?)?;
+    Ok(path)
+}
+
+struct RegionView {
+    origin: (i32, i32),
+    anchor: Option<(i32, i32)>,
+    current: (i32, i32),
+    selected: Option<Rect>,
+}
+
+impl RegionView {
+    /// Selection so far, in screen coordinates
+    fn rect(&self) -> Option<Rect> {
+        let (ax, ay) = self.anchor?;
+        let (cx, cy) = self.current;
+        Some(Rect {
+            x: ax.min(cx) + self.origin.0,
+            y: ay.min(cy) + self.origin.1,
+            width: (ax - cx).abs(),
+            height: (ay - cy).abs(),
+        })
+    }
+}
+
+thread_local! {
+    static REGION: RefCell<Option<RegionView>> = const { RefCell::new(None) };
+}
+
+fn client_point(lparam: LPARAM) -> (i32, i32) {
+    ((lparam.0 & 0xFFFF) as i16 as i32, ((lparam.0 >> 16) & 0xFFFF) as i16 as i32)
+}
+
+unsafe fn paint_region(hwnd: HWND) {
+    let mut ps = PAINTSTRUCT::default();
+    let hdc = BeginPaint(hwnd, &mut ps);
+
+    let shade = CreateSolidBrush(SHADE_COLOR);
+    let hole = CreateSolidBrush(TRANSPARENT_KEY);
+    let border = CreateSolidBrush(BORDER_COLOR);
+
+    FillRect(hdc, &ps.rcPaint, shade);
+    REGION.with(|view| {
+        if let Some(view) = view.borrow().as_ref() {
+            if let Some(rect) = view.rect() {
+                let client = RECT {
+                    left: rect.x - view.origin.0,
+                    top: rect.y - view.origin.1,
+                    right: rect.right() - view.origin.0,
+                    bottom: rect.bottom() - view.origin.1,
+                };
+                FillRect(hdc, &client, hole);
+                FrameRect(hdc, &client, border);
+            }
+        }
+    });
+
+    for brush in [shade, hole, border] {
+        let _ = DeleteObject(brush);
+    }
+    let _ = EndPaint(hwnd, &ps);
+}
+
+unsafe extern "system" fn region_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_LBUTTONDOWN => {
+            let point = client_point(lparam);
+            REGION.with(|view| {
+                if let Some(view) = view.borrow_mut().as_mut() {
+                    view.anchor = Some(point);
+                    view.current = point;
+                }
+            });
+            SetCapture(hwnd);
+            LRESULT(0)
+        }
+        WM_MOUSEMOVE => {
+            let point = client_point(lparam);
+            let dragging = REGION.with(|view| match view.borrow_mut().as_mut() {
+                Some(view) if view.anchor.is_some() => {
+                    view.current = point;
+                    true
+                }
+                _ => false,
+            });
+            if dragging {
+                let _ = InvalidateRect(hwnd, None, false);
+            }
+            LRESULT(0)
+        }
+        WM_LBUTTONUP => {
+            let point = client_point(lparam);
+            REGION.with(|view| {
+                if let Some(view) = view.borrow_mut().as_mut() {
+                    view.current = point;
+                    view.selected = view
+                        .rect()
+                        .filter(|rect| rect.width >= MIN_REGION_SIZE && rect.height >= MIN_REGION_SIZE);
+                }
+            });
+            let _ = ReleaseCapture();
+            PostQuitMessage(0);
+            LRESULT(0)
+        }
+        WM_RBUTTONDOWN => {
+            PostQuitMessage(0);
+            LRESULT(0)
+        }
+        WM_KEYDOWN if wparam.0 == VK_ESCAPE.0 as usize => {
+            PostQuitMessage(0);
+            LRESULT(0)
+        }
+        WM_PAINT => {
+            paint_region(hwnd);
+            LRESULT(0)
+        }
+        // Painting covers everything; skipping the erase avoids flicker
+        WM_ERASEBKGND => LRESULT(1),
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+/// Shade every monitor and let the user drag out a rectangle. Escape or a
+/// right click cancels. Runs its own window thread and blocks until done.
+pub fn select_region() -> Result<Option<Rect>> {
+    let (tx, rx) = mpsc::channel::<Result<Option<Rect>, String>>();
+
+    thread::spawn(move || unsafe {
+        let instance = match GetModuleHandleW(None) {
+            Ok(instance) => instance,
+            Err(e) => {
+                let _ = tx.send(Err(format!("GetModuleHandleW failed: {}", e)));
+                return;
+            }
+        };
+
+        // Registering again after the first selection fails harmlessly
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(region_proc),
+            hInstance: instance.into(),
+            hCursor: LoadCursorW(None, IDC_CROSS).unwrap_or_default(),
+            lpszClassName: w!("WinShaperRegionSelect"),
+            ..Default::default()
+        };
+        RegisterClassW(&class);
+
+        let screen = virtual_screen();
+        let overlay = match CreateWindowExW(
+            WS_EX_LAYERED | WS_EX_TOPMOST | WS_EX_TOOLWINDOW,
+            w!("WinShaperRegionSelect"),
+            w!("WinShaper region select"),
+            WS_POPUP,
+            screen.x,
+            screen.y,
+            screen.width,
+            screen.height,
+            None,
+            None,
+            instance,
+            None,
+        ) {
+            Ok(overlay) => overlay,
+            Err(e) => {
+                let _ = tx.send(Err(format!("Could not create region overlay: {}", e)));
+                return;
+            }
+        };
+        let _ = SetLayeredWindowAttributes(overlay, TRANSPARENT_KEY, OVERLAY_ALPHA, LWA_COLORKEY | LWA_ALPHA);
+
+        REGION.with(|view| {
+            *view.borrow_mut() = Some(RegionView {
+                origin: (screen.x, screen.y),
+                anchor: None,
+                current: (0, 0),
+                selected: None,
+            })
+        });
+        let _ = ShowWindow(overlay, SW_SHOW);
+        // Escape only reaches the overlay if it has the keyboard
+        let _ = SetForegroundWindow(overlay);
+        let _ = SetFocus(overlay);
+
+        let mut msg = MSG::default();
+        while GetMessageW(&mut msg, HWND::default(), 0, 0).as_bool() {
+            let _ = TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+
+        let _ = DestroyWindow(overlay);
+        let selected = REGION.with(|view| view.borrow_mut().take().and_then(|view| view.selected));
+        let _ = tx.send(Ok(selected));
+    });
+
+    let selected = rx
+        .recv()
+        .map_err(|_| anyhow::anyhow!("Region selection thread exited unexpectedly"))?
+        .map_err(|e| anyhow::anyhow!(e))?;
+    if selected.is_some() {
+        thread::sleep(OVERLAY_CLEAR);
+    }
+    Ok(selected)
+}
@@ -0,0 +1,448 @@
+use super::layout_engine::Rect;
+use super::window_ops;
+use std::cell::RefCell;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use windows::core::w;
+use windows::Win32::Foundation::{COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM};
+use windows::Win32::Graphics::Gdi::{
+    BeginPaint, CreateSolidBrush, DeleteObject, EndPaint, FillRect, FrameRect, InvalidateRect,
+    PAINTSTRUCT,
+};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetAsyncKeyState, VK_CONTROL, VK_MENU, VK_SHIFT};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetCursorPos, GetMessageW,
+    GetSystemMetrics, PostThreadMessageW, RegisterClassW, SetLayeredWindowAttributes, SetTimer,
+    ShowWindow, TranslateMessage, LWA_ALPHA, LWA_COLORKEY, MSG, SM_CXVIRTUALSCREEN,
+    SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN, SW_HIDE, SW_SHOWNOACTIVATE,
+    WM_ERASEBKGND, WM_PAINT, WM_QUIT, WM_TIMER, WNDCLASSW, WS_EX_LAYERED, WS_EX_NOACTIVATE,
+    WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_EX_TRANSPARENT, WS_POPUP,
+};
+
+/// How often the drag is sampled while the overlay is up
+const TICK_MS: u32 = 15;
+const OVERLAY_ALPHA: u8 = 150;
+/// Painted in this colour means fully transparent
+const TRANSPARENT_KEY: COLORREF = COLORREF(0x00FF00FF);
+const ZONE_COLOR: COLORREF = COLORREF(0x00403020);
+const HIGHLIGHT_COLOR: COLORREF = COLORREF(0x00D77800);
+const BORDER_COLOR: COLORREF = COLORREF(0x00F0F0F0);
+const ZONE_SPACING: i32 = 4;
+
+/// Modifier key that must be held for part of drag-to-snap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModifierKey {
+    None,
+    Shift,
+    Ctrl,
+    Alt,
+}
+
+impl ModifierKey {
+    pub fn parse(text: &str) -> Result<Self> {
+        serde_json::from_value(serde_json::json!(text.to_lowercase()))
+            .map_err(|_| anyhow::anyhow!("Unknown modifier key: {}", text))
+    }
+
+    fn is_held(self) -> bool {
+        let key = match self {
+            ModifierKey::None => return false,
+            ModifierKey::Shift => VK_SHIFT,
+            ModifierKey::Ctrl => VK_CONTROL,
+            ModifierKey::Alt => VK_MENU,
+        };
+        unsafe { GetAsyncKeyState(key.0 as i32) as u16 & 0x8000 != 0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DragSnapOptions {
+    /// Held to show the overlay; `None` shows it on every drag
+    pub activation: ModifierKey,
+    /// Held to select a range of zones; `None` disables spanning
+    pub span: ModifierKey,
+}
+
+/// A zone a window can be dropped on
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapZone {
+    /// Index of the monitor, to keep spans on one monitor
+    pub monitor: usize,
+    pub layout: String,
+    pub zone: usize,
+    pub rect: Rect,
+}
+
+/// What the drop will snap to
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapSelection {
+    pub rect: Rect,
+    pub zones: Vec<SnapZone>,
+}
+
+pub fn zone_at(zones: &[SnapZone], x: i32, y: i32) -> Option<usize> {
+    zones.iter().position(|zone| zone.rect.contains(x, y))
+}
+
+/// Smallest rectangle covering all `rects`
+pub fn union_rect(rects: impl IntoIterator<Item = Rect>) -> Option<Rect> {
+    rects.into_iter().reduce(|a, b| {
+        let x = a.x.min(b.x);
+        let y = a.y.min(b.y);
+        Rect {
+            x,
+            y,
+            width: a.right().max(b.right()) - x,
+            height: a.bottom().max(b.bottom()) - y,
+        }
+    })
+}
+
+/// Zones selected by spanning from `anchor` to `current`: both of them and
+/// every zone of the same monitor inside their bounding box
+pub fn span_selection(zones: &[SnapZone], anchor: usize, current: usize) -> Vec<usize> {
+    if zones[anchor].monitor != zones[current].monitor {
+        return vec![current];
+    }
+    let Some(bounds) = union_rect([zones[anchor].rect, zones[current].rect]) else {
+        return vec![current];
+    };
+
+    (0..zones.len())
+        .filter(|&index| {
+            let rect = zones[index].rect;
+            index == anchor
+                || index == current
+                || (zones[index].monitor == zones[anchor].monitor
+                    && rect.x >= bounds.x
+                    && rect.y >= bounds.y
+                    && rect.right() <= bounds.right()
+                    && rect.bottom() <= bounds.bottom())
+        })
+        .collect()
+}
+
+struct OverlayView {
+    zones: Vec<SnapZone>,
+    options: DragSnapOptions,
+    /// Top-left of the virtual screen, the overlay's client origin
+    origin: (i32, i32),
+    window: isize,
+    start_size: (i32, i32),
+    /// Set once the window's size changes: a border drag, not a move
+    resizing: bool,
+    anchor: Option<usize>,
+    highlighted: Vec<usize>,
+    visible: bool,
+    selection: Arc<Mutex<Option<SnapSelection>>>,
+}
+
+thread_local! {
+    static VIEW: RefCell<Option<OverlayView>> = const { RefCell::new(None) };
+}
+
+fn to_client(rect: Rect, origin: (i32, i32)) -> RECT {
+    RECT {
+        left: rect.x - origin.0 + ZONE_SPACING,
+        top: rect.y - origin.1 + ZONE_SPACING,
+        right: rect.right() - origin.0 - ZONE_SPACING,
+        bottom: rect.bottom() - origin.1 - ZONE_SPACING,
+    }
+}
+
+/// Sample cursor and modifiers, update the selection and decide what to redraw
+unsafe fn tick(hwnd: HWND) {
+    let mut cursor = POINT::default();
+    if GetCursorPos(&mut cursor).is_err() {
+        return;
+    }
+
+    let update = VIEW.with(|view| {
+        let mut view = view.borrow_mut();
+        let view = view.as_mut()?;
+
+        if !view.resizing {
+            if let Ok(rect) = window_ops::window_rect(window_ops::raw_to_hwnd(view.window)) {
+                view.resizing = (rect.width, rect.height) != view.start_size;
+            }
+        }
+
+        let active = !view.resizing
+            && (view.options.activation == ModifierKey::None || view.options.activation.is_held());
+
+        let selected = match zone_at(&view.zones, cursor.x, cursor.y).filter(|_| active) {
+            Some(current) if view.options.span.is_held() => {
+                let anchor = *view.anchor.get_or_insert(current);
+                span_selection(&view.zones, anchor, current)
+            }
+            Some(current) => {
+                view.anchor = None;
+                vec![current]
+            }
+            None => {
+                view.anchor = None;
+                Vec::new()
+            }
+        };
+
+        let selection = union_rect(selected.iter().map(|&index| view.zones[index].rect)).map(|rect| SnapSelection {
+            rect,
+            zones: selected.iter().map(|&index| view.zones[index].clone()).collect(),
+        });
+        if let Ok(mut shared) = view.selection.lock() {
+            *shared = selection;
+        }
+
+        let show = (active != view.visible).then_some(active);
+        view.visible = active;
+        let redraw = selected != view.highlighted;
+        view.highlighted = selected;
+        Some((show, redraw))
+    });
+
+    // Window calls happen outside the borrow; they may re-enter the window proc
+    if let Some((show, redraw)) = update {
+        if let Some(show) = show {
+            let _ = ShowWindow(hwnd, if show { SW_SHOWNOACTIVATE } else { SW_HIDE });
+        }
+        if redraw {
+            let _ = InvalidateRect(hwnd, None, false);
+        }
+    }
+}
+
+unsafe fn paint(hwnd: HWND) {
+    let mut ps = PAINTSTRUCT::default();
+    let hdc = BeginPaint(hwnd, &mut ps);
+
+    let background = CreateSolidBrush(TRANSPARENT_KEY);
+    let zone_brush = CreateSolidBrush(ZONE_COLOR);
+    let highlight_brush = CreateSolidBrush(HIGHLIGHT_COLOR);
+    let border_brush = CreateSolidBrush(BORDER_COLOR);
+
+    FillRect(hdc, &ps.rcPaint, background);
+    VIEW.with(|view| {
+        if let Some(view) = view.borrow().as_ref() {
+            for (index, zone) in view.zones.iter().enumerate() {
+                let rect = to_client(zone.rect, view.origin);
+                let brush = if view.highlighted.contains(&index) { highlight_brush } else { zone_brush };
+                FillRect(hdc, &rect, brush);
+                FrameRect(hdc, &rect, border_brush);
+            }
+        }
+    });
+
+    for brush in [background, zone_brush, highlight_brush, border_brush] {
+        let _ = DeleteObject(brush);
+    }
+    let _ = EndPaint(hwnd, &ps);
+}
+
+unsafe extern "system" fn overlay_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_TIMER => {
+            tick(hwnd);
+            LRESULT(0)
+        }
+        WM_PAINT => {
+            paint(hwnd);
+            LRESULT(0)
+        }
+        // Painting covers everything; skipping the erase avoids flicker
+        WM_ERASEBKGND => LRESULT(1),
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+/// One window drag: a click-through overlay over all monitors showing the
+/// zones, tracking which one the window would snap to if dropped now
+pub struct DragSession {
+    pub window: isize,
+    thread_id: u32,
+    handle: JoinHandle<()>,
+    selection: Arc<Mutex<Option<SnapSelection>>>,
+}
+
+impl DragSession {
+    pub fn start(window: isize, zones: Vec<SnapZone>, options: DragSnapOptions) -> Result<Self> {
+        let start = window_ops::window_rect(window_ops::raw_to_hwnd(window))?;
+        let selection = Arc::new(Mutex::new(None));
+        let shared = selection.clone();
+        let (tx, rx) = mpsc::channel::<Result<u32, String>>();
+
+        let handle = thread::spawn(move || unsafe {
+            let instance = match GetModuleHandleW(None) {
+                Ok(instance) => instance,
+                Err(e) => {
+                    let _ = tx.send(Err(format!("GetModuleHandleW failed: {}", e)));
+                    return;
+                }
+            };
+
+            // Registering again after the first session fails harmlessly
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(overlay_proc),
+                hInstance: instance.into(),
+                lpszClassName: w!("WinShaperSnapOverlay"),
+                ..Default::default()
+            };
+            RegisterClassW(&class);
+
+            let origin = (GetSystemMetrics(SM_XVIRTUALSCREEN), GetSystemMetrics(SM_YVIRTUALSCREEN));
+            let overlay = match CreateWindowExW(
+                WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+                w!("WinShaperSnapOverlay"),
+                w!("WinShaper snap overlay"),
+                WS_POPUP,
+                origin.0,
+                origin.1,
+                GetSystemMetrics(SM_CXVIRTUALSCREEN),
+                GetSystemMetrics(SM_CYVIRTUALSCREEN),
+                None,
+                None,
+                instance,
+                None,
+            ) {
+                Ok(overlay) => overlay,
+                Err(e) => {
+                    let _ = tx.send(Err(format!("Could not create snap overlay: {}", e)));
+                    return;
+                }
+            };
+            let _ = SetLayeredWindowAttributes(overlay, TRANSPARENT_KEY, OVERLAY_ALPHA, LWA_COLORKEY | LWA_ALPHA);
+
+            VIEW.with(|view| {
+                *view.borrow_mut() = Some(OverlayView {
+                    zones,
+                    options,
+                    origin,
+                    window,
+                    start_size: (start.width, start.height),
+                    resizing: false,
+                    anchor: None,
+                    highlighted: Vec::new(),
+                    visible: false,
+                    selection: shared,
+                })
+            });
+            SetTimer(overlay, 1, TICK_MS, None);
+            let _ = tx.send(Ok(GetCurrentThreadId()));
+
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, HWND::default(), 0, 0).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+
+            let _ = DestroyWindow(overlay);
+            VIEW.with(|view| *view.borrow_mut() = None);
+        });
+
+        let thread_id = rx
+            .recv()
+            .map_err(|_| anyhow::anyhow!("Snap overlay thread exited unexpectedly"))?
+            .map_err(|e| anyhow::anyhow!(e))?;
+
+        Ok(Self { window, thread_id, handle, selection })
+    }
+
+    /// Close the overlay and return what the window was dropped on, if anything
+    pub fn finish(self) -> Option<SnapSelection> {
+        unsafe {
+            let _ = PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+        }
+        let _ = self.handle.join();
+        self.selection.lock().ok().and_then(|mut selection| selection.take())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    fn zone(monitor: usize, zone: usize, rect: Rect) -> SnapZone {
+        SnapZone { monitor, layout: "test".to_string(), zone, rect }
+    }
+
+    /// A 2x2 grid on monitor 0 and one full zone on monitor 1 to its right
+    fn grid() -> Vec<SnapZone> {
+        vec![
+            zone(0, 0, rect(0, 0, 100, 100)),
+            zone(0, 1, rect(100, 0, 100, 100)),
+            zone(0, 2, rect(0, 100, 100, 100)),
+            zone(0, 3, rect(100, 100, 100, 100)),
+            zone(1, 0, rect(200, 0, 100, 200)),
+        ]
+    }
+
+    #[test]
+    fn zone_at_finds_the_zone_under_the_cursor() {
+        let zones = grid();
+        assert_eq!(zone_at(&zones, 50, 50), Some(0));
+        assert_eq!(zone_at(&zones, 150, 150), Some(3));
+        // Shared edges belong to the zone right of or below them
+        assert_eq!(zone_at(&zones, 100, 0), Some(1));
+        assert_eq!(zone_at(&zones, 0, 100), Some(2));
+        assert_eq!(zone_at(&zones, 250, 199), Some(4));
+        assert_eq!(zone_at(&zones, 300, 0), None);
+        assert_eq!(zone_at(&zones, -1, 50), None);
+        assert_eq!(zone_at(&[], 0, 0), None);
+    }
+
+    #[test]
+    fn union_rect_covers_every_rect() {
+        assert_eq!(union_rect([]), None);
+        assert_eq!(union_rect([rect(10, 20, 30, 40)]), Some(rect(10, 20, 30, 40)));
+        assert_eq!(union_rect([rect(100, 100, 100, 100), rect(0, 0, 100, 100)]), Some(rect(0, 0, 200, 200)));
+        // Rects that do not touch are covered together with the gap between them
+        assert_eq!(union_rect([rect(0, 0, 10, 10), rect(50, -20, 10, 10)]), Some(rect(0, -20, 60, 30)));
+    }
+
+    #[test]
+    fn span_selection_joins_adjacent_zones() {
+        let zones = grid();
+        assert_eq!(span_selection(&zones, 0, 1), vec![0, 1]);
+        assert_eq!(span_selection(&zones, 3, 1), vec![1, 3]);
+        assert_eq!(span_selection(&zones, 2, 2), vec![2]);
+    }
+
+    #[test]
+    fn span_selection_fills_the_box_between_non_adjacent_zones() {
+        let zones = grid();
+        assert_eq!(span_selection(&zones, 0, 3), vec![0, 1, 2, 3]);
+        assert_eq!(span_selection(&zones, 1, 2), vec![0, 1, 2, 3]);
+
+        let columns = vec![
+            zone(0, 0, rect(0, 0, 100, 100)),
+            zone(0, 1, rect(100, 0, 100, 100)),
+            zone(0, 2, rect(200, 0, 100, 100)),
+        ];
+        assert_eq!(span_selection(&columns, 2, 0), vec![0, 1, 2]);
+
+        // A zone sticking out of the box is left out
+        let side = vec![
+            zone(0, 0, rect(0, 0, 100, 200)),
+            zone(0, 1, rect(100, 0, 100, 100)),
+            zone(0, 2, rect(100, 100, 100, 100)),
+        ];
+        assert_eq!(span_selection(&side, 1, 2), vec![1, 2]);
+        assert_eq!(span_selection(&side, 0, 1), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn span_selection_stays_on_one_monitor() {
+        let zones = grid();
+        assert_eq!(span_selection(&zones, 1, 4), vec![4]);
+        assert_eq!(span_selection(&zones, 4, 0), vec![0]);
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use anyhow::Result;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+
+/// `content_type` of history items that hold a snippet template
+pub const SNIPPET_CONTENT_TYPE: &str = "snippet";
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+const DEFAULT_TIME_FORMAT: &str = "%H:%M:%S";
+
+/// Values available while expanding a snippet template
+pub struct ExpansionContext<'a> {
+    pub now: DateTime<Local>,
+    pub clipboard: Option<&'a str>,
+    pub inputs: &'a HashMap<String, String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder { name: &'a str, arg: Option<&'a str> },
+}
+
+/// Split a template into literal text and `{name}` / `{name:arg}` placeholders.
+/// `{{` and `}}` produce literal braces; an unclosed `{` is kept as text.
+fn parse(template: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix("{{") {
+            segments.push(Segment::Text("{"));
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("}}") {
+            segments.push(Segment::Text("}"));
+            rest = tail;
+        } else if rest.starts_with('{') {
+            match rest.find('}') {
+                Some(end) => {
+                    let body = &rest[1..end];
+                    let (name, arg) = match body.split_once(':') {
+                        Some((name, arg)) => (name.trim(), Some(arg)),
+                        None => (body.trim(), None),
+                    };
+                    segments.push(Segment::Placeholder { name, arg });
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    segments.push(Segment::Text(rest));
+                    rest = "";
+                }
+            }
+        } else {
+            // Braces are ASCII, so the cut always lands on a char boundary
+            let end = rest.find(['{', '}']).unwrap_or(rest.len()).max(1);
+            segments.push(Segment::Text(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+
+    segments
+}
+
+fn format_time(now: &DateTime<Local>, format: &str) -> Result<String> {
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(anyhow::anyhow!("Invalid date/time format: {}", format));
+    }
+
+    let mut out = String::new();
+    write!(out, "{}", now.format_with_items(items.into_iter()))
+        .map_err(|_| anyhow::anyhow!("Invalid date/time format: {}", format))?;
+    Ok(out)
+}
+
+/// Check that every placeholder is known and every format string is valid
+pub fn validate(template: &str) -> Result<()> {
+    let now = Local::now();
+    for segment in parse(template) {
+        if let Segment::Placeholder { name, arg } = segment {
+            match name {
+                "date" | "time" => {
+                    let default = if name == "date" { DEFAULT_DATE_FORMAT } else { DEFAULT_TIME_FORMAT };
+                    format_time(&now, arg.unwrap_or(default))?;
+                }
+                "input" => {
+                    if arg.is_none_or(|arg| arg.trim().is_empty()) {
+                        return Err(anyhow::anyhow!("{{input}} placeholder needs a name, e.g. {{input:Name}}"));
+                    }
+                }
+                "clipboard" | "uuid" => {}
+                _ => return Err(anyhow::anyhow!("Unknown placeholder: {{{}}}", name)),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Names of the `{input:Name}` placeholders, in order of first appearance
+pub fn required_inputs(template: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for segment in parse(template) {
+        if let Segment::Placeholder { name: "input", arg: Some(arg) } = segment {
+            let arg = arg.trim().to_string();
+            if !names.contains(&arg) {
+                names.push(arg);
+            }
+        }
+    }
+    names
+}
+
+/// Expand all placeholders in a template
+pub fn expand(template: &str, ctx: &ExpansionContext) -> Result<String> {
+    let missing: Vec<String> = required_inputs(template)
+        .into_iter()
+        .filter(|name| !ctx.inputs.contains_key(name))
+        .collect();
+    if !missing.is_empty() {
+        return Err(anyhow::anyhow!("Missing snippet inputs: {}", missing.join(", ")));
+    }
+
+    let mut out = String::with_capacity(template.len());
+    for segment in parse(template) {
+        match segment {
+            Segment::Text(text) => out.push_str(text),
+            Segment::Placeholder { name, arg } => match name {
+                "date" => out.push_str(&format_time(&ctx.now, arg.unwrap_or(DEFAULT_DATE_FORMAT))?),
+                "time" => out.push_str(&format_time(&ctx.now, arg.unwrap_or(DEFAULT_TIME_FORMAT))?),
+                "clipboard" => out.push_str(ctx.clipboard.unwrap_or_default()),
+                "uuid" => out.push_str(&uuid::Uuid::new_v4().to_string()),
+                "input" => {
+                    let key = arg.unwrap_or_default().trim();
+                    out.push_str(ctx.inputs.get(key).map(String::as_str).unwrap_or_default());
+                }
+                _ => return Err(anyhow::anyhow!("Unknown placeholder: {{{}}}", name)),
+            },
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn context<'a>(inputs: &'a HashMap<String, String>) -> ExpansionContext<'a> {
+        ExpansionContext {
+            now: Local.with_ymd_and_hms(2024, 3, 9, 14, 5, 7).unwrap(),
+            clipboard: Some("copied"),
+            inputs,
+        }
+    }
+
+    #[test]
+    fn parse_splits_text_placeholders_and_escapes() {
+        assert_eq!(
+            parse("Hi {input: Name }, {{x}} {date:%Y}"),
+            vec![
+                Segment::Text("Hi "),
+                Segment::Placeholder { name: "input", arg: Some(" Name ") },
+                Segment::Text(", "),
+                Segment::Text("{"),
+                Segment::Text("x"),
+                Segment::Text("}"),
+                Segment::Text(" "),
+                Segment::Placeholder { name: "date", arg: Some("%Y") },
+            ]
+        );
+        // An unclosed brace is plain text
+        assert_eq!(parse("a {b"), vec![Segment::Text("a "), Segment::Text("{b")]);
+    }
+
+    #[test]
+    fn expand_fills_every_placeholder() {
+        let inputs = HashMap::from([("Name".to_string(), "Ada".to_string())]);
+        let expanded = expand("{input:Name} on {date} at {time:%H:%M}: {clipboard} {{ok}}", &context(&inputs)).unwrap();
+        assert_eq!(expanded, "Ada on 2024-03-09 at 14:05: copied {ok}");
+
+        let uuid = expand("{uuid}", &context(&inputs)).unwrap();
+        assert!(uuid::Uuid::parse_str(&uuid).is_ok());
+
+        let empty = HashMap::new();
+        let no_clipboard = ExpansionContext { clipboard: None, ..context(&empty) };
+        assert_eq!(expand("[{clipboard}]", &no_clipboard).unwrap(), "[]");
+    }
+
+    #[test]
+    fn expand_needs_every_input() {
+        let inputs = HashMap::from([("First".to_string(), "Ada".to_string())]);
+        let error = expand("{input:First} {input:Last} {input:Title}", &context(&inputs)).unwrap_err();
+        assert_eq!(error.to_string(), "Missing snippet inputs: Last, Title");
+    }
+
+    #[test]
+    fn required_inputs_are_trimmed_and_listed_once() {
+        assert_eq!(
+            required_inputs("{input:B} {input: A } {input:B} {date}"),
+            vec!["B".to_string(), "A".to_string()]
+        );
+        assert!(required_inputs("no inputs {clipboard}").is_empty());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_and_malformed_placeholders() {
+        assert!(validate("Plain text, {{braces}} and {date:%d.%m} {time} {uuid} {clipboard} {input:X}").is_ok());
+        assert!(validate("{unknown}").is_err());
+        assert!(validate("{input}").is_err());
+        assert!(validate("{input: }").is_err());
+        assert!(validate("{date:%Q}").is_err());
+    }
+}
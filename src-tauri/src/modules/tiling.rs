@@ -0,0 +1,855 @@
+use super::layout_engine::Rect;
+use super::monitors::MonitorInfo;
+use super::window_backend::WindowBackend;
+use super::window_ops::{WindowInfo, WindowMatcher};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_TILING_GAP: i32 = 8;
+
+/// How far one resize step moves a split, as a fraction of the split's size
+pub const RESIZE_STEP: f64 = 0.05;
+
+const MIN_RATIO: f64 = 0.1;
+const MAX_RATIO: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::Left, Direction::Right, Direction::Up, Direction::Down];
+
+    fn axis(self) -> Axis {
+        match self {
+            Direction::Left | Direction::Right => Axis::Horizontal,
+            Direction::Up | Direction::Down => Axis::Vertical,
+        }
+    }
+
+    /// Right and Down point from a split's first child towards its second
+    fn is_forward(self) -> bool {
+        matches!(self, Direction::Right | Direction::Down)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Direction::Left => "left",
+            Direction::Right => "right",
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+}
+
+/// `Horizontal` splits place children side by side, `Vertical` stacks them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf(isize),
+    Split {
+        axis: Axis,
+        /// Share of the space given to `first`
+        ratio: f64,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+fn split_rect(area: Rect, axis: Axis, ratio: f64) -> (Rect, Rect) {
+    match axis {
+        Axis::Horizontal => {
+            let first_width = (area.width as f64 * ratio).round() as i32;
+            (
+                Rect { width: first_width, ..area },
+                Rect { x: area.x + first_width, width: area.width - first_width, ..area },
+            )
+        }
+        Axis::Vertical => {
+            let first_height = (area.height as f64 * ratio).round() as i32;
+            (
+                Rect { height: first_height, ..area },
+                Rect { y: area.y + first_height, height: area.height - first_height, ..area },
+            )
+        }
+    }
+}
+
+fn inset(rect: Rect, amount: i32) -> Rect {
+    Rect {
+        x: rect.x + amount,
+        y: rect.y + amount,
+        width: (rect.width - 2 * amount).max(1),
+        height: (rect.height - 2 * amount).max(1),
+    }
+}
+
+enum Resize {
+    NotHere,
+    /// The window is below this node but no suitable split has been found yet
+    Pending,
+    Done,
+}
+
+impl Node {
+    fn contains(&self, window: isize) -> bool {
+        match self {
+            Node::Leaf(id) => *id == window,
+            Node::Split { first, second, .. } => first.contains(window) || second.contains(window),
+        }
+    }
+
+    fn collect(&self, out: &mut Vec<isize>) {
+        match self {
+            Node::Leaf(id) => out.push(*id),
+            Node::Split { first, second, .. } => {
+                first.collect(out);
+                second.collect(out);
+            }
+        }
+    }
+
+    fn layout(&self, area: Rect, out: &mut Vec<(isize, Rect)>) {
+        match self {
+            Node::Leaf(id) => out.push((*id, area)),
+            Node::Split { axis, ratio, first, second } => {
+                let (first_area, second_area) = split_rect(area, *axis, *ratio);
+                first.layout(first_area, out);
+                second.layout(second_area, out);
+            }
+        }
+    }
+
+    /// Remove a leaf, collapsing its parent split into the sibling.
+    /// Returns `None` if this whole node was the removed leaf.
+    fn remove(self, window: isize) -> Option<Node> {
+        match self {
+            Node::Leaf(id) if id == window => None,
+            Node::Leaf(_) => Some(self),
+            Node::Split { axis, ratio, first, second } => match (first.remove(window), second.remove(window)) {
+                (Some(first), Some(second)) => Some(Node::Split {
+                    axis,
+                    ratio,
+                    first: Box::new(first),
+                    second: Box::new(second),
+                }),
+                (Some(only), None) | (None, Some(only)) => Some(only),
+                (None, None) => None,
+            },
+        }
+    }
+
+    /// Replace the leaf `target` with a split holding it and `window`
+    fn split_leaf(&mut self, target: isize, window: isize, axis: Axis, window_first: bool) -> bool {
+        match self {
+            Node::Leaf(id) if *id == target => {
+                let (first, second) = if window_first { (window, target) } else { (target, window) };
+                *self = Node::Split {
+                    axis,
+                    ratio: 0.5,
+                    first: Box::new(Node::Leaf(first)),
+                    second: Box::new(Node::Leaf(second)),
+                };
+                true
+            }
+            Node::Leaf(_) => false,
+            Node::Split { first, second, .. } => {
+                first.split_leaf(target, window, axis, window_first)
+                    || second.split_leaf(target, window, axis, window_first)
+            }
+        }
+    }
+
+    fn swap(&mut self, a: isize, b: isize) {
+        match self {
+            Node::Leaf(id) if *id == a => *id = b,
+            Node::Leaf(id) if *id == b => *id = a,
+            Node::Leaf(_) => {}
+            Node::Split { first, second, .. } => {
+                first.swap(a, b);
+                second.swap(a, b);
+            }
+        }
+    }
+
+    /// Move the edge of `window` that faces `direction` by `delta`, using the
+    /// closest enclosing split whose divider lies on that side
+    fn resize(&mut self, window: isize, direction: Direction, delta: f64) -> Resize {
+        match self {
+            Node::Leaf(id) if *id == window => Resize::Pending,
+            Node::Leaf(_) => Resize::NotHere,
+            Node::Split { axis, ratio, first, second } => {
+                let (in_first, result) = match first.resize(window, direction, delta) {
+                    Resize::NotHere => (false, second.resize(window, direction, delta)),
+                    result => (true, result),
+                };
+                match result {
+                    Resize::Pending if *axis == direction.axis() && in_first == direction.is_forward() => {
+                        let change = if in_first { delta } else { -delta };
+                        *ratio = (*ratio + change).clamp(MIN_RATIO, MAX_RATIO);
+                        Resize::Done
+                    }
+                    result => result,
+                }
+            }
+        }
+    }
+}
+
+/// Windows of one monitor arranged as a binary split tree
+#[derive(Debug, Clone, Default)]
+pub struct TilingTree {
+    root: Option<Node>,
+}
+
+impl TilingTree {
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn contains(&self, window: isize) -> bool {
+        self.root.as_ref().is_some_and(|root| root.contains(window))
+    }
+
+    /// Windows in tree order (left to right, top to bottom)
+    pub fn windows(&self) -> Vec<isize> {
+        let mut windows = Vec::new();
+        if let Some(root) = &self.root {
+            root.collect(&mut windows);
+        }
+        windows
+    }
+
+    /// Rectangle for every window when the tree fills `area`, with `gap`
+    /// pixels between windows and around the edges
+    pub fn layout(&self, area: Rect, gap: i32) -> Vec<(isize, Rect)> {
+        let mut rects = Vec::new();
+        if let Some(root) = &self.root {
+            root.layout(inset(area, gap / 2), &mut rects);
+        }
+        for (_, rect) in rects.iter_mut() {
+            *rect = inset(*rect, gap - gap / 2);
+        }
+        rects
+    }
+
+    /// Add a window by splitting `target` (or the last window) along its longer side
+    pub fn insert(&mut self, window: isize, target: Option<isize>, area: Rect) {
+        if self.contains(window) {
+            return;
+        }
+        if self.root.is_none() {
+            self.root = Some(Node::Leaf(window));
+            return;
+        }
+
+        let windows = self.windows();
+        let target = target
+            .filter(|target| windows.contains(target))
+            .or_else(|| windows.last().copied());
+        let Some(target) = target else {
+            return;
+        };
+
+        let target_rect = self
+            .layout(area, 0)
+            .into_iter()
+            .find(|(id, _)| *id == target)
+            .map(|(_, rect)| rect)
+            .unwrap_or(area);
+        let axis = if target_rect.width >= target_rect.height { Axis::Horizontal } else { Axis::Vertical };
+
+        if let Some(root) = self.root.as_mut() {
+            root.split_leaf(target, window, axis, false);
+        }
+    }
+
+    /// Add a window next to `target` on the given side of it
+    pub fn insert_beside(&mut self, window: isize, target: isize, side: Direction) -> bool {
+        if self.contains(window) {
+            return false;
+        }
+        match self.root.as_mut() {
+            Some(root) => root.split_leaf(target, window, side.axis(), !side.is_forward()),
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, window: isize) -> bool {
+        if !self.contains(window) {
+            return false;
+        }
+        self.root = self.root.take().and_then(|root| root.remove(window));
+        true
+    }
+
+    pub fn swap(&mut self, a: isize, b: isize) {
+        if let Some(root) = self.root.as_mut() {
+            root.swap(a, b);
+        }
+    }
+
+    pub fn resize(&mut self, window: isize, direction: Direction, delta: f64) -> bool {
+        match self.root.as_mut() {
+            Some(root) => matches!(root.resize(window, direction, delta), Resize::Done),
+            None => false,
+        }
+    }
+
+    /// The window next to `window` in `direction`: the closest one beyond that
+    /// edge, preferring the one sharing the most of that edge
+    pub fn neighbor(&self, window: isize, direction: Direction, area: Rect) -> Option<isize> {
+        let rects = self.layout(area, 0);
+        let (_, from) = rects.iter().find(|(id, _)| *id == window)?;
+
+        rects
+            .iter()
+            .filter(|(id, _)| *id != window)
+            .filter_map(|(id, rect)| {
+                let (distance, overlap) = match direction {
+                    Direction::Left => (from.x - rect.right(), span_overlap(from.y, from.bottom(), rect.y, rect.bottom())),
+                    Direction::Right => (rect.x - from.right(), span_overlap(from.y, from.bottom(), rect.y, rect.bottom())),
+                    Direction::Up => (from.y - rect.bottom(), span_overlap(from.x, from.right(), rect.x, rect.right())),
+                    Direction::Down => (rect.y - from.bottom(), span_overlap(from.x, from.right(), rect.x, rect.right())),
+                };
+                (distance >= 0 && overlap > 0).then_some((distance, -overlap, *id))
+            })
+            .min()
+            .map(|(_, _, id)| id)
+    }
+}
+
+fn span_overlap(a_start: i32, a_end: i32, b_start: i32, b_end: i32) -> i32 {
+    a_end.min(b_end) - a_start.max(b_start)
+}
+
+/// Callback that runs a tiling hotkey's action. Runs on the hotkey thread.
+pub type TilingActionHandler = Arc<dyn Fn(TilingAction) + Send + Sync>;
+
+/// A keyboard command in tiling mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TilingAction {
+    Focus(Direction),
+    Move(Direction),
+    Swap(Direction),
+    Resize(Direction),
+    ToggleFloating,
+    Retile,
+}
+
+impl TilingAction {
+    /// Setting key such as `focus_left` or `toggle_floating`
+    pub fn key(&self) -> String {
+        match self {
+            TilingAction::Focus(direction) => format!("focus_{}", direction.name()),
+            TilingAction::Move(direction) => format!("move_{}", direction.name()),
+            TilingAction::Swap(direction) => format!("swap_{}", direction.name()),
+            TilingAction::Resize(direction) => format!("resize_{}", direction.name()),
+            TilingAction::ToggleFloating => "toggle_floating".to_string(),
+            TilingAction::Retile => "retile".to_string(),
+        }
+    }
+
+    pub fn from_key(key: &str) -> Result<Self> {
+        Self::all()
+            .into_iter()
+            .find(|action| action.key() == key)
+            .ok_or_else(|| anyhow::anyhow!("Unknown tiling action: {}", key))
+    }
+
+    pub fn all() -> Vec<Self> {
+        let mut actions = Vec::new();
+        for direction in Direction::ALL {
+            actions.push(TilingAction::Focus(direction));
+            actions.push(TilingAction::Move(direction));
+            actions.push(TilingAction::Swap(direction));
+            actions.push(TilingAction::Resize(direction));
+        }
+        actions.push(TilingAction::ToggleFloating);
+        actions.push(TilingAction::Retile);
+        actions
+    }
+
+    /// Default hotkey for each action
+    pub fn default_hotkey(&self) -> String {
+        let arrow = |direction: &Direction| match direction {
+            Direction::Left => "Left",
+            Direction::Right => "Right",
+            Direction::Up => "Up",
+            Direction::Down => "Down",
+        };
+        match self {
+            TilingAction::Focus(direction) => format!("Win+Alt+{}", arrow(direction)),
+            TilingAction::Move(direction) => format!("Win+Alt+Shift+{}", arrow(direction)),
+            TilingAction::Swap(direction) => format!("Win+Ctrl+Alt+{}", arrow(direction)),
+            TilingAction::Resize(direction) => format!("Ctrl+Alt+Shift+{}", arrow(direction)),
+            TilingAction::ToggleFloating => "Win+Alt+F".to_string(),
+            TilingAction::Retile => "Win+Alt+R".to_string(),
+        }
+    }
+}
+
+/// Keeps one split tree per monitor in sync with the desktop and applies
+/// tiling commands through a `WindowBackend`
+pub struct TilingManager<B: WindowBackend> {
+    backend: B,
+    trees: HashMap<String, TilingTree>,
+    /// Windows matching any of these are never tiled
+    floating_rules: Vec<WindowMatcher>,
+    /// Windows floated by hand with `ToggleFloating`
+    floating: HashSet<isize>,
+    gap: i32,
+}
+
+impl<B: WindowBackend> TilingManager<B> {
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            trees: HashMap::new(),
+            floating_rules: Vec::new(),
+            floating: HashSet::new(),
+            gap: DEFAULT_TILING_GAP,
+        }
+    }
+
+    pub fn set_gap(&mut self, gap: i32) {
+        self.gap = gap.max(0);
+    }
+
+    pub fn set_floating_rules(&mut self, rules: Vec<WindowMatcher>) {
+        self.floating_rules = rules;
+    }
+
+    fn is_tileable(&self, window: &WindowInfo) -> bool {
+        !window.minimized
+            && !window.maximized
+            && !self.floating.contains(&window.hwnd)
+            && !self.floating_rules.iter().any(|rule| rule.matches(window))
+    }
+
+    fn tree_of(&self, window: isize) -> Option<String> {
+        self.trees
+            .iter()
+            .find(|(_, tree)| tree.contains(window))
+            .map(|(monitor, _)| monitor.clone())
+    }
+
+    fn monitor(&self, id: &str) -> Option<MonitorInfo> {
+        self.backend.monitors().into_iter().find(|monitor| monitor.id == id)
+    }
+
+    /// Tile every arrangeable window that is open now
+    pub fn tile_all(&mut self) -> Result<()> {
+        self.trees.clear();
+        // Oldest first, so the most recently used window is inserted last
+        let mut windows: Vec<WindowInfo> = self
+            .backend
+            .app_windows()
+            .into_iter()
+            .filter(|window| self.is_tileable(window))
+            .collect();
+        windows.reverse();
+
+        for window in &windows {
+            if let Some(monitor) = self.backend.monitor_of(window.hwnd) {
+                self.trees
+                    .entry(monitor.id.clone())
+                    .or_default()
+                    .insert(window.hwnd, None, monitor.work_area);
+            }
+        }
+        self.retile_all()
+    }
+
+    pub fn retile_all(&mut self) -> Result<()> {
+        let monitors: Vec<String> = self.trees.keys().cloned().collect();
+        for monitor in monitors {
+            self.retile(&monitor)?;
+        }
+        Ok(())
+    }
+
+    fn retile(&mut self, monitor_id: &str) -> Result<()> {
+        let Some(monitor) = self.monitor(monitor_id) else {
+            return Ok(());
+        };
+        let Some(tree) = self.trees.get(monitor_id) else {
+            return Ok(());
+        };
+
+        for (window, rect) in tree.layout(monitor.work_area, self.gap) {
+            if let Err(e) = self.backend.place(window, rect) {
+                log::warn!("Could not tile window {:#x}: {}", window, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop windows that no longer exist from every tree
+    fn prune(&mut self) -> Vec<String> {
+        let mut changed = Vec::new();
+        for (monitor, tree) in self.trees.iter_mut() {
+            for window in tree.windows() {
+                if !self.backend.is_alive(window) {
+                    tree.remove(window);
+                    changed.push(monitor.clone());
+                }
+            }
+        }
+        self.floating.retain(|window| self.backend.is_alive(*window));
+        changed
+    }
+
+    /// Start tiling a window, next to the focused window of its monitor
+    pub fn add_window(&mut self, window: isize) -> Result<()> {
+        if self.tree_of(window).is_some() {
+            return Ok(());
+        }
+        let Some(info) = self.backend.window(window) else {
+            return Ok(());
+        };
+        if !self.is_tileable(&info) {
+            return Ok(());
+        }
+        let Some(monitor) = self.backend.monitor_of(window) else {
+            return Ok(());
+        };
+
+        let focused = self.backend.foreground().filter(|focused| *focused != window);
+        self.trees
+            .entry(monitor.id.clone())
+            .or_default()
+            .insert(window, focused, monitor.work_area);
+        self.retile(&monitor.id)
+    }
+
+    /// Stop tiling a window and close the gap it leaves
+    pub fn remove_window(&mut self, window: isize) -> Result<()> {
+        let Some(monitor) = self.tree_of(window) else {
+            return Ok(());
+        };
+        if let Some(tree) = self.trees.get_mut(&monitor) {
+            tree.remove(window);
+        }
+        self.retile(&monitor)
+    }
+
+    /// Called for window shown/created events
+    pub fn window_appeared(&mut self, window: isize) -> Result<()> {
+        for monitor in self.prune() {
+            self.retile(&monitor)?;
+        }
+        self.add_window(window)
+    }
+
+    /// Called for window hidden/destroyed/minimized events
+    pub fn window_disappeared(&mut self, window: isize) -> Result<()> {
+        self.remove_window(window)
+    }
+
+    fn focused_in_tree(&self) -> Result<(isize, String, MonitorInfo)> {
+        let focused = self
+            .backend
+            .foreground()
+            .ok_or_else(|| anyhow::anyhow!("No focused window"))?;
+        let monitor_id = self
+            .tree_of(focused)
+            .ok_or_else(|| anyhow::anyhow!("Focused window is not tiled"))?;
+        let monitor = self
+            .monitor(&monitor_id)
+            .ok_or_else(|| anyhow::anyhow!("Monitor {} is gone", monitor_id))?;
+        Ok((focused, monitor_id, monitor))
+    }
+
+    /// The monitor beyond `from` in `direction`, nearest first
+    fn monitor_towards(&self, from: &MonitorInfo, direction: Direction) -> Option<MonitorInfo> {
+        let (from_x, from_y) = from.bounds.center();
+        self.backend
+            .monitors()
+            .into_iter()
+            .filter(|monitor| monitor.id != from.id)
+            .filter_map(|monitor| {
+                let (x, y) = monitor.bounds.center();
+                let distance = match direction {
+                    Direction::Left => from_x - x,
+                    Direction::Right => x - from_x,
+                    Direction::Up => from_y - y,
+                    Direction::Down => y - from_y,
+                };
+                (distance > 0).then_some((distance, monitor))
+            })
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, monitor)| monitor)
+    }
+
+    pub fn run(&mut self, action: TilingAction) -> Result<()> {
+        match action {
+            TilingAction::Focus(direction) => self.focus(direction),
+            TilingAction::Move(direction) => self.move_window(direction),
+            TilingAction::Swap(direction) => self.swap(direction),
+            TilingAction::Resize(direction) => self.resize(direction, RESIZE_STEP),
+            TilingAction::ToggleFloating => self.toggle_floating(),
+            TilingAction::Retile => self.tile_all(),
+        }
+    }
+
+    pub fn focus(&mut self, direction: Direction) -> Result<()> {
+        let (focused, monitor_id, monitor) = self.focused_in_tree()?;
+        let neighbor = self.trees[&monitor_id].neighbor(focused, direction, monitor.work_area);
+
+        let target = match neighbor {
+            Some(neighbor) => Some(neighbor),
+            // At the edge of a monitor, continue onto the next one
+            None => self
+                .monitor_towards(&monitor, direction)
+                .and_then(|next| self.trees.get(&next.id))
+                .and_then(|tree| tree.windows().first().copied()),
+        };
+
+        match target {
+            Some(target) => self.backend.focus(target),
+            None => Ok(()),
+        }
+    }
+
+    pub fn swap(&mut self, direction: Direction) -> Result<()> {
+        let (focused, monitor_id, monitor) = self.focused_in_tree()?;
+        let Some(neighbor) = self.trees[&monitor_id].neighbor(focused, direction, monitor.work_area) else {
+            return Ok(());
+        };
+
+        if let Some(tree) = self.trees.get_mut(&monitor_id) {
+            tree.swap(focused, neighbor);
+        }
+        self.retile(&monitor_id)
+    }
+
+    /// Move the focused window past its neighbor in `direction`, or onto the
+    /// next monitor that way if it is already at the edge
+    pub fn move_window(&mut self, direction: Direction) -> Result<()> {
+        let (focused, monitor_id, monitor) = self.focused_in_tree()?;
+        let neighbor = self.trees[&monitor_id].neighbor(focused, direction, monitor.work_area);
+
+        match neighbor {
+            Some(neighbor) => {
+                if let Some(tree) = self.trees.get_mut(&monitor_id) {
+                    tree.remove(focused);
+                    tree.insert_beside(focused, neighbor, direction);
+                }
+                self.retile(&monitor_id)
+            }
+            None => {
+                let Some(next) = self.monitor_towards(&monitor, direction) else {
+                    return Ok(());
+                };
+                if let Some(tree) = self.trees.get_mut(&monitor_id) {
+                    tree.remove(focused);
+                }
+                self.trees
+                    .entry(next.id.clone())
+                    .or_default()
+                    .insert(focused, None, next.work_area);
+                self.retile(&monitor_id)?;
+                self.retile(&next.id)
+            }
+        }
+    }
+
+    pub fn resize(&mut self, direction: Direction, delta: f64) -> Result<()> {
+        let (focused, monitor_id, _) = self.focused_in_tree()?;
+        let resized = self
+            .trees
+            .get_mut(&monitor_id)
+            .is_some_and(|tree| tree.resize(focused, direction, delta));
+        if resized {
+            self.retile(&monitor_id)?;
+        }
+        Ok(())
+    }
+
+    /// Take the focused window out of tiling, or put a floating one back in
+    pub fn toggle_floating(&mut self) -> Result<()> {
+        let focused = self
+            .backend
+            .foreground()
+            .ok_or_else(|| anyhow::anyhow!("No focused window"))?;
+
+        if self.floating.remove(&focused) {
+            self.add_window(focused)
+        } else {
+            self.floating.insert(focused);
+            self.remove_window(focused)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::window_backend::FakeBackend;
+
+    const AREA: Rect = Rect { x: 0, y: 0, width: 1000, height: 600 };
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    fn tree(windows: &[isize]) -> TilingTree {
+        let mut tree = TilingTree::default();
+        for window in windows {
+            tree.insert(*window, None, AREA);
+        }
+        tree
+    }
+
+    #[test]
+    fn insert_splits_the_target_along_its_longer_side() {
+        let mut tree = tree(&[1, 2]);
+        assert_eq!(tree.layout(AREA, 0), vec![(1, rect(0, 0, 500, 600)), (2, rect(500, 0, 500, 600))]);
+
+        // Window 1 is now taller than wide, so it is split top and bottom
+        tree.insert(3, Some(1), AREA);
+        assert_eq!(tree.windows(), vec![1, 3, 2]);
+        assert_eq!(
+            tree.layout(AREA, 0),
+            vec![(1, rect(0, 0, 500, 300)), (3, rect(0, 300, 500, 300)), (2, rect(500, 0, 500, 600))]
+        );
+
+        tree.insert(3, Some(2), AREA);
+        assert_eq!(tree.windows(), vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn insert_beside_puts_the_window_on_the_given_side() {
+        let mut tree = tree(&[1, 2]);
+        assert!(tree.insert_beside(3, 2, Direction::Up));
+        assert!(!tree.insert_beside(4, 9, Direction::Left));
+        assert_eq!(
+            tree.layout(AREA, 0),
+            vec![(1, rect(0, 0, 500, 600)), (3, rect(500, 0, 500, 300)), (2, rect(500, 300, 500, 300))]
+        );
+    }
+
+    #[test]
+    fn remove_collapses_the_split_into_the_sibling() {
+        let mut tree = tree(&[1, 2]);
+        tree.insert(3, Some(1), AREA);
+
+        assert!(tree.remove(1));
+        assert!(!tree.remove(1));
+        assert_eq!(tree.layout(AREA, 0), vec![(3, rect(0, 0, 500, 600)), (2, rect(500, 0, 500, 600))]);
+
+        assert!(tree.remove(3));
+        assert!(tree.remove(2));
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn resize_moves_the_divider_on_the_facing_side() {
+        let mut tree = tree(&[1, 2]);
+
+        assert!(tree.resize(1, Direction::Right, 0.1));
+        assert_eq!(tree.layout(AREA, 0)[0].1, rect(0, 0, 600, 600));
+
+        // Window 2 has no divider to its right or below it
+        assert!(!tree.resize(2, Direction::Right, 0.1));
+        assert!(!tree.resize(2, Direction::Down, 0.1));
+
+        assert!(tree.resize(2, Direction::Left, 0.3));
+        assert_eq!(tree.layout(AREA, 0)[1].1, rect(300, 0, 700, 600));
+
+        // Ratios stay within bounds however far they are pushed
+        assert!(tree.resize(1, Direction::Right, 5.0));
+        assert_eq!(tree.layout(AREA, 0)[0].1, rect(0, 0, 900, 600));
+    }
+
+    #[test]
+    fn layout_leaves_gaps_between_and_around_windows() {
+        let tree = tree(&[1, 2]);
+        assert_eq!(tree.layout(AREA, 8), vec![(1, rect(8, 8, 488, 584)), (2, rect(504, 8, 488, 584))]);
+    }
+
+    fn window(hwnd: isize) -> WindowInfo {
+        WindowInfo {
+            hwnd,
+            pid: 1,
+            exe: "app.exe".to_string(),
+            exe_path: String::new(),
+            class_name: String::new(),
+            title: String::new(),
+            rect: rect(100, 100, 200, 200),
+            maximized: false,
+            minimized: false,
+        }
+    }
+
+    fn manager(windows: &[isize]) -> TilingManager<FakeBackend> {
+        let backend = FakeBackend {
+            windows: windows.iter().map(|hwnd| window(*hwnd)).collect(),
+            monitors: vec![MonitorInfo {
+                id: "DISPLAY1".to_string(),
+                bounds: AREA,
+                work_area: AREA,
+                dpi: 96,
+                scale: 1.0,
+                primary: true,
+            }],
+            ..Default::default()
+        };
+        let mut manager = TilingManager::new(backend);
+        manager.set_gap(0);
+        manager
+    }
+
+    #[test]
+    fn manager_places_windows_as_they_come_and_go() {
+        // The most recently used window is tiled last
+        let mut manager = manager(&[1, 2]);
+        manager.tile_all().unwrap();
+        assert_eq!(
+            manager.backend.placements,
+            vec![(2, rect(0, 0, 500, 600)), (1, rect(500, 0, 500, 600))]
+        );
+
+        manager.backend.placements.clear();
+        manager.backend.windows.push(window(3));
+        manager.backend.foreground = Some(2);
+        manager.window_appeared(3).unwrap();
+        assert_eq!(
+            manager.backend.placements,
+            vec![(2, rect(0, 0, 500, 300)), (3, rect(0, 300, 500, 300)), (1, rect(500, 0, 500, 600))]
+        );
+
+        manager.backend.placements.clear();
+        manager.window_disappeared(2).unwrap();
+        assert_eq!(
+            manager.backend.placements,
+            vec![(3, rect(0, 0, 500, 600)), (1, rect(500, 0, 500, 600))]
+        );
+    }
+
+    #[test]
+    fn manager_focuses_the_neighbor() {
+        let mut manager = manager(&[1, 2]);
+        manager.tile_all().unwrap();
+
+        manager.backend.foreground = Some(2);
+        manager.focus(Direction::Right).unwrap();
+        assert_eq!(manager.backend.foreground, Some(1));
+
+        manager.focus(Direction::Right).unwrap();
+        assert_eq!(manager.backend.foreground, Some(1));
+    }
+}
@@ -0,0 +1,125 @@
+use super::layout_engine::Rect;
+use super::monitors::{self, MonitorInfo};
+use super::window_ops::{self, WindowInfo};
+use anyhow::Result;
+
+use windows::Win32::UI::WindowsAndMessaging::SetForegroundWindow;
+
+/// The window system operations window management logic depends on. Keeping
+/// them behind a trait lets that logic run against a fake desktop.
+pub trait WindowBackend: Send + Sync {
+    /// Arrangeable application windows, most recently used first
+    fn app_windows(&self) -> Vec<WindowInfo>;
+    /// Details of an arrangeable window, `None` if it is gone or not arrangeable
+    fn window(&self, window: isize) -> Option<WindowInfo>;
+    fn is_alive(&self, window: isize) -> bool;
+    fn foreground(&self) -> Option<isize>;
+    fn monitors(&self) -> Vec<MonitorInfo>;
+    fn monitor_of(&self, window: isize) -> Option<MonitorInfo>;
+    fn place(&mut self, window: isize, rect: Rect) -> Result<()>;
+    fn focus(&mut self, window: isize) -> Result<()>;
+}
+
+/// The real desktop
+#[derive(Debug, Default)]
+pub struct Win32Backend;
+
+impl WindowBackend for Win32Backend {
+    fn app_windows(&self) -> Vec<WindowInfo> {
+        window_ops::enumerate_windows().unwrap_or_default()
+    }
+
+    fn window(&self, window: isize) -> Option<WindowInfo> {
+        let hwnd = window_ops::raw_to_hwnd(window);
+        if !window_ops::is_app_window(hwnd) {
+            return None;
+        }
+        window_ops::window_info(hwnd).ok()
+    }
+
+    fn is_alive(&self, window: isize) -> bool {
+        window_ops::is_window(window_ops::raw_to_hwnd(window))
+    }
+
+    fn foreground(&self) -> Option<isize> {
+        window_ops::foreground_window().ok().map(window_ops::hwnd_to_raw)
+    }
+
+    fn monitors(&self) -> Vec<MonitorInfo> {
+        monitors::enumerate_monitors().unwrap_or_default()
+    }
+
+    fn monitor_of(&self, window: isize) -> Option<MonitorInfo> {
+        monitors::monitor_for_window(window_ops::raw_to_hwnd(window)).ok()
+    }
+
+    fn place(&mut self, window: isize, rect: Rect) -> Result<()> {
+        window_ops::place_window(window_ops::raw_to_hwnd(window), rect)
+    }
+
+    fn focus(&mut self, window: isize) -> Result<()> {
+        if !unsafe { SetForegroundWindow(window_ops::raw_to_hwnd(window)) }.as_bool() {
+            return Err(anyhow::anyhow!("Could not focus window"));
+        }
+        Ok(())
+    }
+}
+
+/// A desktop held in memory that records what is done to it
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    /// Most recently used first, like `app_windows`
+    pub windows: Vec<WindowInfo>,
+    pub monitors: Vec<MonitorInfo>,
+    pub foreground: Option<isize>,
+    /// Every `place` call, in order
+    pub placements: Vec<(isize, Rect)>,
+}
+
+#[cfg(test)]
+impl WindowBackend for FakeBackend {
+    fn app_windows(&self) -> Vec<WindowInfo> {
+        self.windows.clone()
+    }
+
+    fn window(&self, window: isize) -> Option<WindowInfo> {
+        self.windows.iter().find(|info| info.hwnd == window).cloned()
+    }
+
+    fn is_alive(&self, window: isize) -> bool {
+        self.windows.iter().any(|info| info.hwnd == window)
+    }
+
+    fn foreground(&self) -> Option<isize> {
+        self.foreground
+    }
+
+    fn monitors(&self) -> Vec<MonitorInfo> {
+        self.monitors.clone()
+    }
+
+    fn monitor_of(&self, window: isize) -> Option<MonitorInfo> {
+        let (x, y) = self.window(window)?.rect.center();
+        self.monitors.iter().find(|monitor| monitor.bounds.contains(x, y)).cloned()
+    }
+
+    fn place(&mut self, window: isize, rect: Rect) -> Result<()> {
+        let info = self
+            .windows
+            .iter_mut()
+            .find(|info| info.hwnd == window)
+            .ok_or_else(|| anyhow::anyhow!("No window {:#x}", window))?;
+        info.rect = rect;
+        self.placements.push((window, rect));
+        Ok(())
+    }
+
+    fn focus(&mut self, window: isize) -> Result<()> {
+        if !self.is_alive(window) {
+            return Err(anyhow::anyhow!("Could not focus window"));
+        }
+        self.foreground = Some(window);
+        Ok(())
+    }
+}
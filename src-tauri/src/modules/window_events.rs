@@ -0,0 +1,212 @@
+use std::cell::RefCell;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use anyhow::Result;
+
+use windows::core::w;
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetAncestor, GetMessageW,
+    PostThreadMessageW, RegisterClassW, TranslateMessage, CHILDID_SELF, EVENT_OBJECT_DESTROY,
+    EVENT_OBJECT_HIDE, EVENT_OBJECT_SHOW, EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_MINIMIZEEND,
+    EVENT_SYSTEM_MINIMIZESTART, EVENT_SYSTEM_MOVESIZEEND, EVENT_SYSTEM_MOVESIZESTART, GA_ROOT, MSG, OBJID_WINDOW, WINEVENT_OUTOFCONTEXT,
+    WINEVENT_SKIPOWNPROCESS, WM_DISPLAYCHANGE, WM_DPICHANGED, WM_QUIT, WNDCLASSW,
+    WS_EX_TOOLWINDOW, WS_POPUP,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowEventKind {
+    /// A top-level window was shown, usually right after it was created
+    Created,
+    /// A window became the foreground window
+    Focused,
+    /// A window was hidden, which includes being closed
+    Hidden,
+    /// A window was destroyed; the handle is no longer valid
+    Destroyed,
+    Minimized,
+    /// A minimized window was restored
+    Restored,
+    /// The user started dragging a window's title bar or border
+    MoveSizeStart,
+    /// The user released a window being dragged
+    MoveSizeEnd,
+    /// Monitors were added, removed or rearranged, or their resolution or
+    /// scaling changed. `hwnd` is 0.
+    DisplayChanged,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowEvent {
+    pub kind: WindowEventKind,
+    pub hwnd: isize,
+}
+
+/// Callback a module installs to receive window events. Runs on the watcher thread.
+pub type WindowEventHandler = Arc<dyn Fn(WindowEvent) + Send + Sync>;
+
+type EventCallback = Box<dyn Fn(WindowEvent)>;
+
+thread_local! {
+    // Out-of-context WinEvent callbacks run on the thread that installed the
+    // hook, so each watcher thread keeps its own callback here.
+    static CALLBACK: RefCell<Option<EventCallback>> = RefCell::new(None);
+}
+
+const WATCHED_EVENTS: &[(u32, WindowEventKind)] = &[
+    (EVENT_OBJECT_SHOW, WindowEventKind::Created),
+    (EVENT_SYSTEM_FOREGROUND, WindowEventKind::Focused),
+    (EVENT_OBJECT_HIDE, WindowEventKind::Hidden),
+    (EVENT_OBJECT_DESTROY, WindowEventKind::Destroyed),
+    (EVENT_SYSTEM_MINIMIZESTART, WindowEventKind::Minimized),
+    (EVENT_SYSTEM_MINIMIZEEND, WindowEventKind::Restored),
+    (EVENT_SYSTEM_MOVESIZESTART, WindowEventKind::MoveSizeStart),
+    (EVENT_SYSTEM_MOVESIZEEND, WindowEventKind::MoveSizeEnd),
+];
+
+unsafe extern "system" fn on_win_event(
+    _hook: HWINEVENTHOOK,
+    event: u32,
+    hwnd: HWND,
+    id_object: i32,
+    id_child: i32,
+    _event_thread: u32,
+    _event_time: u32,
+) {
+    // Only whole top-level windows, not their controls, carets or menus
+    if hwnd.0.is_null() || id_object != OBJID_WINDOW.0 || id_child != CHILDID_SELF as i32 {
+        return;
+    }
+
+    let Some(kind) = WATCHED_EVENTS.iter().find(|(id, _)| *id == event).map(|(_, kind)| *kind) else {
+        return;
+    };
+
+    // A destroyed window has no ancestors left to check
+    if kind != WindowEventKind::Destroyed && GetAncestor(hwnd, GA_ROOT) != hwnd {
+        return;
+    }
+
+    CALLBACK.with(|callback| {
+        if let Some(callback) = callback.borrow().as_ref() {
+            callback(WindowEvent { kind, hwnd: hwnd.0 as isize });
+        }
+    });
+}
+
+// Display changes aren't WinEvents; they are broadcast to top-level windows,
+// so each watcher thread keeps a hidden one to receive them
+unsafe extern "system" fn display_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if msg == WM_DISPLAYCHANGE || msg == WM_DPICHANGED {
+        CALLBACK.with(|callback| {
+            if let Some(callback) = callback.borrow().as_ref() {
+                callback(WindowEvent { kind: WindowEventKind::DisplayChanged, hwnd: 0 });
+            }
+        });
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+/// Watches other processes' top-level windows come and go, delivering
+/// events to a callback on a dedicated message-loop thread
+pub struct WindowEventWatcher {
+    thread_id: u32,
+}
+
+impl WindowEventWatcher {
+    pub fn start<F>(on_event: F) -> Result<Self>
+    where
+        F: Fn(WindowEvent) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<Result<u32, String>>();
+
+        thread::spawn(move || unsafe {
+            CALLBACK.with(|callback| *callback.borrow_mut() = Some(Box::new(on_event)));
+
+            let mut hooks = Vec::with_capacity(WATCHED_EVENTS.len());
+            for (event, _) in WATCHED_EVENTS {
+                let hook = SetWinEventHook(
+                    *event,
+                    *event,
+                    None,
+                    Some(on_win_event),
+                    0,
+                    0,
+                    WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+                );
+                if hook.is_invalid() {
+                    for hook in hooks {
+                        let _ = UnhookWinEvent(hook);
+                    }
+                    let _ = tx.send(Err(format!("Could not hook window event {:#x}", event)));
+                    return;
+                }
+                hooks.push(hook);
+            }
+
+            // Without it the watcher still works, it just never reports display changes
+            let display_window = GetModuleHandleW(None).ok().and_then(|instance| {
+                let class = WNDCLASSW {
+                    lpfnWndProc: Some(display_proc),
+                    hInstance: instance.into(),
+                    lpszClassName: w!("WinShaperDisplayWatcher"),
+                    ..Default::default()
+                };
+                // Registering again from another watcher fails harmlessly
+                RegisterClassW(&class);
+                CreateWindowExW(
+                    WS_EX_TOOLWINDOW,
+                    w!("WinShaperDisplayWatcher"),
+                    w!(""),
+                    WS_POPUP,
+                    0,
+                    0,
+                    0,
+                    0,
+                    None,
+                    None,
+                    instance,
+                    None,
+                )
+                .ok()
+            });
+            if display_window.is_none() {
+                log::warn!("Could not create display change window; monitor changes won't be noticed");
+            }
+            let _ = tx.send(Ok(GetCurrentThreadId()));
+
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, HWND::default(), 0, 0).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+
+            if let Some(window) = display_window {
+                let _ = DestroyWindow(window);
+            }
+            for hook in hooks {
+                let _ = UnhookWinEvent(hook);
+            }
+            CALLBACK.with(|callback| *callback.borrow_mut() = None);
+        });
+
+        let thread_id = rx
+            .recv()
+            .map_err(|_| anyhow::anyhow!("Window event thread exited unexpectedly"))?
+            .map_err(|e| anyhow::anyhow!(e))?;
+
+        Ok(Self { thread_id })
+    }
+
+    /// Ask the watcher thread to unhook and exit. This does not wait for it:
+    /// callers usually hold the lock the callback needs, and a callback
+    /// already in flight must be able to finish.
+    pub fn stop(self) {
+        unsafe {
+            let _ = PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+        }
+    }
+}
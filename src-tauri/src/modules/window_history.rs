@@ -0,0 +1,60 @@
+use super::window_ops::WindowGeometry;
+use std::collections::HashMap;
+
+/// Undo steps kept per window. The oldest entry, the window's original
+/// geometry, is never dropped.
+pub const HISTORY_LIMIT: usize = 20;
+
+/// Geometry each window had before DynamicSplit moved it, newest last
+#[derive(Debug, Default)]
+pub struct MoveHistory {
+    entries: HashMap<isize, Vec<WindowGeometry>>,
+}
+
+impl MoveHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember where a window was before it is moved. Repeating the same
+    /// geometry adds no step, so undo never appears to do nothing.
+    pub fn record(&mut self, window: isize, geometry: WindowGeometry) {
+        let stack = self.entries.entry(window).or_default();
+        if stack.last() == Some(&geometry) {
+            return;
+        }
+        stack.push(geometry);
+        if stack.len() > HISTORY_LIMIT {
+            stack.remove(1);
+        }
+    }
+
+    /// Take the most recent step back for a window
+    pub fn undo(&mut self, window: isize) -> Option<WindowGeometry> {
+        let stack = self.entries.get_mut(&window)?;
+        let geometry = stack.pop();
+        if stack.is_empty() {
+            self.entries.remove(&window);
+        }
+        geometry
+    }
+
+    /// Forget a window's history and return the geometry it had before it was
+    /// first moved
+    pub fn take_original(&mut self, window: isize) -> Option<WindowGeometry> {
+        self.entries.remove(&window).and_then(|stack| stack.into_iter().next())
+    }
+
+    /// Forget all history, returning every window's original geometry
+    pub fn take_all_originals(&mut self) -> Vec<(isize, WindowGeometry)> {
+        self.entries
+            .drain()
+            .filter_map(|(window, stack)| stack.into_iter().next().map(|geometry| (window, geometry)))
+            .collect()
+    }
+
+    /// Drop the history of windows that no longer exist
+    pub fn retain_windows(&mut self, mut alive: impl FnMut(isize) -> bool) {
+        self.entries.retain(|window, _| alive(*window));
+    }
+}
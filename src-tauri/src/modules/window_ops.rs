@@ -0,0 +1,361 @@
+use super::layout_engine::Rect;
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use windows::core::PWSTR;
+use windows::Win32::Foundation::{CloseHandle, BOOL, HWND, LPARAM, RECT};
+use windows::Win32::Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED, DWMWA_EXTENDED_FRAME_BOUNDS};
+use windows::Win32::System::Threading::{
+    GetCurrentProcessId, OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+    PROCESS_QUERY_LIMITED_INFORMATION,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    EnumWindows, GetClassNameW, GetForegroundWindow, GetWindow, GetWindowLongW, GetWindowPlacement,
+    GetWindowRect, GetWindowTextW, GetWindowThreadProcessId, IsIconic, IsWindow, IsWindowVisible,
+    IsZoomed, SetWindowPlacement, SetWindowPos, ShowWindow, GWL_EXSTYLE, GW_OWNER, SWP_NOACTIVATE,
+    SWP_NOZORDER, SWP_SHOWWINDOW, SW_MAXIMIZE, SW_RESTORE, SW_SHOWMINIMIZED, SW_SHOWMINNOACTIVE,
+    SW_SHOWNOACTIVATE, SW_SHOWNORMAL, WINDOWPLACEMENT, WS_EX_TOOLWINDOW,
+};
+
+/// A top-level application window as seen by the window management features
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowInfo {
+    pub hwnd: isize,
+    pub pid: u32,
+    /// Executable file name, e.g. `Code.exe`
+    pub exe: String,
+    /// Full path of the executable, empty if the process could not be queried
+    pub exe_path: String,
+    pub class_name: String,
+    pub title: String,
+    pub rect: Rect,
+    pub maximized: bool,
+    pub minimized: bool,
+}
+
+/// Selects windows by executable, class and title. Unset fields match anything;
+/// `exe` and `class_name` compare case-insensitively, `title` is a regex.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowMatcher {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exe: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<TitlePattern>,
+}
+
+/// Case-insensitive title regex, compiled once when the matcher is parsed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TitlePattern {
+    source: String,
+    regex: Regex,
+}
+
+impl TitlePattern {
+    pub fn new(source: &str) -> Result<Self> {
+        let regex = RegexBuilder::new(source)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Invalid title pattern '{}': {}", source, e))?;
+        Ok(Self { source: source.to_string(), regex })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, title: &str) -> bool {
+        self.regex.is_match(title)
+    }
+}
+
+impl PartialEq for TitlePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl TryFrom<String> for TitlePattern {
+    type Error = anyhow::Error;
+
+    fn try_from(source: String) -> Result<Self> {
+        TitlePattern::new(&source)
+    }
+}
+
+impl From<TitlePattern> for String {
+    fn from(pattern: TitlePattern) -> Self {
+        pattern.source
+    }
+}
+
+impl WindowMatcher {
+    pub fn matches(&self, window: &WindowInfo) -> bool {
+        if let Some(exe) = &self.exe {
+            if !exe.eq_ignore_ascii_case(&window.exe) {
+                return false;
+            }
+        }
+        if let Some(class_name) = &self.class_name {
+            if !class_name.eq_ignore_ascii_case(&window.class_name) {
+                return false;
+            }
+        }
+        if let Some(title) = &self.title {
+            if !title.is_match(&window.title) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl From<RECT> for Rect {
+    fn from(rect: RECT) -> Self {
+        Rect {
+            x: rect.left,
+            y: rect.top,
+            width: rect.right - rect.left,
+            height: rect.bottom - rect.top,
+        }
+    }
+}
+
+/// Window handles cross threads and live in maps as plain integers
+pub fn hwnd_to_raw(hwnd: HWND) -> isize {
+    hwnd.0 as isize
+}
+
+pub fn raw_to_hwnd(raw: isize) -> HWND {
+    HWND(raw as *mut _)
+}
+
+pub fn foreground_window() -> Result<HWND> {
+    let hwnd = unsafe { GetForegroundWindow() };
+    if hwnd.0.is_null() {
+        return Err(anyhow::anyhow!("No foreground window found"));
+    }
+    Ok(hwnd)
+}
+
+pub fn is_window(hwnd: HWND) -> bool {
+    unsafe { IsWindow(hwnd).as_bool() }
+}
+
+fn read_text(read: impl FnOnce(&mut [u16]) -> i32) -> String {
+    let mut buffer = [0u16; 512];
+    let len = read(&mut buffer).max(0) as usize;
+    String::from_utf16_lossy(&buffer[..len])
+}
+
+fn process_path(pid: u32) -> String {
+    unsafe {
+        let Ok(process) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) else {
+            return String::new();
+        };
+
+        let mut buffer = [0u16; 1024];
+        let mut len = buffer.len() as u32;
+        let path = match QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, PWSTR(buffer.as_mut_ptr()), &mut len) {
+            Ok(()) => String::from_utf16_lossy(&buffer[..len as usize]),
+            Err(_) => String::new(),
+        };
+        let _ = CloseHandle(process);
+        path
+    }
+}
+
+/// Describe a window, whatever its kind
+pub fn window_info(hwnd: HWND) -> Result<WindowInfo> {
+    if !is_window(hwnd) {
+        return Err(anyhow::anyhow!("Window no longer exists"));
+    }
+
+    let mut pid = 0u32;
+    unsafe {
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+    }
+
+    let exe_path = process_path(pid);
+    let exe = exe_path.rsplit('\\').next().unwrap_or_default().to_string();
+
+    Ok(WindowInfo {
+        hwnd: hwnd_to_raw(hwnd),
+        pid,
+        exe,
+        exe_path,
+        class_name: read_text(|buffer| unsafe { GetClassNameW(hwnd, buffer) }),
+        title: read_text(|buffer| unsafe { GetWindowTextW(hwnd, buffer) }),
+        rect: visible_rect(hwnd)?,
+        maximized: unsafe { IsZoomed(hwnd).as_bool() },
+        minimized: unsafe { IsIconic(hwnd).as_bool() },
+    })
+}
+
+/// Whether a window is a regular application window a user would arrange:
+/// visible, titled, not owned, not a tool window and not cloaked
+pub fn is_app_window(hwnd: HWND) -> bool {
+    unsafe {
+        if !IsWindowVisible(hwnd).as_bool() || GetWindowTextW(hwnd, &mut [0u16; 2]) == 0 {
+            return false;
+        }
+        if GetWindow(hwnd, GW_OWNER).is_ok_and(|owner| !owner.0.is_null()) {
+            return false;
+        }
+        if GetWindowLongW(hwnd, GWL_EXSTYLE) as u32 & WS_EX_TOOLWINDOW.0 != 0 {
+            return false;
+        }
+
+        // UWP frames and windows on other virtual desktops are cloaked
+        let mut cloaked = 0u32;
+        let _ = DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_CLOAKED,
+            &mut cloaked as *mut u32 as *mut _,
+            std::mem::size_of::<u32>() as u32,
+        );
+        cloaked == 0
+    }
+}
+
+unsafe extern "system" fn collect_window(hwnd: HWND, data: LPARAM) -> BOOL {
+    let handles = &mut *(data.0 as *mut Vec<HWND>);
+    handles.push(hwnd);
+    BOOL(1)
+}
+
+/// Application windows of other processes, most recently used first
+/// (EnumWindows walks the Z order from the top)
+pub fn enumerate_windows() -> Result<Vec<WindowInfo>> {
+    let mut handles: Vec<HWND> = Vec::new();
+    unsafe { EnumWindows(Some(collect_window), LPARAM(&mut handles as *mut _ as isize)) }
+        .map_err(|e| anyhow::anyhow!("EnumWindows failed: {}", e))?;
+
+    let own_pid = unsafe { GetCurrentProcessId() };
+    Ok(handles
+        .into_iter()
+        .filter(|hwnd| is_app_window(*hwnd))
+        .filter_map(|hwnd| window_info(hwnd).ok())
+        .filter(|window| window.pid != own_pid)
+        .collect())
+}
+
+/// Outer window rectangle, including the invisible resize borders
+pub fn window_rect(hwnd: HWND) -> Result<Rect> {
+    let mut rect = RECT::default();
+    unsafe { GetWindowRect(hwnd, &mut rect) }
+        .map_err(|e| anyhow::anyhow!("GetWindowRect failed: {}", e))?;
+    Ok(rect.into())
+}
+
+/// Rectangle the user actually sees, without the invisible resize borders
+pub fn visible_rect(hwnd: HWND) -> Result<Rect> {
+    let mut rect = RECT::default();
+    let visible = unsafe {
+        DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut rect as *mut RECT as *mut _,
+            std::mem::size_of::<RECT>() as u32,
+        )
+    };
+    match visible {
+        Ok(()) => Ok(rect.into()),
+        Err(_) => window_rect(hwnd),
+    }
+}
+
+/// Move and resize a window so its visible frame fills `target`.
+/// Maximized and minimized windows are restored first, otherwise Windows
+/// ignores the move.
+pub fn place_window(hwnd: HWND, target: Rect) -> Result<()> {
+    unsafe {
+        if IsZoomed(hwnd).as_bool() || IsIconic(hwnd).as_bool() {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+    }
+
+    // Windows 10+ frames have invisible borders; grow the outer rect by them
+    // so neighbouring zones line up without visible gaps.
+    let outer = window_rect(hwnd)?;
+    let visible = visible_rect(hwnd)?;
+    let left = visible.x - outer.x;
+    let top = visible.y - outer.y;
+    let right = outer.right() - visible.right();
+    let bottom = outer.bottom() - visible.bottom();
+
+    unsafe {
+        SetWindowPos(
+            hwnd,
+            HWND(std::ptr::null_mut()),
+            target.x - left,
+            target.y - top,
+            target.width + left + right,
+            target.height + top + bottom,
+            SWP_NOZORDER | SWP_NOACTIVATE | SWP_SHOWWINDOW,
+        )
+        .map_err(|e| anyhow::anyhow!("SetWindowPos failed: {}", e))
+    }
+}
+
+/// Maximize or minimize a window, typically right after placing it so it
+/// restores to the placed rectangle
+pub fn set_window_state(hwnd: HWND, maximized: bool, minimized: bool) {
+    let command = if minimized {
+        SW_SHOWMINNOACTIVE
+    } else if maximized {
+        SW_MAXIMIZE
+    } else {
+        return;
+    };
+    unsafe {
+        let _ = ShowWindow(hwnd, command);
+    }
+}
+
+/// A window's restored rectangle and show state, as Windows itself keeps them.
+/// Restoring it puts back a maximized or minimized window together with the
+/// size it had before it was maximized or minimized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowGeometry(WINDOWPLACEMENT);
+
+pub fn window_geometry(hwnd: HWND) -> Result<WindowGeometry> {
+    let mut placement = WINDOWPLACEMENT {
+        length: std::mem::size_of::<WINDOWPLACEMENT>() as u32,
+        ..Default::default()
+    };
+    unsafe { GetWindowPlacement(hwnd, &mut placement) }
+        .map_err(|e| anyhow::anyhow!("GetWindowPlacement failed: {}", e))?;
+    Ok(WindowGeometry(placement))
+}
+
+/// Put a window back to a saved geometry without activating it
+pub fn restore_geometry(hwnd: HWND, geometry: &WindowGeometry) -> Result<()> {
+    let mut placement = geometry.0;
+    placement.showCmd = match placement.showCmd {
+        cmd if cmd == SW_SHOWNORMAL.0 as u32 => SW_SHOWNOACTIVATE.0 as u32,
+        cmd if cmd == SW_SHOWMINIMIZED.0 as u32 => SW_SHOWMINNOACTIVE.0 as u32,
+        cmd => cmd,
+    };
+    unsafe { SetWindowPlacement(hwnd, &placement) }
+        .map_err(|e| anyhow::anyhow!("SetWindowPlacement failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_patterns_are_checked_when_parsed() {
+        let matcher: WindowMatcher = serde_json::from_value(serde_json::json!({ "title": "^Visual Studio" })).unwrap();
+        assert_eq!(matcher.title.as_ref().map(TitlePattern::as_str), Some("^Visual Studio"));
+        assert_eq!(serde_json::to_value(&matcher).unwrap(), serde_json::json!({ "title": "^Visual Studio" }));
+
+        let invalid = serde_json::from_value::<WindowMatcher>(serde_json::json!({ "title": "(" }));
+        assert!(invalid.unwrap_err().to_string().contains("Invalid title pattern"));
+    }
+}
@@ -0,0 +1,108 @@
+use super::window_ops::{WindowInfo, WindowMatcher};
+use std::fs;
+use std::path::Path;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Current rules file format version
+pub const RULES_VERSION: u32 = 1;
+
+fn default_true() -> bool {
+    true
+}
+
+/// Put windows of a given application into a layout zone automatically
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutRule {
+    pub name: String,
+    #[serde(flatten)]
+    pub matcher: WindowMatcher,
+    pub layout: String,
+    #[serde(default)]
+    pub zone: usize,
+    /// Monitor id to place the window on; defaults to the window's own monitor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+    /// Higher priority rules are tried first
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Re-apply every time the window is focused, not just the first time
+    #[serde(default)]
+    pub reapply_on_focus: bool,
+}
+
+impl LayoutRule {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Rule name must not be empty"));
+        }
+        if self.matcher == WindowMatcher::default() {
+            return Err(anyhow::anyhow!("Rule '{}' must match on exe, class or title", self.name));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RulesFile {
+    version: u32,
+    rules: Vec<LayoutRule>,
+}
+
+/// Order rules by descending priority; rules with equal priority keep their order
+pub fn sort_rules(rules: &mut [LayoutRule]) {
+    rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+}
+
+/// The highest priority enabled rule matching `window`. Expects sorted rules.
+pub fn find_rule<'a>(rules: &'a [LayoutRule], window: &WindowInfo) -> Option<&'a LayoutRule> {
+    rules
+        .iter()
+        .find(|rule| rule.enabled && rule.matcher.matches(window))
+}
+
+/// Add `incoming` rules to `rules`, replacing rules with the same name.
+/// Returns the number of rules added or replaced.
+pub fn merge_rules(rules: &mut Vec<LayoutRule>, incoming: Vec<LayoutRule>) -> usize {
+    let count = incoming.len();
+    for rule in incoming {
+        match rules.iter_mut().find(|existing| existing.name == rule.name) {
+            Some(existing) => *existing = rule,
+            None => rules.push(rule),
+        }
+    }
+    sort_rules(rules);
+    count
+}
+
+pub fn export_rules(path: &Path, rules: &[LayoutRule]) -> Result<()> {
+    let file = RulesFile {
+        version: RULES_VERSION,
+        rules: rules.to_vec(),
+    };
+    fs::write(path, serde_json::to_string_pretty(&file)?)?;
+    log::info!("Exported {} layout rules to {:?}", rules.len(), path);
+    Ok(())
+}
+
+/// Read and validate rules from a JSON file written by `export_rules`
+pub fn import_rules(path: &Path) -> Result<Vec<LayoutRule>> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Could not read rules file: {}", e))?;
+    let file: RulesFile = serde_json::from_str(&json)?;
+
+    if file.version > RULES_VERSION {
+        return Err(anyhow::anyhow!(
+            "Rules file version {} is newer than supported version {}",
+            file.version,
+            RULES_VERSION
+        ));
+    }
+
+    for rule in &file.rules {
+        rule.validate()?;
+    }
+    Ok(file.rules)
+}
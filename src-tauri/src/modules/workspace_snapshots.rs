@@ -0,0 +1,268 @@
+use super::layout_engine::Rect;
+use super::monitors::{self, MonitorInfo};
+use super::window_ops::WindowInfo;
+use std::fs;
+use std::path::PathBuf;
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+/// Where one window was when the snapshot was taken
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowSnapshot {
+    pub exe: String,
+    /// Used to launch the app if it is not running at restore time
+    pub exe_path: String,
+    pub class_name: String,
+    pub title: String,
+    /// Id of the monitor the window was on
+    pub monitor: String,
+    pub rect: Rect,
+    pub maximized: bool,
+    pub minimized: bool,
+}
+
+/// A named arrangement of all top-level windows, with the monitor set it was
+/// taken on so it can be mapped onto a different one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceSnapshot {
+    pub name: String,
+    pub created_at: i64,
+    pub monitors: Vec<MonitorInfo>,
+    pub windows: Vec<WindowSnapshot>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RestoreReport {
+    /// Titles of windows that were moved into place
+    pub restored: Vec<String>,
+    /// Executables started because no matching window was open
+    pub launched: Vec<String>,
+    /// Snapshot windows with no match that were not launched
+    pub missing: Vec<String>,
+}
+
+impl WorkspaceSnapshot {
+    pub fn capture(name: &str, monitors: Vec<MonitorInfo>, windows: &[WindowInfo]) -> Self {
+        let snapshot_windows = windows
+            .iter()
+            .map(|window| {
+                let (center_x, center_y) = window.rect.center();
+                let monitor = monitors
+                    .iter()
+                    .find(|monitor| monitor.bounds.contains(center_x, center_y))
+                    .or_else(|| monitors.iter().find(|monitor| monitor.primary))
+                    .map(|monitor| monitor.id.clone())
+                    .unwrap_or_default();
+
+                WindowSnapshot {
+                    exe: window.exe.clone(),
+                    exe_path: window.exe_path.clone(),
+                    class_name: window.class_name.clone(),
+                    title: window.title.clone(),
+                    monitor,
+                    rect: window.rect,
+                    maximized: window.maximized,
+                    minimized: window.minimized,
+                }
+            })
+            .collect();
+
+        Self {
+            name: name.to_string(),
+            created_at: Utc::now().timestamp(),
+            monitors,
+            windows: snapshot_windows,
+        }
+    }
+
+    /// Where a snapshot window should go on the current monitors. Windows keep
+    /// their exact rectangle if their monitor is still there with the same work
+    /// area; otherwise the rectangle is scaled into the work area of the same
+    /// monitor or, if it is gone, the primary monitor.
+    pub fn target_rect(&self, window: &WindowSnapshot, current: &[MonitorInfo]) -> Rect {
+        let Some(old) = self.monitors.iter().find(|monitor| monitor.id == window.monitor) else {
+            return window.rect;
+        };
+
+        let target = current
+            .iter()
+            .find(|monitor| monitor.id == old.id)
+            .or_else(|| current.iter().find(|monitor| monitor.primary))
+            .or_else(|| current.first());
+
+        match target {
+            Some(target) if target.work_area == old.work_area => window.rect,
+            Some(target) => monitors::translate_rect(window.rect, old.work_area, target.work_area),
+            None => window.rect,
+        }
+    }
+}
+
+/// Identifies a set of monitors and their arrangement, to notice docking changes
+pub fn monitor_set_key(monitors: &[MonitorInfo]) -> String {
+    monitors
+        .iter()
+        .map(|monitor| {
+            let b = monitor.bounds;
+            format!("{}@{},{},{}x{}", monitor.id, b.x, b.y, b.width, b.height)
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Pair snapshot windows with open windows. Matching runs in passes from
+/// strictest (same exe, class and title) to loosest (same exe) so an exact
+/// match is never taken by a looser one; each open window is used once.
+pub fn match_windows(snapshot: &[WindowSnapshot], open: &[WindowInfo]) -> Vec<Option<usize>> {
+    type Pass = fn(&WindowSnapshot, &WindowInfo) -> bool;
+    let passes: [Pass; 3] = [
+        |s, w| s.class_name == w.class_name && s.title == w.title,
+        |s, w| s.class_name == w.class_name,
+        |_, _| true,
+    ];
+
+    let mut used = vec![false; open.len()];
+    let mut matched: Vec<Option<usize>> = vec![None; snapshot.len()];
+
+    for pass in passes {
+        for (slot, saved) in matched.iter_mut().zip(snapshot) {
+            if slot.is_some() {
+                continue;
+            }
+            *slot = (0..open.len()).find(|&index| {
+                !used[index] && saved.exe.eq_ignore_ascii_case(&open[index].exe) && pass(saved, &open[index])
+            });
+            if let Some(index) = *slot {
+                used[index] = true;
+            }
+        }
+    }
+
+    matched
+}
+
+/// Named snapshots persisted as one JSON file in the app data directory
+pub struct SnapshotStore {
+    path: PathBuf,
+    snapshots: Vec<WorkspaceSnapshot>,
+}
+
+impl SnapshotStore {
+    pub fn default_path() -> Result<PathBuf> {
+        let app_data = dirs::data_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not find app data directory"))?;
+        Ok(app_data.join("WinShaper").join("workspaces.json"))
+    }
+
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let snapshots = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self { path, snapshots })
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&self.snapshots)?)?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> &[WorkspaceSnapshot] {
+        &self.snapshots
+    }
+
+    pub fn get(&self, name: &str) -> Option<&WorkspaceSnapshot> {
+        self.snapshots.iter().find(|snapshot| snapshot.name == name)
+    }
+
+    /// Store a snapshot, replacing any existing one with the same name
+    pub fn insert(&mut self, snapshot: WorkspaceSnapshot) -> Result<()> {
+        self.snapshots.retain(|existing| existing.name != snapshot.name);
+        self.snapshots.push(snapshot);
+        self.save()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        let before = self.snapshots.len();
+        self.snapshots.retain(|snapshot| snapshot.name != name);
+        if self.snapshots.len() == before {
+            return Err(anyhow::anyhow!("Workspace '{}' not found", name));
+        }
+        self.save()
+    }
+
+    /// Most recent snapshot taken on exactly this monitor set
+    pub fn latest_for_monitors(&self, key: &str) -> Option<&WorkspaceSnapshot> {
+        self.snapshots
+            .iter()
+            .filter(|snapshot| monitor_set_key(&snapshot.monitors) == key)
+            .max_by_key(|snapshot| snapshot.created_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECT: Rect = Rect { x: 0, y: 0, width: 100, height: 100 };
+
+    fn saved(exe: &str, class_name: &str, title: &str) -> WindowSnapshot {
+        WindowSnapshot {
+            exe: exe.to_string(),
+            exe_path: String::new(),
+            class_name: class_name.to_string(),
+            title: title.to_string(),
+            monitor: String::new(),
+            rect: RECT,
+            maximized: false,
+            minimized: false,
+        }
+    }
+
+    fn open(hwnd: isize, exe: &str, class_name: &str, title: &str) -> WindowInfo {
+        WindowInfo {
+            hwnd,
+            pid: 1,
+            exe: exe.to_string(),
+            exe_path: String::new(),
+            class_name: class_name.to_string(),
+            title: title.to_string(),
+            rect: RECT,
+            maximized: false,
+            minimized: false,
+        }
+    }
+
+    #[test]
+    fn exact_matches_win_over_looser_ones() {
+        // The first saved window would take the exact match of the second
+        // if matching were done one window at a time
+        let snapshot = [
+            saved("Code.exe", "Chrome_WidgetWin_1", "notes"),
+            saved("Code.exe", "Chrome_WidgetWin_1", "main.rs"),
+        ];
+        let windows = [
+            open(1, "code.exe", "Chrome_WidgetWin_1", "main.rs"),
+            open(2, "Code.exe", "Chrome_WidgetWin_1", "todo"),
+        ];
+
+        assert_eq!(match_windows(&snapshot, &windows), vec![Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn each_open_window_is_used_once() {
+        let snapshot = [
+            saved("notepad.exe", "Notepad", "a"),
+            saved("notepad.exe", "Notepad", "b"),
+            saved("calc.exe", "CalcFrame", ""),
+        ];
+        let windows = [open(1, "explorer.exe", "CabinetWClass", "a"), open(2, "notepad.exe", "Notepad", "b")];
+
+        assert_eq!(match_windows(&snapshot, &windows), vec![None, Some(1), None]);
+    }
+}
@@ -0,0 +1,194 @@
+import { useState, useEffect, useRef, type FormEvent, type KeyboardEvent } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { getCurrentWindow } from '@tauri-apps/api/window';
+import './QuickPaste.css';
+
+interface ClipboardItem {
+  id: number;
+  content: string;
+  content_type: string;
+  timestamp: number;
+  preview: string;
+  pinned?: boolean;
+  abbreviation?: string;
+}
+
+// Snippet waiting for its {input:Name} values before it can be pasted
+interface PendingSnippet {
+  id: number;
+  names: string[];
+  values: Record<string, string>;
+}
+
+export function QuickPaste() {
+  const [items, setItems] = useState<ClipboardItem[]>([]);
+  const [query, setQuery] = useState('');
+  const [selected, setSelected] = useState(0);
+  const [pending, setPending] = useState<PendingSnippet | null>(null);
+  const searchRef = useRef<HTMLInputElement>(null);
+  // Set once the picker is closing, so the blur that follows doesn't cancel again
+  const closingRef = useRef(false);
+
+  const loadItems = async (text: string) => {
+    try {
+      const results = await invoke<ClipboardItem[]>('quick_paste_items', { query: text });
+      setItems(results);
+      setSelected(0);
+    } catch (error) {
+      console.error('Failed to load quick paste items:', error);
+    }
+  };
+
+  const reset = () => {
+    closingRef.current = false;
+    setQuery('');
+    setPending(null);
+    loadItems('');
+    searchRef.current?.focus();
+  };
+
+  const cancel = async () => {
+    if (closingRef.current) return;
+    closingRef.current = true;
+    try {
+      await invoke('quick_paste_cancel');
+    } catch (error) {
+      console.error('Failed to close quick paste:', error);
+    }
+  };
+
+  const paste = async (id: number, inputs: Record<string, string> = {}) => {
+    if (closingRef.current) return;
+    closingRef.current = true;
+    try {
+      await invoke('quick_paste_select', { id, inputs });
+    } catch (error) {
+      console.error('Failed to paste item:', error);
+    }
+  };
+
+  const choose = async (index: number) => {
+    const item = items[index];
+    if (!item) return;
+
+    if (item.content_type === 'snippet') {
+      try {
+        const names = await invoke<string[]>('get_snippet_inputs', { id: item.id });
+        if (names.length > 0) {
+          setPending({ id: item.id, names, values: {} });
+          return;
+        }
+      } catch (error) {
+        console.error('Failed to read snippet inputs:', error);
+      }
+    }
+    paste(item.id);
+  };
+
+  useEffect(() => {
+    reset();
+
+    // The backend reuses the window, so start over every time it is shown
+    const unlistenOpen = listen('quick-paste-open', reset);
+    const unlistenFocus = getCurrentWindow().onFocusChanged(({ payload: focused }) => {
+      if (!focused) cancel();
+    });
+
+    return () => {
+      unlistenOpen.then((unlisten) => unlisten());
+      unlistenFocus.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  const handleKeyDown = (e: KeyboardEvent) => {
+    if (e.key === 'Escape') {
+      e.preventDefault();
+      cancel();
+      return;
+    }
+    if (pending) return;
+
+    // Digits pick by position; while typing a query they need Ctrl or Alt
+    if (/^[1-9]$/.test(e.key) && (query === '' || e.ctrlKey || e.altKey)) {
+      e.preventDefault();
+      choose(Number(e.key) - 1);
+      return;
+    }
+
+    switch (e.key) {
+      case 'ArrowDown':
+        e.preventDefault();
+        setSelected((index) => Math.min(index + 1, items.length - 1));
+        break;
+      case 'ArrowUp':
+        e.preventDefault();
+        setSelected((index) => Math.max(index - 1, 0));
+        break;
+      case 'Enter':
+        e.preventDefault();
+        choose(selected);
+        break;
+    }
+  };
+
+  const handlePendingSubmit = (e: FormEvent) => {
+    e.preventDefault();
+    if (pending) paste(pending.id, pending.values);
+  };
+
+  return (
+    <div className="quick-paste" onKeyDown={handleKeyDown}>
+      {pending ? (
+        <form className="quick-paste-inputs" onSubmit={handlePendingSubmit}>
+          {pending.names.map((name, index) => (
+            <label key={name}>
+              {name}
+              <input
+                type="text"
+                autoFocus={index === 0}
+                value={pending.values[name] ?? ''}
+                onChange={(e) =>
+                  setPending({ ...pending, values: { ...pending.values, [name]: e.target.value } })
+                }
+              />
+            </label>
+          ))}
+          <button type="submit">Paste</button>
+        </form>
+      ) : (
+        <>
+          <input
+            ref={searchRef}
+            type="text"
+            className="quick-paste-search"
+            placeholder="Type to filter, 1-9 to paste..."
+            value={query}
+            autoFocus
+            onChange={(e) => {
+              setQuery(e.target.value);
+              loadItems(e.target.value);
+            }}
+          />
+          <ul className="quick-paste-list">
+            {items.length === 0 && <li className="quick-paste-empty">No matching items</li>}
+            {items.map((item, index) => (
+              <li
+                key={item.id}
+                className={index === selected ? 'selected' : ''}
+                onMouseEnter={() => setSelected(index)}
+                onClick={() => choose(index)}
+              >
+                <span className="quick-paste-key">{index < 9 ? index + 1 : ''}</span>
+                <span className="quick-paste-preview">
+                  {item.abbreviation ? <em>{item.abbreviation} </em> : null}
+                  {item.preview}
+                </span>
+              </li>
+            ))}
+          </ul>
+        </>
+      )}
+    </div>
+  );
+}
@@ -1,9 +1,13 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
 import App from "./App";
+import { QuickPaste } from "./components/QuickPaste";
+
+// The quick paste picker is a separate window loading index.html#quick-paste
+const isQuickPaste = window.location.hash === "#quick-paste";
 
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
-    <App />
+    {isQuickPaste ? <QuickPaste /> : <App />}
   </React.StrictMode>,
 );